@@ -0,0 +1,110 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::{Component, Path};
+use std::sync::Mutex;
+
+use pdf_core::{Block, Config, FsResolver, PROJECT_CONFIG};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::Document;
+
+/// Zip the markdown, the nearest `config.toml`, the images it refers to and
+/// the rendered PDF into one archive at `path`.
+///
+/// Files are stored under the paths the document uses, so the archive renders
+/// the same once unpacked. Images outside the document's folder (absolute or
+/// `../` paths) can't be stored that way and are left out, as are remote
+/// images and data URIs.
+#[tauri::command]
+pub fn export_bundle(
+    renderer: tauri::State<'_, pdf_core::Renderer>,
+    document: tauri::State<'_, Mutex<Document>>,
+    markdown: &str,
+    path: &str,
+) -> Result<(), String> {
+    let source = document
+        .lock()
+        .map_err(|e| e.to_string())?
+        .path
+        .clone()
+        .ok_or("Save the document first so its images and config can be found")?;
+    let dir = source.parent().unwrap_or(Path::new("."));
+    let stem = source
+        .file_stem()
+        .map_or("document".into(), |stem| stem.to_string_lossy());
+
+    let renderer = crate::document_renderer(&renderer, &document, markdown)?
+        .with_resolver(FsResolver::new(dir));
+    let pdf = renderer.render_pdf(markdown)?;
+
+    let mut files: Vec<(String, Vec<u8>)> = vec![
+        (format!("{}.md", stem), markdown.as_bytes().to_vec()),
+        (format!("{}.pdf", stem), pdf),
+    ];
+    if let Some(config) = Config::find_project_config(&source) {
+        let bytes = std::fs::read(&config).map_err(|e| format!("{}: {}", config.display(), e))?;
+        files.push((PROJECT_CONFIG.to_string(), bytes));
+    }
+    for image in local_files(markdown, renderer.config()) {
+        if files.iter().any(|(name, _)| *name == image) {
+            continue;
+        }
+        let file = dir.join(&image);
+        let bytes = std::fs::read(&file).map_err(|e| format!("{}: {}", file.display(), e))?;
+        files.push((image, bytes));
+    }
+
+    write_zip(Path::new(path), &files)
+}
+
+/// Archive names of the images and background image the document reads from
+/// its own folder.
+fn local_files(markdown: &str, config: &Config) -> Vec<String> {
+    let images = pdf_core::parse(markdown)
+        .into_iter()
+        .filter_map(|block| match block {
+            Block::Image { path, .. } => Some(path),
+            _ => None,
+        });
+    config
+        .page
+        .background_image
+        .clone()
+        .into_iter()
+        .chain(images)
+        .filter_map(|path| archive_name(&path))
+        .collect()
+}
+
+/// `path` with `/` separators and without `./`, or `None` unless it is a
+/// relative file path that stays inside its folder, which also rules out URLs
+/// and data URIs.
+fn archive_name(path: &str) -> Option<String> {
+    if path.contains(':') {
+        return None;
+    }
+    let mut parts = Vec::new();
+    for component in Path::new(path).components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_string_lossy()),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    Some(parts.join("/"))
+}
+
+fn write_zip(path: &Path, files: &[(String, Vec<u8>)]) -> Result<(), String> {
+    let in_file = |e: String| format!("{}: {}", path.display(), e);
+    let file = File::create(path).map_err(|e| in_file(e.to_string()))?;
+    let mut zip = ZipWriter::new(file);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    for (name, bytes) in files {
+        zip.start_file(name.as_str(), options)
+            .map_err(|e| in_file(e.to_string()))?;
+        zip.write_all(bytes).map_err(|e| in_file(e.to_string()))?;
+    }
+    zip.finish().map_err(|e| in_file(e.to_string()))?;
+    Ok(())
+}
@@ -1,50 +1,460 @@
-use serde::Serialize;
+mod bundle;
+mod preview;
+mod settings;
+mod templates;
+
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use serde::{Deserialize, Serialize};
 use tauri::{
     menu::{MenuBuilder, MenuItemBuilder, SubmenuBuilder},
-    Emitter, Manager,
+    DragDropEvent, Emitter, Manager, WindowEvent,
+};
+use tauri_plugin_dialog::{
+    DialogExt, MessageDialogButtons, MessageDialogKind, MessageDialogResult,
 };
+use typst_kit::fonts::FontSearcher;
+use typst_library::text::{FontStyle, FontWeight};
+
+/// The markdown file being edited, tracked here so closing the window can
+/// ask about unsaved changes.
+#[derive(Default)]
+struct Document {
+    path: Option<PathBuf>,
+    modified: bool,
+    /// Commit checked out in the file's repository for `build_info.git_commit`,
+    /// looked up when the path changes rather than on every render
+    commit: Option<String>,
+}
+
+impl Document {
+    fn set_path(&mut self, path: PathBuf) {
+        if self.path.as_ref() != Some(&path) {
+            self.commit = pdf_core::git_commit(&path);
+            self.path = Some(path);
+        }
+    }
+}
+
+/// The shared renderer's config with the document's file named in the build
+/// info and the settings from the markdown's frontmatter (`pdf:`, `title:`,
+/// `author:`) applied, e.g. those of a template. Merged here rather than by
+/// the renderer so the export dialog's options can go on top.
+fn document_config(
+    renderer: &pdf_core::Renderer,
+    document: &Mutex<Document>,
+    markdown: &str,
+) -> Result<pdf_core::Config, String> {
+    let mut config = renderer.config().clone();
+    {
+        let document = document.lock().map_err(|e| e.to_string())?;
+        if let Some(path) = &document.path {
+            config.build_info.source = Some(path.display().to_string());
+            config.build_info.commit = document.commit.clone();
+        }
+    }
+    config.merge_frontmatter(markdown)?;
+    Ok(config)
+}
 
+/// The shared renderer with [`document_config`].
+fn document_renderer(
+    renderer: &pdf_core::Renderer,
+    document: &Mutex<Document>,
+    markdown: &str,
+) -> Result<pdf_core::Renderer, String> {
+    Ok(renderer.with_config(document_config(renderer, document, markdown)?))
+}
+
+/// A range of markdown lines and where it starts in the preview.
 #[derive(Serialize)]
-struct SvgDocument {
-    pages: Vec<String>,
-    width_pt: f64,
-    height_pt: f64,
+struct SourceRange {
+    start_line: usize,
+    end_line: usize,
+    /// 1-based page number
+    page: usize,
+    /// Distance from the top of the page, in points
+    y: f64,
 }
 
+/// Map the markdown's line ranges to their positions on the rendered pages,
+/// for scrolling the editor and the preview in sync.
 #[tauri::command]
-fn render_markdown_to_svg(markdown: &str) -> Result<SvgDocument, String> {
-    let doc = pdf_core::markdown_to_svg(markdown)?;
-    Ok(SvgDocument {
-        pages: doc.pages,
-        width_pt: doc.width_pt,
-        height_pt: doc.height_pt,
+async fn source_map(
+    renderer: tauri::State<'_, pdf_core::Renderer>,
+    document: tauri::State<'_, Mutex<Document>>,
+    markdown: String,
+) -> Result<Vec<SourceRange>, String> {
+    let positions =
+        document_renderer(&renderer, &document, &markdown)?.source_positions(&markdown)?;
+    Ok(positions
+        .into_iter()
+        .map(|position| SourceRange {
+            start_line: position.start_line,
+            end_line: position.end_line,
+            page: position.page,
+            y: position.y,
+        })
+        .collect())
+}
+
+/// Counts for the status bar and the headings for the outline sidebar.
+#[derive(Serialize)]
+struct DocumentStats {
+    words: usize,
+    characters: usize,
+    pages: usize,
+    reading_minutes: usize,
+    outline: Vec<OutlineItem>,
+}
+
+#[derive(Serialize)]
+struct OutlineItem {
+    level: u8,
+    title: String,
+    /// 1-based page number
+    page: usize,
+}
+
+#[tauri::command]
+async fn document_stats(
+    renderer: tauri::State<'_, pdf_core::Renderer>,
+    document: tauri::State<'_, Mutex<Document>>,
+    markdown: String,
+) -> Result<DocumentStats, String> {
+    let renderer = document_renderer(&renderer, &document, &markdown)?;
+    let (stats, outline) = renderer.stats_and_outline(&markdown)?;
+    let outline = outline
+        .into_iter()
+        .map(|entry| OutlineItem {
+            level: entry.level,
+            title: entry.title,
+            page: entry.page,
+        })
+        .collect();
+    Ok(DocumentStats {
+        words: stats.words,
+        characters: stats.characters,
+        pages: stats.estimated_pages,
+        reading_minutes: stats.reading_minutes(),
+        outline,
     })
 }
 
+/// An installed font family and the styles it comes in, e.g. "Bold Italic".
+#[derive(Clone, Serialize)]
+struct FontFamily {
+    name: String,
+    styles: Vec<String>,
+}
+
+/// List the fonts installed on the system for the font picker; the renderer
+/// loads them too. Searching is slow, so the list is kept for the rest of the
+/// session.
+#[tauri::command]
+async fn list_system_fonts() -> Vec<FontFamily> {
+    static FAMILIES: OnceLock<Vec<FontFamily>> = OnceLock::new();
+    FAMILIES
+        .get_or_init(|| {
+            let fonts = FontSearcher::new().include_system_fonts(true).search();
+            fonts
+                .book
+                .families()
+                .map(|(name, infos)| {
+                    let mut variants: Vec<_> = infos
+                        .map(|info| (info.variant.weight, info.variant.style))
+                        .collect();
+                    variants.sort();
+                    variants.dedup();
+                    FontFamily {
+                        name: name.to_string(),
+                        styles: variants
+                            .into_iter()
+                            .map(|(weight, style)| style_name(weight, style))
+                            .collect(),
+                    }
+                })
+                .collect()
+        })
+        .clone()
+}
+
+fn style_name(weight: FontWeight, style: FontStyle) -> String {
+    let weight = match weight.to_number() {
+        0..=149 => "Thin",
+        150..=249 => "ExtraLight",
+        250..=349 => "Light",
+        350..=449 => "Regular",
+        450..=549 => "Medium",
+        550..=649 => "SemiBold",
+        650..=749 => "Bold",
+        750..=849 => "ExtraBold",
+        _ => "Black",
+    };
+    match style {
+        FontStyle::Normal => weight.to_string(),
+        FontStyle::Italic if weight == "Regular" => "Italic".to_string(),
+        FontStyle::Oblique if weight == "Regular" => "Oblique".to_string(),
+        FontStyle::Italic => format!("{} Italic", weight),
+        FontStyle::Oblique => format!("{} Oblique", weight),
+    }
+}
+
+/// Choices from the PDF export dialog, applied on top of the config.
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct PdfExportOptions {
+    /// First page to export (1-based)
+    first_page: Option<usize>,
+    /// Last page to export, or through the end
+    last_page: Option<usize>,
+    toc: Option<bool>,
+    watermark: Option<String>,
+    /// Paper size name understood by Typst, e.g. "a4" or "us-letter"
+    paper_size: Option<String>,
+}
+
 #[tauri::command]
-fn save_pdf_to_file(markdown: &str, path: &str) -> Result<(), String> {
-    let pdf_bytes = pdf_core::markdown_to_pdf(markdown)?;
+fn save_pdf_to_file(
+    renderer: tauri::State<'_, pdf_core::Renderer>,
+    document: tauri::State<'_, Mutex<Document>>,
+    markdown: &str,
+    path: &str,
+    options: Option<PdfExportOptions>,
+) -> Result<(), String> {
+    let options = options.unwrap_or_default();
+    let mut config = document_config(&renderer, &document, markdown)?;
+    if let Some(toc) = options.toc {
+        config.document.toc = toc;
+    }
+    if let Some(watermark) = options.watermark.filter(|text| !text.is_empty()) {
+        config.page.watermark = Some(watermark);
+    }
+    if let Some(paper_size) = options.paper_size {
+        config.page.size = Some(paper_size);
+    }
+    let renderer = renderer.with_config(config);
+    let pdf_bytes = match (options.first_page, options.last_page) {
+        (None, None) => renderer.render_pdf(markdown)?,
+        (first, last) => {
+            renderer.render_pdf_pages(markdown, first.unwrap_or(1)..=last.unwrap_or(usize::MAX))?
+        }
+    };
     std::fs::write(path, pdf_bytes).map_err(|e| e.to_string())
 }
 
+/// Extensions of the files the app opens, also used for dropped files
+const MARKDOWN_EXTENSIONS: [&str; 3] = ["md", "markdown", "txt"];
+
+/// The file the app was launched with, until the frontend asks for it.
+struct LaunchFile(Mutex<Option<PathBuf>>);
+
+/// Ask the frontend to open a file dropped on the window or opened from the
+/// file manager, with an `open-requested` event. The frontend holds the editor
+/// content, so it asks about unsaved changes and reports read errors.
+fn open_from_os(app: &tauri::AppHandle, path: &Path) {
+    let _ = app.emit("open-requested", path);
+}
+
+fn is_markdown(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            MARKDOWN_EXTENSIONS
+                .iter()
+                .any(|known| extension.eq_ignore_ascii_case(known))
+        })
+}
+
+/// Read the markdown file at `path` and make it the current document.
+#[tauri::command]
+fn read_markdown_file(
+    document: tauri::State<'_, Mutex<Document>>,
+    path: &str,
+) -> Result<String, String> {
+    let markdown = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let mut document = document.lock().map_err(|e| e.to_string())?;
+    document.set_path(PathBuf::from(path));
+    document.modified = false;
+    Ok(markdown)
+}
+
+/// The file the app was launched with, once. The frontend asks for it when it
+/// has loaded, since it wasn't listening for `open-requested` events yet.
+#[tauri::command]
+fn take_launch_file(launch_file: tauri::State<'_, LaunchFile>) -> Result<Option<PathBuf>, String> {
+    Ok(launch_file.0.lock().map_err(|e| e.to_string())?.take())
+}
+
+/// Save `markdown` to `path`, or to the current document's file when no path
+/// is given, returning the path written.
+#[tauri::command]
+fn save_markdown_to_file(
+    document: tauri::State<'_, Mutex<Document>>,
+    markdown: &str,
+    path: Option<String>,
+) -> Result<PathBuf, String> {
+    let mut document = document.lock().map_err(|e| e.to_string())?;
+    let path = path
+        .map(PathBuf::from)
+        .or_else(|| document.path.clone())
+        .ok_or("No file to save to")?;
+    std::fs::write(&path, markdown).map_err(|e| e.to_string())?;
+    document.set_path(path.clone());
+    document.modified = false;
+    Ok(path)
+}
+
+/// Record whether the editor has changes that aren't saved yet.
+#[tauri::command]
+fn set_document_modified(
+    document: tauri::State<'_, Mutex<Document>>,
+    modified: bool,
+) -> Result<(), String> {
+    document.lock().map_err(|e| e.to_string())?.modified = modified;
+    Ok(())
+}
+
+/// Write SVG pages next to `path`, returning the paths written.
+#[tauri::command]
+fn save_svg_to_file(
+    renderer: tauri::State<'_, pdf_core::Renderer>,
+    document: tauri::State<'_, Mutex<Document>>,
+    markdown: &str,
+    path: &str,
+) -> Result<Vec<PathBuf>, String> {
+    let doc = document_renderer(&renderer, &document, markdown)?.render_svg(markdown)?;
+    write_pages(
+        Path::new(path),
+        doc.pages.into_iter().map(String::into_bytes),
+    )
+}
+
+/// Write one PNG per page next to `path` (144 DPI unless given), returning the paths written.
+#[tauri::command]
+fn save_png_to_file(
+    renderer: tauri::State<'_, pdf_core::Renderer>,
+    document: tauri::State<'_, Mutex<Document>>,
+    markdown: &str,
+    path: &str,
+    dpi: Option<f32>,
+) -> Result<Vec<PathBuf>, String> {
+    let pages = document_renderer(&renderer, &document, markdown)?
+        .render_png(markdown, dpi.unwrap_or(144.0))?;
+    write_pages(Path::new(path), pages.into_iter())
+}
+
+/// Write a single page to `path`, or several as `name-1.ext`, `name-2.ext`, ...
+fn write_pages(
+    path: &Path,
+    pages: impl ExactSizeIterator<Item = Vec<u8>>,
+) -> Result<Vec<PathBuf>, String> {
+    let single = pages.len() == 1;
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension().unwrap_or_default().to_string_lossy();
+    let mut written = Vec::new();
+    for (i, page) in pages.enumerate() {
+        let page_path = if single {
+            path.to_path_buf()
+        } else {
+            path.with_file_name(format!("{}-{}.{}", stem, i + 1, extension))
+        };
+        std::fs::write(&page_path, page).map_err(|e| e.to_string())?;
+        written.push(page_path);
+    }
+    Ok(written)
+}
+
+const SAVE: &str = "Save";
+const DONT_SAVE: &str = "Don't Save";
+const CANCEL: &str = "Cancel";
+
+/// Ask whether to save a modified document before closing `window`. Saving
+/// happens in the frontend, which holds the editor content and closes the
+/// window again once the file is written.
+fn confirm_close(window: &tauri::Window) {
+    let target = window.clone();
+    window
+        .dialog()
+        .message("Do you want to save the changes to this document before closing?")
+        .title("Unsaved changes")
+        .kind(MessageDialogKind::Warning)
+        .buttons(MessageDialogButtons::YesNoCancelCustom(
+            SAVE.to_string(),
+            DONT_SAVE.to_string(),
+            CANCEL.to_string(),
+        ))
+        .parent(window)
+        .show_with_result(move |result| match result {
+            MessageDialogResult::Custom(button) if button == SAVE => {
+                let _ = target.emit("save-before-close", ());
+            }
+            MessageDialogResult::Custom(button) if button == DONT_SAVE => {
+                let _ = target.destroy();
+            }
+            _ => {}
+        });
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
+        // Shared across preview renders so edits recompile incrementally
+        .manage(
+            pdf_core::Renderer::new(pdf_core::Config::compiled_default())
+                .with_system_fonts()
+                .with_frontmatter_merged(),
+        )
+        .manage(Mutex::new(Document::default()))
+        .manage(preview::Preview::default())
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
         .setup(|app| {
+            let settings_path = app.path().app_config_dir()?.join("settings.json");
+            app.manage(settings::Settings::load(settings_path));
+
             let open_file = MenuItemBuilder::with_id("open_file", "Open File...")
                 .accelerator("CmdOrCtrl+O")
                 .build(app)?;
 
+            let save_markdown = MenuItemBuilder::with_id("save_markdown", "Save")
+                .accelerator("CmdOrCtrl+S")
+                .build(app)?;
+
+            let save_markdown_as = MenuItemBuilder::with_id("save_markdown_as", "Save As...")
+                .accelerator("CmdOrCtrl+Shift+S")
+                .build(app)?;
+
             let save_pdf = MenuItemBuilder::with_id("save_pdf", "Save PDF...")
                 .accelerator("CmdOrCtrl+R")
                 .build(app)?;
 
+            let export_svg = MenuItemBuilder::with_id("export_svg", "Export SVG...").build(app)?;
+
+            let export_png = MenuItemBuilder::with_id("export_png", "Export PNG...").build(app)?;
+
+            let export_bundle =
+                MenuItemBuilder::with_id("export_bundle", "Export Bundle...").build(app)?;
+
+            let mut new_from_template = SubmenuBuilder::new(app, "New From Template");
+            for (id, name) in templates::menu_items() {
+                let item = MenuItemBuilder::with_id(format!("template:{}", id), name).build(app)?;
+                new_from_template = new_from_template.item(&item);
+            }
+            let new_from_template = new_from_template.build()?;
+
             let file_menu = SubmenuBuilder::new(app, "File")
+                .item(&new_from_template)
                 .item(&open_file)
+                .item(&save_markdown)
+                .item(&save_markdown_as)
+                .separator()
                 .item(&save_pdf)
+                .item(&export_svg)
+                .item(&export_png)
+                .item(&export_bundle)
                 .separator()
                 .close_window()
                 .build()?;
@@ -69,6 +479,14 @@ pub fn run() {
 
             app.set_menu(menu)?;
 
+            // Windows and Linux pass a file opened with the app as an argument;
+            // the frontend asks for it with `take_launch_file` once loaded
+            let launch_file = std::env::args_os()
+                .skip(1)
+                .map(PathBuf::from)
+                .find(|path| path.is_file());
+            app.manage(LaunchFile(Mutex::new(launch_file)));
+
             Ok(())
         })
         .on_menu_event(|app, event| {
@@ -77,17 +495,82 @@ pub fn run() {
                     "open_file" => {
                         let _ = window.emit("menu-open-file", ());
                     }
+                    "save_markdown" => {
+                        let _ = window.emit("menu-save-markdown", ());
+                    }
+                    "save_markdown_as" => {
+                        let _ = window.emit("menu-save-markdown-as", ());
+                    }
                     "save_pdf" => {
                         let _ = window.emit("menu-save-pdf", ());
                     }
-                    _ => {}
+                    "export_svg" => {
+                        let _ = window.emit("menu-export-svg", ());
+                    }
+                    "export_png" => {
+                        let _ = window.emit("menu-export-png", ());
+                    }
+                    "export_bundle" => {
+                        let _ = window.emit("menu-export-bundle", ());
+                    }
+                    id => {
+                        if let Some(template) = id.strip_prefix("template:") {
+                            let _ = window.emit("menu-new-from-template", template);
+                        }
+                    }
                 }
             }
         })
+        .on_window_event(|window, event| match event {
+            WindowEvent::CloseRequested { api, .. } => {
+                let document = window.state::<Mutex<Document>>();
+                if document.lock().is_ok_and(|document| document.modified) {
+                    api.prevent_close();
+                    confirm_close(window);
+                }
+            }
+            WindowEvent::DragDrop(DragDropEvent::Drop { paths, .. }) => {
+                if let Some(path) = paths.iter().find(|path| is_markdown(path)) {
+                    open_from_os(window.app_handle(), path);
+                }
+            }
+            _ => {}
+        })
         .invoke_handler(tauri::generate_handler![
-            render_markdown_to_svg,
-            save_pdf_to_file
+            preview::render_preview,
+            preview::cancel_preview,
+            preview::preview_pages,
+            source_map,
+            document_stats,
+            list_system_fonts,
+            templates::list_templates,
+            templates::new_from_template,
+            settings::get_setting,
+            settings::set_setting,
+            settings::take_settings_error,
+            read_markdown_file,
+            save_markdown_to_file,
+            set_document_modified,
+            take_launch_file,
+            save_pdf_to_file,
+            save_svg_to_file,
+            save_png_to_file,
+            bundle::export_bundle
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(on_run_event);
 }
+
+/// Files opened from Finder arrive as an event rather than as arguments.
+#[cfg(target_os = "macos")]
+fn on_run_event(app: &tauri::AppHandle, event: tauri::RunEvent) {
+    if let tauri::RunEvent::Opened { urls } = event {
+        if let Some(path) = urls.iter().find_map(|url| url.to_file_path().ok()) {
+            open_from_os(app, &path);
+        }
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn on_run_event(_app: &tauri::AppHandle, _event: tauri::RunEvent) {}
@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::Mutex;
+
+use pdf_core::{CancellationToken, TextRun, CANCELLED};
+use serde::Serialize;
+use tauri::{Emitter, Manager};
+
+/// Preview renders run in the background. Each request cancels the render
+/// before it, and requests superseded while waiting for a render to finish
+/// are skipped, so only the latest content is rendered.
+#[derive(Default)]
+pub struct Preview {
+    /// Generation of the latest request and the token cancelling its render
+    latest: Mutex<(u64, CancellationToken)>,
+    /// The last completed render, locked while rendering the next one
+    rendered: Mutex<Rendered>,
+}
+
+#[derive(Default)]
+struct Rendered {
+    generation: u64,
+    pages: Vec<Page>,
+    width_pt: f64,
+    height_pt: f64,
+    next_id: u64,
+}
+
+/// A rendered page. Its ID is kept for as long as its SVG stays the same.
+struct Page {
+    id: u64,
+    hash: u64,
+    svg: String,
+    text: Vec<TextRun>,
+    /// Generation of the render that produced this SVG
+    since: u64,
+}
+
+impl Rendered {
+    /// Replace the pages, keeping the IDs of those whose SVG didn't change,
+    /// even if they moved.
+    fn update(&mut self, generation: u64, svgs: Vec<String>, text: Vec<Vec<TextRun>>) {
+        let mut previous: HashMap<u64, Vec<Page>> = HashMap::new();
+        for page in self.pages.drain(..) {
+            previous.entry(page.hash).or_default().push(page);
+        }
+        self.pages = svgs
+            .into_iter()
+            .zip(text)
+            .map(|(svg, text)| {
+                let hash = hash(&svg);
+                let unchanged = previous.get_mut(&hash).and_then(|pages| {
+                    let index = pages.iter().position(|page| page.svg == svg)?;
+                    Some(pages.remove(index))
+                });
+                unchanged.unwrap_or_else(|| {
+                    self.next_id += 1;
+                    Page {
+                        id: self.next_id,
+                        hash,
+                        svg,
+                        text,
+                        since: generation,
+                    }
+                })
+            })
+            .collect();
+        self.generation = generation;
+    }
+}
+
+fn hash(svg: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    svg.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Payload of the `render-complete` event. The pages are fetched with
+/// [`preview_pages`].
+#[derive(Clone, Serialize)]
+struct RenderComplete {
+    generation: u64,
+    page_count: usize,
+}
+
+/// Payload of the `render-failed` event.
+#[derive(Clone, Serialize)]
+struct RenderFailed {
+    generation: u64,
+    error: String,
+}
+
+/// The latest preview returned by [`preview_pages`].
+#[derive(Serialize)]
+pub struct PreviewUpdate {
+    generation: u64,
+    pages: Vec<PageUpdate>,
+    width_pt: f64,
+    height_pt: f64,
+}
+
+#[derive(Serialize)]
+struct PageUpdate {
+    id: u64,
+    /// `None` when the page is unchanged since the generation asked about
+    svg: Option<String>,
+    /// Text runs for a selectable layer over the SVG, sent along with it
+    text: Option<Vec<TextRun>>,
+}
+
+/// Start rendering a preview of `markdown`, returning its generation. The
+/// result arrives as a `render-complete` or `render-failed` event with that
+/// generation, unless a newer request supersedes it first.
+#[tauri::command]
+pub fn render_preview(
+    app: tauri::AppHandle,
+    preview: tauri::State<'_, Preview>,
+    markdown: String,
+) -> Result<u64, String> {
+    let (generation, cancel) = {
+        let mut latest = preview.latest.lock().map_err(|e| e.to_string())?;
+        latest.1.cancel();
+        *latest = (latest.0 + 1, CancellationToken::new());
+        latest.clone()
+    };
+    tauri::async_runtime::spawn_blocking(move || {
+        render_in_background(&app, generation, &cancel, &markdown)
+    });
+    Ok(generation)
+}
+
+/// Cancel the latest preview render, e.g. when the editor was cleared.
+#[tauri::command]
+pub fn cancel_preview(preview: tauri::State<'_, Preview>) -> Result<(), String> {
+    preview.latest.lock().map_err(|e| e.to_string())?.1.cancel();
+    Ok(())
+}
+
+/// The pages of the last completed render in order, with the SVG only for
+/// pages that changed after generation `since`; pass 0 to get every page.
+#[tauri::command]
+pub fn preview_pages(
+    preview: tauri::State<'_, Preview>,
+    since: u64,
+) -> Result<PreviewUpdate, String> {
+    let rendered = preview.rendered.lock().map_err(|e| e.to_string())?;
+    let pages = rendered
+        .pages
+        .iter()
+        .map(|page| {
+            let changed = page.since > since;
+            PageUpdate {
+                id: page.id,
+                svg: changed.then(|| page.svg.clone()),
+                text: changed.then(|| page.text.clone()),
+            }
+        })
+        .collect();
+    Ok(PreviewUpdate {
+        generation: rendered.generation,
+        pages,
+        width_pt: rendered.width_pt,
+        height_pt: rendered.height_pt,
+    })
+}
+
+fn render_in_background(
+    app: &tauri::AppHandle,
+    generation: u64,
+    cancel: &CancellationToken,
+    markdown: &str,
+) {
+    let preview = app.state::<Preview>();
+    let Ok(mut rendered) = preview.rendered.lock() else {
+        return;
+    };
+    // Superseded while waiting for the previous render
+    if cancel.is_cancelled() {
+        return;
+    }
+    let result = crate::document_renderer(&app.state(), &app.state(), markdown)
+        .and_then(|renderer| renderer.render_svg_cancellable(markdown, cancel));
+    match result {
+        Ok(_) if cancel.is_cancelled() => {}
+        Ok(doc) => {
+            rendered.width_pt = doc.width_pt;
+            rendered.height_pt = doc.height_pt;
+            rendered.update(generation, doc.pages, doc.text);
+            let page_count = rendered.pages.len();
+            drop(rendered);
+            let _ = app.emit(
+                "render-complete",
+                RenderComplete {
+                    generation,
+                    page_count,
+                },
+            );
+        }
+        Err(error) if error == CANCELLED => {}
+        Err(error) => {
+            let _ = app.emit("render-failed", RenderFailed { generation, error });
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// App preferences, kept in `settings.json` in the app's config directory.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct AppSettings {
+    /// Preview zoom, `None` to fit the page to the window
+    zoom: Option<f64>,
+    theme: Theme,
+    /// Delay after the last edit before saving, `None` to turn auto-save off
+    autosave_interval_ms: Option<u64>,
+    /// Where export dialogs start, `None` for the last directory saved to
+    export_dir: Option<PathBuf>,
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        Self {
+            zoom: None,
+            theme: Theme::System,
+            autosave_interval_ms: Some(2000),
+            export_dir: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Theme {
+    System,
+    Light,
+    Dark,
+}
+
+pub struct Settings {
+    path: PathBuf,
+    values: Mutex<AppSettings>,
+    /// Why the settings file was ignored, until the frontend asks
+    load_error: Mutex<Option<String>>,
+}
+
+impl Settings {
+    /// Read the settings file at `path`, falling back to the defaults if it is
+    /// missing or unreadable.
+    pub fn load(path: PathBuf) -> Self {
+        let mut load_error = None;
+        let values = match fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                load_error = Some(format!(
+                    "Ignoring invalid settings in {}: {}",
+                    path.display(),
+                    e
+                ));
+                AppSettings::default()
+            }),
+            Err(_) => AppSettings::default(),
+        };
+        Self {
+            path,
+            values: Mutex::new(values),
+            load_error: Mutex::new(load_error),
+        }
+    }
+
+    fn save(&self, values: &AppSettings) -> Result<(), String> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("creating {}: {}", dir.display(), e))?;
+        }
+        let json = serde_json::to_string_pretty(values).map_err(|e| e.to_string())?;
+        fs::write(&self.path, json).map_err(|e| format!("writing {}: {}", self.path.display(), e))
+    }
+}
+
+fn to_map(values: &AppSettings) -> Result<serde_json::Map<String, Value>, String> {
+    match serde_json::to_value(values).map_err(|e| e.to_string())? {
+        Value::Object(map) => Ok(map),
+        _ => unreachable!("settings serialize to an object"),
+    }
+}
+
+/// Why the settings file couldn't be used, once, for the frontend to tell the
+/// user when it has loaded. The defaults apply until a setting is changed,
+/// which overwrites the file.
+#[tauri::command]
+pub fn take_settings_error(settings: tauri::State<'_, Settings>) -> Result<Option<String>, String> {
+    Ok(settings
+        .load_error
+        .lock()
+        .map_err(|e| e.to_string())?
+        .take())
+}
+
+/// The value of setting `key`, or its default if it was never set.
+#[tauri::command]
+pub fn get_setting(settings: tauri::State<'_, Settings>, key: String) -> Result<Value, String> {
+    let values = settings.values.lock().map_err(|e| e.to_string())?;
+    to_map(&values)?
+        .remove(&key)
+        .ok_or_else(|| format!("unknown setting {}", key))
+}
+
+/// Change setting `key` and write the settings file. Pass `null` to reset a
+/// setting that can be unset.
+#[tauri::command]
+pub fn set_setting(
+    settings: tauri::State<'_, Settings>,
+    key: String,
+    value: Value,
+) -> Result<(), String> {
+    let mut values = settings.values.lock().map_err(|e| e.to_string())?;
+    let mut map = to_map(&values)?;
+    match map.get_mut(&key) {
+        Some(current) => *current = value,
+        None => return Err(format!("unknown setting {}", key)),
+    }
+    let updated = serde_json::from_value(Value::Object(map))
+        .map_err(|e| format!("invalid value for {}: {}", key, e))?;
+    settings.save(&updated)?;
+    *values = updated;
+    Ok(())
+}
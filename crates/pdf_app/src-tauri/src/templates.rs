@@ -0,0 +1,89 @@
+use std::sync::Mutex;
+
+use serde::Serialize;
+
+use crate::Document;
+
+/// A starting document. Its settings are in the frontmatter's `pdf:` section.
+struct Template {
+    id: &'static str,
+    name: &'static str,
+    description: &'static str,
+    markdown: &'static str,
+}
+
+const TEMPLATES: [Template; 5] = [
+    Template {
+        id: "report",
+        name: "Report",
+        description: "Title page, table of contents and numbered pages",
+        markdown: include_str!("../templates/report.md"),
+    },
+    Template {
+        id: "letter",
+        name: "Letter",
+        description: "Addresses, date, subject and signature",
+        markdown: include_str!("../templates/letter.md"),
+    },
+    Template {
+        id: "invoice",
+        name: "Invoice",
+        description: "Line items with subtotal, tax and total",
+        markdown: include_str!("../templates/invoice.md"),
+    },
+    Template {
+        id: "meeting-notes",
+        name: "Meeting Notes",
+        description: "Agenda, decisions and action items",
+        markdown: include_str!("../templates/meeting-notes.md"),
+    },
+    Template {
+        id: "resume",
+        name: "Resume",
+        description: "Profile, experience, education and skills",
+        markdown: include_str!("../templates/resume.md"),
+    },
+];
+
+#[derive(Serialize)]
+pub struct TemplateInfo {
+    id: &'static str,
+    name: &'static str,
+    description: &'static str,
+}
+
+/// Menu items for File → New From Template, as (id, name).
+pub fn menu_items() -> impl Iterator<Item = (&'static str, &'static str)> {
+    TEMPLATES
+        .iter()
+        .map(|template| (template.id, template.name))
+}
+
+#[tauri::command]
+pub fn list_templates() -> Vec<TemplateInfo> {
+    TEMPLATES
+        .iter()
+        .map(|template| TemplateInfo {
+            id: template.id,
+            name: template.name,
+            description: template.description,
+        })
+        .collect()
+}
+
+/// Start a new, not yet saved document from a template, returning its markdown.
+#[tauri::command]
+pub fn new_from_template(
+    document: tauri::State<'_, Mutex<Document>>,
+    id: &str,
+) -> Result<String, String> {
+    let template = TEMPLATES
+        .iter()
+        .find(|template| template.id == id)
+        .ok_or_else(|| format!("Unknown template: {}", id))?;
+    let mut document = document.lock().map_err(|e| e.to_string())?;
+    document.path = None;
+    document.modified = false;
+    document.commit = None;
+    Ok(template.markdown.to_string())
+}
@@ -1,18 +1,20 @@
 import { useState, useEffect, useRef, useCallback } from "react";
 import { invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
-import { open, save } from "@tauri-apps/plugin-dialog";
-import { readTextFile, writeTextFile, watch } from "@tauri-apps/plugin-fs";
+import { getCurrentWindow } from "@tauri-apps/api/window";
+import { ask, message, open, save } from "@tauri-apps/plugin-dialog";
+import { readTextFile, watch } from "@tauri-apps/plugin-fs";
 import { Editor, EditorHandle } from "./components/Editor";
-import { PdfViewer } from "./components/PdfViewer";
+import { PdfViewer, TextRun } from "./components/PdfViewer";
 import {
   getLastOpenDir,
   setLastOpenDir,
   getLastSaveDir,
   setLastSaveDir,
 } from "./lib/paths";
+import { getSetting, takeSettingsError } from "./lib/settings";
 
-const AUTO_SAVE_DELAY = 2000; // Auto-save after 2 seconds of inactivity
+const AUTO_SAVE_DELAY = 2000; // Until the autosave_interval_ms setting loads
 
 const SAMPLE_MARKDOWN = `# Hello World
 
@@ -35,24 +37,74 @@ fn main() {
 
 interface SvgDocument {
   pages: string[];
+  text: TextRun[][];
   width_pt: number;
   height_pt: number;
 }
 
-function getDefaultPdfName(currentFile: string | null): string {
-  if (!currentFile) return "document.pdf";
+interface DocumentStats {
+  words: number;
+  characters: number;
+  pages: number;
+  reading_minutes: number;
+  outline: { level: number; title: string; page: number }[];
+}
+
+interface RenderComplete {
+  generation: number;
+  page_count: number;
+}
+
+interface PreviewUpdate {
+  generation: number;
+  // Every page in order; `svg` and `text` are null when unchanged since the
+  // page with that ID was last fetched
+  pages: { id: number; svg: string | null; text: TextRun[] | null }[];
+  width_pt: number;
+  height_pt: number;
+}
+
+interface CachedPage {
+  svg: string;
+  text: TextRun[];
+}
+
+interface RenderFailed {
+  generation: number;
+  error: string;
+}
+
+function getDefaultExportName(
+  currentFile: string | null,
+  extension: string,
+): string {
+  if (!currentFile) return `document.${extension}`;
   const name =
     currentFile.split("/").pop() || currentFile.split("\\").pop() || "document";
-  return name.replace(/\.(md|markdown|txt)$/i, "") + ".pdf";
+  return name.replace(/\.(md|markdown|txt)$/i, "") + `.${extension}`;
 }
 
+// Directory export dialogs start in: the export_dir setting, or else the last
+// directory saved to
+async function getExportDir(): Promise<string | undefined> {
+  return (await getSetting("export_dir")) ?? getLastSaveDir();
+}
+
+// Resolution for PNG export; the backend writes one image per page
+const PNG_EXPORT_DPI = 144;
+
 function App() {
   const [markdown, setMarkdown] = useState(SAMPLE_MARKDOWN);
   const [svgDoc, setSvgDoc] = useState<SvgDocument | null>(null);
+  const [stats, setStats] = useState<DocumentStats | null>(null);
   const [currentFile, setCurrentFile] = useState<string | null>(null);
   const [hasUnsavedChanges, setHasUnsavedChanges] = useState(false);
-  const [autoSaveEnabled, _setAutoSaveEnabled] = useState(true); // TODO: expose in config UI
+  const [autoSaveInterval, setAutoSaveInterval] = useState<number | null>(
+    AUTO_SAVE_DELAY,
+  ); // null when auto-save is off
   const debounceRef = useRef<number | null>(null);
+  const renderedGenerationRef = useRef(0); // Generation of the preview shown
+  const pageCacheRef = useRef(new Map<number, CachedPage>()); // Pages by ID
   const autoSaveRef = useRef<number | null>(null);
   const markdownRef = useRef(markdown);
   const currentFileRef = useRef<string | null>(null);
@@ -69,21 +121,82 @@ function App() {
     currentFileRef.current = currentFile;
   }, [currentFile]);
 
-  // Save markdown file to disk
-  const saveMarkdown = useCallback(async () => {
-    const file = currentFileRef.current;
+  // Save markdown file to disk, asking for a path when there is no current
+  // file or for "Save As". Returns whether the file was written.
+  const saveMarkdown = useCallback(async (saveAs = false) => {
     const content = markdownRef.current;
-    if (!file) return;
+    let path: string | null = null;
+
+    if (saveAs || !currentFileRef.current) {
+      const lastDir = getLastSaveDir();
+      const defaultName = getDefaultExportName(currentFileRef.current, "md");
+      path = await save({
+        filters: [{ name: "Markdown", extensions: ["md", "markdown", "txt"] }],
+        defaultPath: lastDir ? `${lastDir}/${defaultName}` : defaultName,
+      });
+      if (!path) return false;
+      setLastSaveDir(path);
+    }
 
     try {
-      await writeTextFile(file, content);
+      const written = await invoke<string>("save_markdown_to_file", {
+        markdown: content,
+        path,
+      });
       savedContentRef.current = content;
+      currentFileRef.current = written; // For callers continuing after a save
+      setCurrentFile(written);
       setHasUnsavedChanges(false);
+      return true;
     } catch (err) {
       console.error("Failed to save file:", err);
+      return false;
     }
   }, []);
 
+  // Whether the current document may be replaced, asking first if it has
+  // changes
+  const confirmDiscard = useCallback(async (title: string) => {
+    const baseline = savedContentRef.current ?? SAMPLE_MARKDOWN;
+    if (markdownRef.current === baseline) return true;
+    return ask("Discard the changes to the current document?", {
+      title,
+      kind: "warning",
+      okLabel: "Discard",
+    });
+  }, []);
+
+  // Read the markdown file at `path` and show it
+  const openPath = useCallback(
+    async (path: string) => {
+      if (!(await confirmDiscard("Open File"))) return;
+      try {
+        const content = await invoke<string>("read_markdown_file", { path });
+        savedContentRef.current = content;
+        isExternalChangeRef.current = true;
+        setMarkdown(content);
+        setCurrentFile(path);
+        setHasUnsavedChanges(false);
+      } catch (err) {
+        await message(String(err), { title: "Could not open file", kind: "error" });
+      }
+    },
+    [confirmDiscard],
+  );
+
+  // Replace the document with a bundled template, which stays unsaved until
+  // "Save" asks for a path
+  const newFromTemplate = useCallback(async (id: string) => {
+    if (!(await confirmDiscard("New From Template"))) return;
+
+    const content = await invoke<string>("new_from_template", { id });
+    savedContentRef.current = content;
+    isExternalChangeRef.current = true;
+    setMarkdown(content);
+    setCurrentFile(null);
+    setHasUnsavedChanges(false);
+  }, [confirmDiscard]);
+
   const openFile = useCallback(async () => {
     const lastDir = getLastOpenDir();
     const selected = await open({
@@ -94,21 +207,16 @@ function App() {
 
     if (selected) {
       setLastOpenDir(selected);
-      const content = await readTextFile(selected);
-      savedContentRef.current = content;
-      isExternalChangeRef.current = true;
-      setMarkdown(content);
-      setCurrentFile(selected);
-      setHasUnsavedChanges(false);
+      await openPath(selected);
     }
-  }, []);
+  }, [openPath]);
 
   const savePdf = useCallback(async () => {
     const currentMarkdown = markdownRef.current;
     if (!currentMarkdown.trim()) return;
 
-    const lastDir = getLastSaveDir();
-    const defaultName = getDefaultPdfName(currentFileRef.current);
+    const lastDir = await getExportDir();
+    const defaultName = getDefaultExportName(currentFileRef.current, "pdf");
     const defaultPath = lastDir ? `${lastDir}/${defaultName}` : defaultName;
 
     const path = await save({
@@ -122,34 +230,154 @@ function App() {
     }
   }, []);
 
+  // Zip the markdown, config.toml, images and PDF. The backend finds the
+  // images next to the markdown file, so an unsaved document is saved first.
+  const exportBundle = useCallback(async () => {
+    const currentMarkdown = markdownRef.current;
+    if (!currentMarkdown.trim()) return;
+    if (!currentFileRef.current && !(await saveMarkdown())) return;
+
+    const lastDir = await getExportDir();
+    const defaultName = getDefaultExportName(currentFileRef.current, "zip");
+    const defaultPath = lastDir ? `${lastDir}/${defaultName}` : defaultName;
+
+    const path = await save({
+      filters: [{ name: "Zip archive", extensions: ["zip"] }],
+      defaultPath,
+    });
+
+    if (path) {
+      setLastSaveDir(path);
+      await invoke("export_bundle", { markdown: currentMarkdown, path });
+    }
+  }, [saveMarkdown]);
+
+  // Export SVG or PNG; multi-page documents become name-1.ext, name-2.ext, ...
+  const exportImages = useCallback(async (format: "svg" | "png") => {
+    const currentMarkdown = markdownRef.current;
+    if (!currentMarkdown.trim()) return;
+
+    const lastDir = await getExportDir();
+    const defaultName = getDefaultExportName(currentFileRef.current, format);
+    const defaultPath = lastDir ? `${lastDir}/${defaultName}` : defaultName;
+
+    const path = await save({
+      filters: [{ name: format.toUpperCase(), extensions: [format] }],
+      defaultPath,
+    });
+
+    if (path) {
+      setLastSaveDir(path);
+      if (format === "svg") {
+        await invoke("save_svg_to_file", { markdown: currentMarkdown, path });
+      } else {
+        await invoke("save_png_to_file", {
+          markdown: currentMarkdown,
+          path,
+          dpi: PNG_EXPORT_DPI,
+        });
+      }
+    }
+  }, []);
+
+  useEffect(() => {
+    takeSettingsError()
+      .then(
+        (error) =>
+          error && message(error, { title: "Settings reset", kind: "warning" }),
+      )
+      .catch((err) => console.error("Failed to load settings:", err));
+    getSetting("autosave_interval_ms")
+      .then(setAutoSaveInterval)
+      .catch((err) => console.error("Failed to load settings:", err));
+  }, []);
+
+  // Open files dropped on the window or opened from the file manager,
+  // including one the app was launched with
+  useEffect(() => {
+    invoke<string | null>("take_launch_file")
+      .then((path) => path && openPath(path))
+      .catch((err) => console.error("Failed to open file:", err));
+
+    const unlistenOpen = listen<string>("open-requested", (event) => {
+      openPath(event.payload);
+    });
+
+    return () => {
+      unlistenOpen.then((fn) => fn());
+    };
+  }, [openPath]);
+
   // Listen for menu events
   useEffect(() => {
     const unlistenOpen = listen("menu-open-file", () => {
       openFile();
     });
 
+    const unlistenNewFromTemplate = listen<string>(
+      "menu-new-from-template",
+      (event) => {
+        newFromTemplate(event.payload);
+      },
+    );
+
+    const unlistenSaveMarkdown = listen("menu-save-markdown", () => {
+      saveMarkdown();
+    });
+
+    const unlistenSaveMarkdownAs = listen("menu-save-markdown-as", () => {
+      saveMarkdown(true);
+    });
+
+    // The backend asks about unsaved changes on close; save, then close again
+    const unlistenSaveBeforeClose = listen("save-before-close", async () => {
+      if (await saveMarkdown()) {
+        await getCurrentWindow().close();
+      }
+    });
+
     const unlistenSave = listen("menu-save-pdf", () => {
       savePdf();
     });
 
+    const unlistenExportSvg = listen("menu-export-svg", () => {
+      exportImages("svg");
+    });
+
+    const unlistenExportPng = listen("menu-export-png", () => {
+      exportImages("png");
+    });
+
+    const unlistenExportBundle = listen("menu-export-bundle", () => {
+      exportBundle();
+    });
+
     return () => {
       unlistenOpen.then((fn) => fn());
+      unlistenNewFromTemplate.then((fn) => fn());
+      unlistenSaveMarkdown.then((fn) => fn());
+      unlistenSaveMarkdownAs.then((fn) => fn());
+      unlistenSaveBeforeClose.then((fn) => fn());
       unlistenSave.then((fn) => fn());
+      unlistenExportSvg.then((fn) => fn());
+      unlistenExportPng.then((fn) => fn());
+      unlistenExportBundle.then((fn) => fn());
     };
-  }, [openFile, savePdf]);
-
-  // Handle Cmd+S to save markdown
+  }, [
+    openFile,
+    newFromTemplate,
+    saveMarkdown,
+    savePdf,
+    exportImages,
+    exportBundle,
+  ]);
+
+  // Keep the backend's modified flag in sync for the close prompt
   useEffect(() => {
-    const handleKeyDown = (e: KeyboardEvent) => {
-      if ((e.metaKey || e.ctrlKey) && e.key === "s") {
-        e.preventDefault();
-        saveMarkdown();
-      }
-    };
-
-    window.addEventListener("keydown", handleKeyDown);
-    return () => window.removeEventListener("keydown", handleKeyDown);
-  }, [saveMarkdown]);
+    invoke("set_document_modified", { modified: hasUnsavedChanges }).catch(
+      (err) => console.error("Failed to update modified state:", err),
+    );
+  }, [hasUnsavedChanges]);
 
   // Watch file for external changes
   useEffect(() => {
@@ -208,13 +436,13 @@ function App() {
       setHasUnsavedChanges(isDirty);
 
       // Auto-save if enabled and there are unsaved changes
-      if (autoSaveEnabled && isDirty) {
+      if (autoSaveInterval !== null && isDirty) {
         if (autoSaveRef.current) {
           clearTimeout(autoSaveRef.current);
         }
         autoSaveRef.current = window.setTimeout(() => {
           saveMarkdown();
-        }, AUTO_SAVE_DELAY);
+        }, autoSaveInterval);
       }
     }
 
@@ -223,7 +451,54 @@ function App() {
         clearTimeout(autoSaveRef.current);
       }
     };
-  }, [markdown, currentFile, autoSaveEnabled, saveMarkdown]);
+  }, [markdown, currentFile, autoSaveInterval, saveMarkdown]);
+
+  // Show background preview renders, fetching only pages that changed since
+  // the preview shown and ignoring results older than it
+  useEffect(() => {
+    const unlistenComplete = listen<RenderComplete>(
+      "render-complete",
+      async (event) => {
+        if (event.payload.generation <= renderedGenerationRef.current) return;
+
+        try {
+          const update = await invoke<PreviewUpdate>("preview_pages", {
+            since: renderedGenerationRef.current,
+          });
+          if (update.generation <= renderedGenerationRef.current) return;
+
+          const cache = pageCacheRef.current;
+          const pages = new Map<number, CachedPage>();
+          for (const { id, svg, text } of update.pages) {
+            const cached = cache.get(id);
+            pages.set(id, {
+              svg: svg ?? cached?.svg ?? "",
+              text: text ?? cached?.text ?? [],
+            });
+          }
+          pageCacheRef.current = pages;
+          renderedGenerationRef.current = update.generation;
+          setSvgDoc({
+            pages: update.pages.map(({ id }) => pages.get(id)?.svg ?? ""),
+            text: update.pages.map(({ id }) => pages.get(id)?.text ?? []),
+            width_pt: update.width_pt,
+            height_pt: update.height_pt,
+          });
+        } catch (err) {
+          console.error("Failed to fetch preview:", err);
+        }
+      },
+    );
+
+    const unlistenFailed = listen<RenderFailed>("render-failed", (event) => {
+      console.error("Failed to render:", event.payload.error);
+    });
+
+    return () => {
+      unlistenComplete.then((fn) => fn());
+      unlistenFailed.then((fn) => fn());
+    };
+  }, []);
 
   // Convert markdown to SVG for preview
   useEffect(() => {
@@ -234,14 +509,13 @@ function App() {
     debounceRef.current = window.setTimeout(async () => {
       if (!markdown.trim()) {
         setSvgDoc(null);
+        await invoke("cancel_preview");
         return;
       }
 
       try {
-        const doc = await invoke<SvgDocument>("render_markdown_to_svg", {
-          markdown,
-        });
-        setSvgDoc(doc);
+        // Renders in the background; the result arrives as "render-complete"
+        await invoke("render_preview", { markdown });
       } catch (err) {
         console.error("Failed to render:", err);
       }
@@ -254,6 +528,17 @@ function App() {
     };
   }, [markdown]);
 
+  // Update the status bar once a new preview is shown; the layout is cached
+  useEffect(() => {
+    if (!svgDoc) {
+      setStats(null);
+      return;
+    }
+    invoke<DocumentStats>("document_stats", { markdown: markdownRef.current })
+      .then(setStats)
+      .catch((err) => console.error("Failed to compute stats:", err));
+  }, [svgDoc]);
+
   // Get just the filename from the path
   const fileName = currentFile
     ? currentFile.split("/").pop() || currentFile.split("\\").pop()
@@ -297,6 +582,19 @@ function App() {
           >
             Page Break
           </button>
+          {stats && (
+            <div
+              className="px-2 py-1 backdrop-blur-sm rounded text-xs"
+              style={{
+                backgroundColor: "rgba(60, 56, 54, 0.9)",
+                color: "#a89984",
+              }}
+            >
+              {stats.words.toLocaleString()} words · {stats.pages}{" "}
+              {stats.pages === 1 ? "page" : "pages"} ·{" "}
+              {stats.reading_minutes} min read
+            </div>
+          )}
         </div>
       </div>
       <div className="w-1/2 min-w-0 flex flex-col">
@@ -1,7 +1,17 @@
 import { useEffect, useState, useRef, useCallback, memo } from "react";
 
+export interface TextRun {
+  text: string;
+  x: number;
+  baseline: number;
+  width: number;
+  size: number;
+}
+
 interface SvgDocument {
   pages: string[];
+  // Text runs of each page, in points from the page's top-left corner
+  text: TextRun[][];
   width_pt: number;
   height_pt: number;
 }
@@ -21,6 +31,40 @@ const SvgRenderer = memo(function SvgRenderer({ svg }: { svg: string }) {
   return <div dangerouslySetInnerHTML={{ __html: svg }} />;
 });
 
+// Invisible copy of the page's text over the SVG, whose glyphs are drawn as
+// shapes, so the text can be selected and copied. Each run is stretched to
+// the width it was laid out at.
+const TextLayer = memo(function TextLayer({
+  runs,
+  width,
+  height,
+}: {
+  runs: TextRun[];
+  width: number;
+  height: number;
+}) {
+  return (
+    <div data-text-layer className="absolute inset-0">
+      <svg viewBox={`0 0 ${width} ${height}`} className="select-text">
+        {runs.map((run, i) => (
+          <text
+            key={i}
+            x={run.x}
+            y={run.baseline}
+            fontSize={run.size}
+            textLength={run.width}
+            lengthAdjust="spacingAndGlyphs"
+            fill="transparent"
+            style={{ whiteSpace: "pre", cursor: "text" }}
+          >
+            {run.text}
+          </text>
+        ))}
+      </svg>
+    </div>
+  );
+});
+
 export function PdfViewer({ svgDoc, onSave }: PdfViewerProps) {
   const [currentPage, setCurrentPage] = useState(1);
   const [scale, setScale] = useState(1);
@@ -422,6 +466,8 @@ export function PdfViewer({ svgDoc, onSave }: PdfViewerProps) {
       // Only start dragging if clicking directly on the container or the document
       const target = e.target as HTMLElement;
       if (target.closest("button")) return;
+      // Leave drags over text to selection
+      if (target.closest("[data-text-layer] text")) return;
 
       isDragging.current = true;
       lastPointer.current = { x: e.clientX, y: e.clientY };
@@ -578,6 +624,7 @@ export function PdfViewer({ svgDoc, onSave }: PdfViewerProps) {
   }, [handleZoomIn, handleZoomOut, handleFit]);
 
   const currentSvg = svgDoc?.pages[currentPage - 1];
+  const currentText = svgDoc?.text[currentPage - 1];
 
   // Calculate scaled dimensions for native SVG rendering (no CSS transform scaling)
   const scaledWidth = svgDoc ? svgDoc.width_pt * scale : 0;
@@ -753,6 +800,13 @@ export function PdfViewer({ svgDoc, onSave }: PdfViewerProps) {
           }}
         >
           <SvgRenderer svg={currentSvg} />
+          {currentText && (
+            <TextLayer
+              runs={currentText}
+              width={svgDoc.width_pt}
+              height={svgDoc.height_pt}
+            />
+          )}
         </div>
       ) : (
         <div
@@ -0,0 +1,27 @@
+import { invoke } from "@tauri-apps/api/core";
+
+// App preferences persisted by the backend in settings.json
+export interface Settings {
+  zoom: number | null; // null fits the page to the window
+  theme: "system" | "light" | "dark";
+  autosave_interval_ms: number | null; // null turns auto-save off
+  export_dir: string | null; // null uses the last directory saved to
+}
+
+export function getSetting<K extends keyof Settings>(
+  key: K,
+): Promise<Settings[K]> {
+  return invoke<Settings[K]>("get_setting", { key });
+}
+
+// Why the settings file was ignored at startup, if it was; null after the first call
+export function takeSettingsError(): Promise<string | null> {
+  return invoke<string | null>("take_settings_error");
+}
+
+export function setSetting<K extends keyof Settings>(
+  key: K,
+  value: Settings[K],
+): Promise<void> {
+  return invoke("set_setting", { key, value });
+}
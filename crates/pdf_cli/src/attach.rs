@@ -0,0 +1,144 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use lopdf::{Dictionary, Document, Object, ObjectId};
+
+/// Page attributes a page can inherit from its ancestors in the page tree
+const INHERITED: [&[u8]; 4] = [b"Resources", b"MediaBox", b"CropBox", b"Rotate"];
+
+/// Put the pages of the `before` PDFs in front of the generated PDF and those of
+/// the `after` PDFs behind it. The generated document stays the base, so its
+/// metadata, outline, links and tags are kept.
+pub fn attach(pdf: Vec<u8>, before: &[PathBuf], after: &[PathBuf]) -> Result<Vec<u8>, String> {
+    if before.is_empty() && after.is_empty() {
+        return Ok(pdf);
+    }
+    let invalid = |e: lopdf::Error| format!("combining PDFs: {}", e);
+    let mut doc = Document::load_mem(&pdf).map_err(invalid)?;
+    let generated_root = doc
+        .catalog()
+        .and_then(|catalog| catalog.get(b"Pages"))
+        .and_then(Object::as_reference)
+        .map_err(invalid)?;
+    let generated_pages = doc.get_pages().len();
+    let root = doc.new_object_id();
+
+    let mut kids = Vec::new();
+    for path in before {
+        import_pages(&mut doc, path, root, &mut kids)?;
+    }
+    let prepended = kids.len();
+    kids.push(Object::Reference(generated_root));
+    for path in after {
+        import_pages(&mut doc, path, root, &mut kids)?;
+    }
+
+    // A new page tree root above the generated pages and the imported ones
+    let count = kids.len() - 1 + generated_pages;
+    let mut pages = Dictionary::new();
+    pages.set("Type", "Pages");
+    pages.set("Kids", kids);
+    pages.set("Count", count as i64);
+    doc.objects.insert(root, Object::Dictionary(pages));
+    doc.get_dictionary_mut(generated_root)
+        .map_err(invalid)?
+        .set("Parent", root);
+    doc.catalog_mut().map_err(invalid)?.set("Pages", root);
+    shift_page_labels(&mut doc, prepended);
+
+    // Drop what only the imported documents' catalogs referred to
+    doc.prune_objects();
+    let mut out = Vec::new();
+    doc.save_to(&mut out)
+        .map_err(|e| format!("combining PDFs: {}", e))?;
+    Ok(out)
+}
+
+/// Copy the pages of the PDF at `path` into `doc` under `parent`, adding them to `kids`.
+fn import_pages(
+    doc: &mut Document,
+    path: &Path,
+    parent: ObjectId,
+    kids: &mut Vec<Object>,
+) -> Result<(), String> {
+    let invalid = |e: lopdf::Error| format!("reading {}: {}", path.display(), e);
+    let bytes = fs::read(path).map_err(|e| format!("reading {}: {}", path.display(), e))?;
+    let mut other = Document::load_mem(&bytes).map_err(invalid)?;
+    other.renumber_objects_with(doc.max_id + 1);
+
+    let mut pages = Vec::new();
+    for id in other.get_pages().into_values() {
+        let mut page = other.get_dictionary(id).map_err(invalid)?.clone();
+        for key in INHERITED {
+            if !page.has(key)
+                && let Some(value) = inherited(&other, &page, key)
+            {
+                page.set(key, value);
+            }
+        }
+        page.set("Parent", parent);
+        // Refers to the imported document's structure tree, which isn't kept
+        page.remove(b"StructParents");
+        pages.push((id, page));
+    }
+
+    doc.max_id = doc.max_id.max(other.max_id);
+    doc.objects.extend(other.objects);
+    for (id, page) in pages {
+        doc.objects.insert(id, Object::Dictionary(page));
+        kids.push(Object::Reference(id));
+    }
+    Ok(())
+}
+
+/// Look up an inheritable attribute in the ancestors of `page`.
+pub fn inherited(doc: &Document, page: &Dictionary, key: &[u8]) -> Option<Object> {
+    let mut node = page;
+    // Bounded, in case of a cyclic page tree
+    for _ in 0..32 {
+        let parent = node.get(b"Parent").and_then(Object::as_reference).ok()?;
+        node = doc.get_dictionary(parent).ok()?;
+        if let Ok(value) = node.get(key) {
+            return Some(value.clone());
+        }
+    }
+    None
+}
+
+/// Keep page labels (e.g. from page numbering) on the generated pages after
+/// `prepended` pages were inserted before them; those get no label.
+fn shift_page_labels(doc: &mut Document, prepended: usize) {
+    if prepended == 0 {
+        return;
+    }
+    let reference = match doc.catalog().and_then(|catalog| catalog.get(b"PageLabels")) {
+        Ok(Object::Reference(id)) => Some(*id),
+        Ok(_) => None,
+        Err(_) => return,
+    };
+    let labels = match reference {
+        Some(id) => doc.get_dictionary_mut(id).ok(),
+        None => doc
+            .catalog_mut()
+            .and_then(|catalog| catalog.get_mut(b"PageLabels"))
+            .and_then(Object::as_dict_mut)
+            .ok(),
+    };
+    let Some(nums) = labels
+        .and_then(|labels| labels.get_mut(b"Nums").ok())
+        .and_then(|nums| nums.as_array_mut().ok())
+    else {
+        return;
+    };
+    for (i, entry) in nums.iter_mut().enumerate() {
+        if i % 2 == 0
+            && let Ok(index) = entry.as_i64()
+        {
+            *entry = Object::Integer(index + prepended as i64);
+        }
+    }
+    nums.splice(
+        0..0,
+        [Object::Integer(0), Object::Dictionary(Dictionary::new())],
+    );
+}
@@ -0,0 +1,186 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use clap::Args;
+use globset::GlobBuilder;
+use rayon::prelude::*;
+use walkdir::WalkDir;
+
+use pdf_core::Diagnostic;
+
+use crate::{Outcome, RenderArgs, fill_template, load_config, new_renderer, render, write_output};
+
+#[derive(Args)]
+pub struct BuildArgs {
+    /// Glob patterns for the input files, e.g. "docs/**/*.md" (quoted, so the
+    /// shell doesn't expand them)
+    #[arg(required = true)]
+    patterns: Vec<String>,
+
+    /// Directory for the output files, mirroring the structure below each
+    /// pattern's base directory
+    #[arg(long, default_value = "dist")]
+    out_dir: PathBuf,
+
+    #[command(flatten)]
+    render: RenderArgs,
+}
+
+/// Convert every matched file in parallel and print a summary, or a JSON array
+/// of per-file results. Returns whether all files converted successfully.
+pub fn run(args: &BuildArgs, json: bool) -> bool {
+    let inputs = match find_inputs(&args.patterns) {
+        Ok(inputs) if inputs.is_empty() => {
+            Err(format!("no files match {}", args.patterns.join(", ")))
+        }
+        result => result,
+    };
+    let inputs = match inputs {
+        Ok(inputs) => inputs,
+        Err(e) => {
+            let mut outcome = Outcome::new(args.patterns.join(", "));
+            outcome.finish(Err(vec![Diagnostic::error(e)]));
+            report(&[outcome], json);
+            return false;
+        }
+    };
+
+    let extension = args.render.format[0].extension();
+    let outcomes: Vec<Outcome> = inputs
+        .par_iter()
+        .map(|(input, relative)| {
+            let output = args.out_dir.join(relative).with_extension(extension);
+            let mut outcome = Outcome::new(input.display().to_string());
+            let result = convert(input, &output, &args.render, &mut outcome);
+            outcome.finish(result);
+            outcome
+        })
+        .collect();
+
+    report(&outcomes, json);
+    outcomes.iter().all(|outcome| outcome.success)
+}
+
+fn report(outcomes: &[Outcome], json: bool) {
+    if json {
+        match serde_json::to_string_pretty(outcomes) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("Error: {}", e),
+        }
+        return;
+    }
+    for outcome in outcomes {
+        outcome.print_messages();
+    }
+    let failed = outcomes.iter().filter(|outcome| !outcome.success).count();
+    println!("{} converted, {} failed", outcomes.len() - failed, failed);
+}
+
+/// Convert one file with its own config, recording the paths written in `outcome`.
+fn convert(
+    input: &Path,
+    output: &Path,
+    options: &RenderArgs,
+    outcome: &mut Outcome,
+) -> Result<(), Vec<Diagnostic>> {
+    let error = |message: String| vec![Diagnostic::error(message)];
+    let markdown = fs::read_to_string(input).map_err(|e| error(e.to_string()))?;
+    let config = load_config(input, &markdown, &options.settings).map_err(error)?;
+    let markdown =
+        fill_template(markdown, options.settings.data.as_deref(), &config).map_err(error)?;
+    let renderer = new_renderer(input, config, &options.settings).map_err(error)?;
+    let rendered = render(&renderer, &markdown, options, None, output)?;
+    outcome.pages = rendered.pages;
+    outcome.diagnostics = rendered.warnings;
+
+    if let Some(dir) = output.parent() {
+        fs::create_dir_all(dir).map_err(|e| error(format!("creating {}: {}", dir.display(), e)))?;
+    }
+    for (path, bytes) in rendered.files {
+        write_output(&path, &bytes)?;
+        outcome.outputs.push(path);
+    }
+    Ok(())
+}
+
+/// Expand glob patterns into `(input, path relative to the pattern's base)` pairs,
+/// sorted and without duplicates.
+fn find_inputs(patterns: &[String]) -> Result<Vec<(PathBuf, PathBuf)>, String> {
+    let mut inputs = Vec::new();
+    for pattern in patterns {
+        let (base, rest) = split_glob(pattern);
+        if rest.as_os_str().is_empty() {
+            // A plain file name without wildcards
+            let file = PathBuf::from(pattern);
+            let name = file.file_name().map(PathBuf::from).unwrap_or_default();
+            inputs.push((file, name));
+            continue;
+        }
+
+        let matcher = GlobBuilder::new(&rest.to_string_lossy())
+            .literal_separator(true)
+            .build()
+            .map_err(|e| format!("invalid pattern {}: {}", pattern, e))?
+            .compile_matcher();
+        let root = if base.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            base.as_path()
+        };
+        for entry in WalkDir::new(root).into_iter().filter_map(Result::ok) {
+            let Ok(relative) = entry.path().strip_prefix(root) else {
+                continue;
+            };
+            if entry.file_type().is_file() && matcher.is_match(relative) {
+                inputs.push((entry.path().to_path_buf(), relative.to_path_buf()));
+            }
+        }
+    }
+    inputs.sort();
+    inputs.dedup_by(|a, b| a.0 == b.0);
+    Ok(inputs)
+}
+
+/// Split a glob into its leading directories without wildcards and the rest:
+/// `docs/**/*.md` becomes (`docs`, `**/*.md`).
+fn split_glob(pattern: &str) -> (PathBuf, PathBuf) {
+    let mut base = PathBuf::new();
+    let mut rest = PathBuf::new();
+    for component in Path::new(pattern).components() {
+        let is_glob = matches!(component, Component::Normal(part)
+            if part.to_string_lossy().contains(['*', '?', '[', '{']));
+        if is_glob || !rest.as_os_str().is_empty() {
+            rest.push(component);
+        } else {
+            base.push(component);
+        }
+    }
+    if rest.as_os_str().is_empty() {
+        return (PathBuf::from(pattern), PathBuf::new());
+    }
+    (base, rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::split_glob;
+
+    #[test]
+    fn glob_base() {
+        assert_eq!(
+            split_glob("docs/**/*.md"),
+            (PathBuf::from("docs"), PathBuf::from("**/*.md"))
+        );
+        assert_eq!(split_glob("*.md"), (PathBuf::new(), PathBuf::from("*.md")));
+        assert_eq!(
+            split_glob("a/b/ch?.md"),
+            (PathBuf::from("a/b"), PathBuf::from("ch?.md"))
+        );
+        assert_eq!(
+            split_glob("notes.md"),
+            (PathBuf::from("notes.md"), PathBuf::new())
+        );
+    }
+}
@@ -0,0 +1,59 @@
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+use pdf_core::Diagnostic;
+
+use crate::{
+    ConfigArgs, Outcome, display_name, fill_template, load_config, new_renderer, read_input,
+};
+
+#[derive(Args)]
+pub struct CheckArgs {
+    /// Markdown files to check, or `-` for stdin
+    #[arg(required = true)]
+    inputs: Vec<PathBuf>,
+
+    #[command(flatten)]
+    settings: ConfigArgs,
+}
+
+/// Parse and compile every input without writing output, printing problems
+/// (or a JSON array of per-file results). Returns whether no file has errors;
+/// warnings alone don't fail the check.
+pub fn run(args: &CheckArgs, json: bool) -> bool {
+    let outcomes: Vec<Outcome> = args
+        .inputs
+        .iter()
+        .map(|input| {
+            let mut outcome = Outcome::new(display_name(input));
+            match check(input, &args.settings) {
+                Ok(diagnostics) => outcome.diagnostics = diagnostics,
+                Err(e) => outcome.diagnostics.push(Diagnostic::error(e)),
+            }
+            outcome.success = !outcome.diagnostics.iter().any(Diagnostic::is_error);
+            outcome
+        })
+        .collect();
+
+    if json {
+        match serde_json::to_string_pretty(&outcomes) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("Error: {}", e),
+        }
+    } else {
+        for outcome in &outcomes {
+            outcome.print_messages();
+        }
+        let failed = outcomes.iter().filter(|outcome| !outcome.success).count();
+        println!("{} passed, {} failed", outcomes.len() - failed, failed);
+    }
+    outcomes.iter().all(|outcome| outcome.success)
+}
+
+fn check(input: &Path, settings: &ConfigArgs) -> Result<Vec<Diagnostic>, String> {
+    let markdown =
+        read_input(input).map_err(|e| format!("reading {}: {}", display_name(input), e))?;
+    let config = load_config(input, &markdown, settings).map_err(|e| format!("config: {}", e))?;
+    let markdown = fill_template(markdown, settings.data.as_deref(), &config)?;
+    Ok(new_renderer(input, config, settings)?.check(&markdown))
+}
@@ -0,0 +1,130 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+use pdf_core::{PageChange, PageDiff};
+use serde::Serialize;
+
+use crate::{ConfigArgs, fill_template, load_config, new_renderer};
+
+#[derive(Args)]
+pub struct DiffArgs {
+    /// Previous and new version of the document, or only the new one with `--against`
+    #[arg(value_name = "OLD NEW", num_args = 1..=2, required = true)]
+    files: Vec<PathBuf>,
+
+    /// Compare with an existing PDF of the previous version instead of its markdown
+    #[arg(long, value_name = "OLD_PDF")]
+    against: Option<PathBuf>,
+
+    /// Directory for the difference images, one `page-N.png` per changed page
+    #[arg(long, default_value = "diff")]
+    out_dir: PathBuf,
+
+    /// Resolution for comparing pages and for the difference images
+    #[arg(long, default_value_t = 72.0)]
+    dpi: f32,
+
+    #[command(flatten)]
+    settings: ConfigArgs,
+}
+
+/// The changed-pages report printed by `--json`.
+#[derive(Serialize)]
+struct Report {
+    old_pages: usize,
+    new_pages: usize,
+    /// Every page that is not unchanged
+    changes: Vec<PageDiff>,
+    images: Vec<PathBuf>,
+}
+
+/// Render both versions, write an image for every page that differs and print
+/// which pages changed.
+pub fn run(args: &DiffArgs, json: bool) -> Result<(), String> {
+    let (old, new) = match (&args.against, args.files.as_slice()) {
+        (Some(pdf), [new]) => (read_pdf(pdf)?, compile(new, &args.settings)?),
+        (None, [old, new]) => (compile(old, &args.settings)?, compile(new, &args.settings)?),
+        (Some(_), _) => return Err("With --against, give only the new version".to_string()),
+        (None, _) => {
+            return Err(
+                "Give the previous and the new version, or the new one and --against old.pdf"
+                    .to_string(),
+            );
+        }
+    };
+    let diffs = pdf_core::diff_documents(&old, &new, args.dpi)?;
+
+    let changes: Vec<PageDiff> = diffs
+        .into_iter()
+        .filter(|diff| diff.change != PageChange::Unchanged)
+        .collect();
+    let mut images = Vec::new();
+    if changes.iter().any(|diff| diff.image.is_some()) {
+        fs::create_dir_all(&args.out_dir)
+            .map_err(|e| format!("creating {}: {}", args.out_dir.display(), e))?;
+    }
+    for diff in &changes {
+        if let Some(image) = &diff.image {
+            let path = args.out_dir.join(format!("page-{}.png", diff.page));
+            fs::write(&path, image).map_err(|e| format!("writing {}: {}", path.display(), e))?;
+            images.push(path);
+        }
+    }
+
+    let report = Report {
+        old_pages: old.pages.len(),
+        new_pages: new.pages.len(),
+        changes,
+        images,
+    };
+    if json {
+        let json = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+        println!("{}", json);
+        return Ok(());
+    }
+    for diff in &report.changes {
+        match diff.change {
+            PageChange::Changed => println!(
+                "Page {}: changed ({:.1}% of pixels)",
+                diff.page,
+                diff.changed_ratio * 100.0
+            ),
+            PageChange::Added => println!("Page {}: added", diff.page),
+            PageChange::Removed => println!("Page {}: removed", diff.page),
+            PageChange::Unchanged => {}
+        }
+    }
+    for path in &report.images {
+        println!("Created {}", path.display());
+    }
+    println!(
+        "{} of {} pages changed",
+        report.changes.len(),
+        report.old_pages.max(report.new_pages)
+    );
+    Ok(())
+}
+
+/// Lay out an existing PDF's pages to compare them with a compiled document.
+fn read_pdf(path: &Path) -> Result<pdf_core::PagedDocument, String> {
+    let in_file = |e: String| format!("{}: {}", path.display(), e);
+    let pdf = fs::read(path).map_err(|e| in_file(e.to_string()))?;
+    let pages = lopdf::Document::load_mem(&pdf)
+        .map_err(|e| in_file(e.to_string()))?
+        .get_pages()
+        .len();
+    pdf_core::Renderer::new(pdf_core::Config::compiled_default())
+        .compile_pdf(pdf, pages)
+        .map_err(in_file)
+}
+
+fn compile(input: &Path, settings: &ConfigArgs) -> Result<pdf_core::PagedDocument, String> {
+    let in_file = |e: String| format!("{}: {}", input.display(), e);
+    let markdown = fs::read_to_string(input).map_err(|e| in_file(e.to_string()))?;
+    let config = load_config(input, &markdown, settings).map_err(in_file)?;
+    let markdown = fill_template(markdown, settings.data.as_deref(), &config).map_err(in_file)?;
+    new_renderer(input, config, settings)
+        .and_then(|renderer| renderer.compile(&markdown))
+        .map_err(in_file)
+}
@@ -0,0 +1,312 @@
+use lopdf::{Document, Object, ObjectId, Stream, dictionary};
+
+use crate::attach::inherited;
+
+/// Gray of the frames drawn around pages, and their width in points
+const FRAME: &str = "0.6 G 0.5 w";
+
+/// Share of its cell a handout page fills, leaving a margin around it
+const HANDOUT_FILL: f32 = 0.94;
+
+/// Landscape A4 in points, the sheet a booklet is printed on
+const A4_LANDSCAPE: (f32, f32) = (841.89, 595.28);
+
+/// Put `per_sheet` (2 or 4) pages of the generated PDF on each page, scaled
+/// down, to print handouts on fewer sheets. Two pages go side by side on a
+/// landscape sheet, four in a grid on a sheet the size of one page; `frames`
+/// draws a thin border around each of them.
+pub fn handout(pdf: Vec<u8>, per_sheet: usize, frames: bool) -> Result<Vec<u8>, String> {
+    let (columns, rows) = match per_sheet {
+        2 => (2, 1),
+        4 => (2, 2),
+        _ => {
+            return Err(format!(
+                "a handout has 2 or 4 pages per sheet, not {}",
+                per_sheet
+            ));
+        }
+    };
+    let grid = Grid {
+        columns,
+        rows,
+        sheet: None,
+        fill: HANDOUT_FILL,
+        frames,
+    };
+    impose(&pdf, &grid, |pages| (0..pages).map(Some).collect())
+}
+
+/// Reorder the pages of the generated PDF for a saddle-stitched booklet: two
+/// pages side by side on each side of landscape A4 sheets, so that printing
+/// double-sided (flipping on the short edge), folding the stack in the middle
+/// and stapling the fold gives the pages in order. A4 pages are scaled down to
+/// A5; blank pages fill up the last sheet.
+pub fn booklet(pdf: Vec<u8>) -> Result<Vec<u8>, String> {
+    let grid = Grid {
+        columns: 2,
+        rows: 1,
+        sheet: Some(A4_LANDSCAPE),
+        fill: 1.0,
+        frames: false,
+    };
+    impose(&pdf, &grid, booklet_order)
+}
+
+/// The pages on each side of a booklet's sheets, left to right: the last and
+/// the first page on the front of the outer sheet, the second and the one
+/// before last on its back, and so on inwards. `None` is a blank page.
+fn booklet_order(pages: usize) -> Vec<Option<usize>> {
+    let padded = pages.div_ceil(4) * 4;
+    let page = |i: usize| Some(i).filter(|&i| i < pages);
+    (0..padded / 4)
+        .flat_map(|sheet| {
+            let (outer, inner) = (2 * sheet, padded - 1 - 2 * sheet);
+            [page(inner), page(outer), page(outer + 1), page(inner - 1)]
+        })
+        .collect()
+}
+
+/// How pages are laid out on a sheet.
+struct Grid {
+    columns: usize,
+    rows: usize,
+    /// Size of the sheets in points, instead of the size of the first page
+    sheet: Option<(f32, f32)>,
+    /// Share of its cell each page fills
+    fill: f32,
+    frames: bool,
+}
+
+/// A page of the generated PDF as a form XObject, to be drawn on a sheet.
+struct Form {
+    id: ObjectId,
+    /// The page's media box: left, bottom, right, top
+    bbox: [f32; 4],
+}
+
+impl Form {
+    fn width(&self) -> f32 {
+        self.bbox[2] - self.bbox[0]
+    }
+
+    fn height(&self) -> f32 {
+        self.bbox[3] - self.bbox[1]
+    }
+}
+
+/// Replace the pages of `pdf` with sheets that each hold a grid of them.
+/// `order` gets the page count and lists the page index for each cell, sheet
+/// after sheet, row by row; `None` leaves a cell empty.
+///
+/// Unless the grid sets a size, the sheets are the size of the first page,
+/// turned to landscape when the grid is wider than tall. Links, the outline and tags point at the original pages,
+/// so the imposed PDF goes without them: it's meant for printing.
+fn impose(
+    pdf: &[u8],
+    grid: &Grid,
+    order: impl FnOnce(usize) -> Vec<Option<usize>>,
+) -> Result<Vec<u8>, String> {
+    let invalid = |e: lopdf::Error| format!("imposing pages: {}", e);
+    let mut doc = Document::load_mem(pdf).map_err(invalid)?;
+    let forms = page_forms(&mut doc)?;
+    let Some(first) = forms.first() else {
+        return Ok(pdf.to_vec());
+    };
+    let (width, height) = grid
+        .sheet
+        .unwrap_or_else(|| sheet_size(first.width(), first.height(), grid));
+    let cell_width = width / grid.columns as f32;
+    let cell_height = height / grid.rows as f32;
+
+    let root = doc
+        .catalog()
+        .and_then(|catalog| catalog.get(b"Pages"))
+        .and_then(Object::as_reference)
+        .map_err(invalid)?;
+    let cells = order(forms.len());
+    let mut sheets = Vec::new();
+    for cells in cells.chunks(grid.columns * grid.rows) {
+        let mut content = String::new();
+        let mut xobjects = lopdf::Dictionary::new();
+        for (i, page) in cells.iter().enumerate() {
+            let Some(form) = page.and_then(|page| forms.get(page)) else {
+                continue;
+            };
+            // Scaled to fit its cell and centered in it; rows go top down
+            let scale = (cell_width / form.width()).min(cell_height / form.height()) * grid.fill;
+            let (column, row) = (i % grid.columns, i / grid.columns);
+            let x = column as f32 * cell_width + (cell_width - form.width() * scale) / 2.0;
+            let y = height - (row + 1) as f32 * cell_height
+                + (cell_height - form.height() * scale) / 2.0;
+            let name = format!("P{}", i);
+            content.push_str(&format!(
+                "q {s} 0 0 {s} {} {} cm /{} Do Q\n",
+                x - form.bbox[0] * scale,
+                y - form.bbox[1] * scale,
+                name,
+                s = scale,
+            ));
+            if grid.frames {
+                content.push_str(&format!(
+                    "q {} {} {} {} {} re S Q\n",
+                    FRAME,
+                    x,
+                    y,
+                    form.width() * scale,
+                    form.height() * scale
+                ));
+            }
+            xobjects.set(name, form.id);
+        }
+        let contents = doc.add_object(Stream::new(lopdf::Dictionary::new(), content.into_bytes()));
+        sheets.push(Object::Reference(doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => root,
+            "MediaBox" => vec![0.into(), 0.into(), width.into(), height.into()],
+            "Resources" => dictionary! { "XObject" => xobjects },
+            "Contents" => contents,
+        })));
+    }
+
+    let pages = doc.get_dictionary_mut(root).map_err(invalid)?;
+    pages.set("Count", sheets.len() as i64);
+    pages.set("Kids", sheets);
+    drop_page_references(&mut doc);
+    doc.prune_objects();
+    let mut out = Vec::new();
+    doc.save_to(&mut out)
+        .map_err(|e| format!("imposing pages: {}", e))?;
+    Ok(out)
+}
+
+/// Sheet size for a grid of pages of the given size: the page itself, in
+/// landscape if the grid is wider than tall.
+fn sheet_size(width: f32, height: f32, grid: &Grid) -> (f32, f32) {
+    let (short, long) = (width.min(height), width.max(height));
+    if grid.columns as f32 * width > grid.rows as f32 * height {
+        (long, short)
+    } else {
+        (short, long)
+    }
+}
+
+/// Turn every page into a form XObject with the page's content and resources.
+fn page_forms(doc: &mut Document) -> Result<Vec<Form>, String> {
+    let invalid = |e: lopdf::Error| format!("imposing pages: {}", e);
+    let mut forms = Vec::new();
+    for id in doc.get_pages().into_values() {
+        let page = doc.get_dictionary(id).map_err(invalid)?;
+        let lookup = |key: &[u8]| {
+            page.get(key)
+                .ok()
+                .cloned()
+                .or_else(|| inherited(doc, page, key))
+        };
+        let media_box = lookup(b"MediaBox").ok_or("imposing pages: a page has no size")?;
+        let resources = lookup(b"Resources").unwrap_or_else(|| dictionary! {}.into());
+        let mut bbox = [0.0; 4];
+        for (value, object) in bbox.iter_mut().zip(
+            doc.dereference(&media_box)
+                .map_err(invalid)?
+                .1
+                .as_array()
+                .map_err(invalid)?,
+        ) {
+            *value = object.as_float().map_err(invalid)?;
+        }
+
+        let content = doc.get_page_content(id).map_err(invalid)?;
+        let mut form = Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Form",
+                "BBox" => media_box,
+                "Resources" => resources,
+            },
+            content,
+        );
+        form.compress().map_err(invalid)?;
+        forms.push(Form {
+            id: doc.add_object(form),
+            bbox,
+        });
+    }
+    Ok(forms)
+}
+
+/// Remove what refers to the original pages from the catalog, so they aren't
+/// kept around: the outline, tags, page labels and named destinations.
+fn drop_page_references(doc: &mut Document) {
+    let names = match doc.catalog_mut() {
+        Ok(catalog) => {
+            for key in [
+                &b"Outlines"[..],
+                b"StructTreeRoot",
+                b"MarkInfo",
+                b"PageLabels",
+                b"Dests",
+                b"OpenAction",
+            ] {
+                catalog.remove(key);
+            }
+            catalog.get(b"Names").cloned()
+        }
+        Err(_) => return,
+    };
+    let names = match names {
+        Ok(Object::Reference(id)) => doc.get_dictionary_mut(id).ok(),
+        Ok(_) => doc
+            .catalog_mut()
+            .and_then(|catalog| catalog.get_mut(b"Names"))
+            .and_then(Object::as_dict_mut)
+            .ok(),
+        Err(_) => None,
+    };
+    if let Some(names) = names {
+        names.remove(b"Dests");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Grid, booklet_order, sheet_size};
+
+    #[test]
+    fn sheet_orientation() {
+        let grid = |columns, rows| Grid {
+            columns,
+            rows,
+            sheet: None,
+            fill: 1.0,
+            frames: false,
+        };
+        // Two A4 pages side by side on landscape A4, four on portrait A4
+        assert_eq!(sheet_size(595.0, 842.0, &grid(2, 1)), (842.0, 595.0));
+        assert_eq!(sheet_size(595.0, 842.0, &grid(2, 2)), (595.0, 842.0));
+        // Landscape slides stack on a portrait sheet
+        assert_eq!(sheet_size(842.0, 595.0, &grid(1, 2)), (595.0, 842.0));
+    }
+
+    #[test]
+    fn saddle_stitch_order() {
+        assert_eq!(
+            booklet_order(8),
+            [7, 0, 1, 6, 5, 2, 3, 4].map(Some).to_vec()
+        );
+        // Padded with blank pages to a multiple of four
+        assert_eq!(
+            booklet_order(5),
+            [
+                None,
+                Some(0),
+                Some(1),
+                None,
+                None,
+                Some(2),
+                Some(3),
+                Some(4)
+            ]
+        );
+        assert!(booklet_order(0).is_empty());
+    }
+}
@@ -1,59 +1,701 @@
+mod attach;
+mod batch;
+mod check;
+mod diff;
+mod impose;
+mod serve;
+mod split;
+
 use std::fs;
-use std::path::PathBuf;
+use std::io::{self, Read, Write};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
 
-use clap::Parser;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use pdf_core::Diagnostic;
+use serde::Serialize;
+use tracing_subscriber::filter::{LevelFilter, Targets};
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::prelude::*;
 
 #[derive(Parser)]
 #[command(name = "pdf")]
 #[command(about = "Convert Markdown files to PDF")]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Cli {
-    /// Input Markdown file
-    input: PathBuf,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Print timings for each phase (parse, typst-gen, compile, export), the
+    /// page count and font loading to stderr
+    #[arg(short, long, global = true)]
+    verbose: bool,
+
+    /// Print the result (outputs, page count, errors and warnings with line
+    /// numbers) as JSON on stdout instead of messages
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Input Markdown files, or `-` to read from stdin. Several files are
+    /// joined in order into one document with continuous page numbering.
+    #[arg(required = true)]
+    inputs: Vec<PathBuf>,
+
+    /// Join multiple inputs without starting each file on a new page
+    #[arg(long)]
+    no_page_breaks: bool,
 
-    /// Output PDF file (defaults to input name with .pdf extension)
+    /// Output file (defaults to the first input's name with the format's extension), or `-`
+    /// for stdout. PNG pages are written next to it as `name-1.png`, `name-2.png`, ...
+    /// Reading from stdin writes to stdout unless an output file is given.
     #[arg(short, long)]
     output: Option<PathBuf>,
 
-    /// Config file (defaults to config.toml in current directory)
+    #[command(flatten)]
+    render: RenderArgs,
+
+    /// Also write the intermediate Typst markup, next to the output as `name.typ`
+    /// or to the given path (`-` for stdout). Written before compiling, so it is
+    /// available for bug reports when compilation fails.
+    #[arg(long, value_name = "PATH", num_args = 0..=1)]
+    emit_typst: Option<Option<PathBuf>>,
+
+    /// Draw change bars in the margin next to the blocks added or edited since
+    /// this earlier version of the document
+    #[arg(long, value_name = "MARKDOWN")]
+    changes_from: Option<PathBuf>,
+
+    /// Only export these pages, e.g. `3-7` or `5` (1-based, inclusive)
+    #[arg(long, value_parser = parse_page_range)]
+    pages: Option<RangeInclusive<usize>>,
+
+    /// Write one file per heading of this level or higher (1 = each `#` chapter)
+    /// into --out-dir, named like `01-introduction.pdf`
+    #[arg(
+        long,
+        value_name = "LEVEL",
+        value_parser = clap::value_parser!(u8).range(1..=6),
+        conflicts_with_all = ["output", "pages", "emit_typst"]
+    )]
+    split_level: Option<u8>,
+
+    /// Directory for the files written by --split-level (default: next to the input)
+    #[arg(long, requires = "split_level")]
+    out_dir: Option<PathBuf>,
+
+    /// Number pages continuously across the files written by --split-level
+    /// instead of starting each at 1
+    #[arg(long, requires = "split_level")]
+    continue_page_numbers: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Convert every file matching glob patterns, in parallel, into an output directory
+    Build(batch::BuildArgs),
+    /// Parse and compile without writing output, reporting broken internal links,
+    /// missing images, unsupported markdown and config problems; exits non-zero
+    /// on errors, e.g. to gate CI
+    Check(check::CheckArgs),
+    /// Render two versions of a document and write difference images for the
+    /// pages that changed, e.g. to review a documentation PR
+    Diff(diff::DiffArgs),
+    /// Preview a file in the browser, re-rendering it whenever it changes
+    Serve(serve::ServeArgs),
+}
+
+/// Options shared by single-document conversion and `pdf build`.
+#[derive(Args)]
+struct RenderArgs {
+    /// Output formats; repeat (`-f pdf -f png`) or separate with commas to get
+    /// several files from a single compile
+    #[arg(short, long, value_enum, value_delimiter = ',', default_values_t = [Format::Pdf])]
+    format: Vec<Format>,
+
+    /// Resolution for PNG output
+    #[arg(long, default_value_t = 144.0)]
+    dpi: f32,
+
+    /// Put the pages of this PDF before the generated ones, e.g. a designed
+    /// cover; repeatable
+    #[arg(long, value_name = "PDF")]
+    prepend: Vec<PathBuf>,
+
+    /// Put the pages of this PDF after the generated ones, e.g. a signed
+    /// appendix; repeatable
+    #[arg(long, value_name = "PDF")]
+    append: Vec<PathBuf>,
+
+    /// Print 2 or 4 pages on each page of the PDF, scaled down, for handouts
+    #[arg(long, value_name = "PAGES", value_parser = parse_pages_per_sheet)]
+    handout: Option<usize>,
+
+    /// Draw a thin frame around each page of a handout
+    #[arg(long, requires = "handout")]
+    handout_frames: bool,
+
+    /// Reorder the PDF's pages two per landscape A4 sheet for a saddle-stitched
+    /// A5 booklet: print double-sided, fold and staple
+    #[arg(long, conflicts_with = "handout")]
+    booklet: bool,
+
+    #[command(flatten)]
+    settings: ConfigArgs,
+}
+
+/// Where config and template data come from, for every command.
+#[derive(Args)]
+struct ConfigArgs {
+    /// Extra config file, merged over the user config and the nearest config.toml
     #[arg(short, long)]
     config: Option<PathBuf>,
+
+    /// JSON/YAML data for {{...}} placeholders (overrides template.data in config)
+    #[arg(long)]
+    data: Option<PathBuf>,
+
+    /// Override a config value, e.g. `--set page.numbers=true`; applied after
+    /// all config files, in order
+    #[arg(long = "set", value_name = "KEY=VALUE", value_parser = parse_assignment)]
+    overrides: Vec<(String, String)>,
+
+    /// Document title for the PDF metadata and title page, overriding the frontmatter
+    #[arg(long)]
+    title: Option<String>,
+
+    /// Document author for the PDF metadata and title page, overriding the frontmatter
+    #[arg(long)]
+    author: Option<String>,
+
+    /// Load every font file in this directory (and below), in addition to the
+    /// bundled fonts; repeatable
+    #[arg(long = "fonts-dir", value_name = "PATH")]
+    fonts_dirs: Vec<PathBuf>,
+
+    /// Turn colors (links, code highlighting, change bars) gray for monochrome
+    /// printing, like `--set print.grayscale=true`
+    #[arg(long)]
+    grayscale: bool,
+
+    /// Save toner on drafts: no background fills and thin rules, like
+    /// `--set print.ink_saver=true`
+    #[arg(long)]
+    ink_saver: bool,
 }
 
-fn main() {
-    let cli = Cli::parse();
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Pdf,
+    Svg,
+    Png,
+    Typst,
+    Html,
+}
 
-    // Load config
-    let config_path = cli.config.unwrap_or_else(|| PathBuf::from("config.toml"));
-    let config = pdf_core::Config::load(&config_path);
+impl Format {
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Pdf => "pdf",
+            Format::Svg => "svg",
+            Format::Png => "png",
+            Format::Typst => "typ",
+            Format::Html => "html",
+        }
+    }
+
+    /// Whether the format needs the laid-out document.
+    fn is_paged(self) -> bool {
+        matches!(self, Format::Pdf | Format::Svg | Format::Png)
+    }
+}
 
-    // Read input file
-    let markdown = match fs::read_to_string(&cli.input) {
-        Ok(content) => content,
-        Err(e) => {
-            eprintln!("Error reading {}: {}", cli.input.display(), e);
-            std::process::exit(1);
+fn parse_page_range(value: &str) -> Result<RangeInclusive<usize>, String> {
+    let (start, end) = value.split_once('-').unwrap_or((value, value));
+    let parse = |n: &str| {
+        n.trim()
+            .parse::<usize>()
+            .map_err(|_| format!("expected a page range like 3-7, got '{}'", value))
+    };
+    Ok(parse(start)?..=parse(end)?)
+}
+
+fn parse_pages_per_sheet(value: &str) -> Result<usize, String> {
+    match value.trim() {
+        "2" => Ok(2),
+        "4" => Ok(4),
+        _ => Err(format!("expected 2 or 4 pages per sheet, got '{}'", value)),
+    }
+}
+
+fn parse_assignment(value: &str) -> Result<(String, String), String> {
+    value
+        .split_once('=')
+        .map(|(key, value)| (key.trim().to_string(), value.to_string()))
+        .ok_or_else(|| format!("expected KEY=VALUE, got '{}'", value))
+}
+
+/// Path argument meaning stdin (for the input) or stdout (for the output).
+fn is_stdio(path: &Path) -> bool {
+    path == Path::new("-")
+}
+
+fn display_name(path: &Path) -> String {
+    if is_stdio(path) {
+        "<stdin>".to_string()
+    } else {
+        path.display().to_string()
+    }
+}
+
+fn read_input(path: &Path) -> io::Result<String> {
+    if is_stdio(path) {
+        let mut content = String::new();
+        io::stdin().read_to_string(&mut content)?;
+        Ok(content)
+    } else {
+        fs::read_to_string(path)
+    }
+}
+
+/// Load config: defaults < user config < nearest config.toml < --config <
+/// the markdown's frontmatter < --set < --title/--author/--grayscale/--ink-saver.
+/// Markdown from stdin picks up the config of the working directory.
+fn load_config(
+    input: &Path,
+    markdown: &str,
+    options: &ConfigArgs,
+) -> Result<pdf_core::Config, String> {
+    let anchor = if is_stdio(input) {
+        std::env::current_dir().unwrap_or_default().join("-")
+    } else {
+        input.to_path_buf()
+    };
+    let mut config = pdf_core::Config::discover(&anchor)?;
+    if !is_stdio(input) {
+        config.build_info.source = Some(input.display().to_string());
+    }
+    if let Some(path) = &options.config {
+        config.merge_file(path)?;
+    }
+    config.merge_frontmatter(markdown)?;
+    for (key, value) in &options.overrides {
+        config.set(key, value)?;
+    }
+    if let Some(title) = &options.title {
+        config.document.title = Some(title.clone());
+    }
+    if let Some(author) = &options.author {
+        config.document.author = Some(author.clone());
+    }
+    if options.grayscale {
+        config.print.grayscale = true;
+    }
+    if options.ink_saver {
+        config.print.ink_saver = true;
+    }
+    if config.build_info.git_commit {
+        config.build_info.commit = pdf_core::git_commit(&anchor);
+    }
+    Ok(config)
+}
+
+/// Create a renderer for the config, with the fonts from `--fonts-dir`, that
+/// reads images and includes relative to the input's directory.
+fn new_renderer(
+    input: &Path,
+    config: pdf_core::Config,
+    options: &ConfigArgs,
+) -> Result<pdf_core::Renderer, String> {
+    let dir = input.parent().unwrap_or(Path::new(""));
+    // `load_config` merged the frontmatter below --set and the other options
+    let mut renderer = pdf_core::Renderer::new(config)
+        .with_resolver(pdf_core::FsResolver::new(dir).trusted())
+        .with_frontmatter_merged();
+    for dir in &options.fonts_dirs {
+        renderer.add_fonts_dir(dir)?;
+    }
+    Ok(renderer)
+}
+
+/// Fill template placeholders from the data file, falling back to `template.data`.
+fn fill_template(
+    markdown: String,
+    data: Option<&Path>,
+    config: &pdf_core::Config,
+) -> Result<String, String> {
+    let data_path = data
+        .map(Path::to_path_buf)
+        .or_else(|| config.template.data.as_ref().map(PathBuf::from));
+    match data_path {
+        Some(path) => {
+            pdf_core::load_data(&path).and_then(|data| pdf_core::apply_template(&markdown, &data))
         }
+        None => Ok(markdown),
+    }
+}
+
+/// Result of converting one document, printed by `--json`.
+#[derive(Serialize, Default)]
+struct Outcome {
+    input: String,
+    success: bool,
+    outputs: Vec<PathBuf>,
+    /// Page count of the laid-out document, if it was compiled
+    pages: Option<usize>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Outcome {
+    fn new(input: String) -> Self {
+        Self {
+            input,
+            ..Self::default()
+        }
+    }
+
+    /// Record the result of a conversion step that produced `errors` on failure.
+    fn finish(&mut self, result: Result<(), Vec<Diagnostic>>) {
+        self.success = result.is_ok();
+        if let Err(errors) = result {
+            self.diagnostics.extend(errors);
+        }
+    }
+
+    /// Print created files to stdout and problems to stderr.
+    fn print_messages(&self) {
+        for path in &self.outputs {
+            println!("Created {}", path.display());
+        }
+        for diagnostic in &self.diagnostics {
+            let label = if diagnostic.is_error() {
+                "Error"
+            } else {
+                "Warning"
+            };
+            eprintln!("{} in {}: {}", label, self.input, diagnostic);
+        }
+    }
+}
+
+/// Files produced by [`render`], plus what the compiler reported.
+struct Rendered {
+    files: Vec<(PathBuf, Vec<u8>)>,
+    pages: Option<usize>,
+    warnings: Vec<Diagnostic>,
+}
+
+/// Convert markdown to the requested formats, returning the files to write.
+/// With several formats, each file is named after `output` with the format's
+/// extension; the document is compiled only once for all of them.
+fn render(
+    renderer: &pdf_core::Renderer,
+    markdown: &str,
+    options: &RenderArgs,
+    pages: Option<RangeInclusive<usize>>,
+    output: &Path,
+) -> Result<Rendered, Vec<Diagnostic>> {
+    let error = |message: String| vec![Diagnostic::error(message)];
+    let multiple = options.format.len() > 1;
+    if multiple && is_stdio(output) {
+        return Err(error(
+            "only one format can be written to stdout".to_string(),
+        ));
+    }
+
+    let has_pdf = options
+        .format
+        .iter()
+        .any(|format| matches!(format, Format::Pdf));
+    let attaches = !options.prepend.is_empty() || !options.append.is_empty();
+    if attaches && !has_pdf {
+        return Err(error(
+            "--prepend and --append only apply to PDF output".to_string(),
+        ));
+    }
+    if (options.handout.is_some() || options.booklet) && !has_pdf {
+        return Err(error(
+            "--handout and --booklet only apply to PDF output".to_string(),
+        ));
+    }
+
+    let mut warnings = Vec::new();
+    let doc = if options.format.iter().any(|format| format.is_paged()) {
+        let report = renderer.compile_report(markdown);
+        let Some(doc) = report.document else {
+            return Err(report.diagnostics);
+        };
+        warnings = report.diagnostics;
+        Some(doc)
+    } else {
+        None
+    };
+
+    let mut files = Vec::new();
+    for &format in &options.format {
+        let path = if multiple {
+            output.with_extension(format.extension())
+        } else {
+            output.to_path_buf()
+        };
+        let contents = match (format, &doc) {
+            (Format::Html, _) => vec![renderer.render_html(markdown).into_bytes()],
+            (Format::Typst, _) => vec![renderer.render_typst(markdown).into_bytes()],
+            (Format::Pdf, Some(doc)) => {
+                let pdf = renderer
+                    .document_to_pdf(doc, pages.clone())
+                    .map_err(error)?;
+                let pdf = attach::attach(pdf, &options.prepend, &options.append).map_err(error)?;
+                let pdf = match options.handout {
+                    Some(per_sheet) => impose::handout(pdf, per_sheet, options.handout_frames),
+                    None if options.booklet => impose::booklet(pdf),
+                    None => Ok(pdf),
+                };
+                vec![pdf.map_err(error)?]
+            }
+            (Format::Png, Some(doc)) => renderer
+                .document_to_png(doc, options.dpi, pages.clone())
+                .map_err(error)?,
+            (Format::Svg, Some(doc)) => renderer
+                .document_to_svg(doc, pages.clone())
+                .map_err(error)?
+                .pages
+                .into_iter()
+                .map(String::into_bytes)
+                .collect(),
+            (_, None) => unreachable!("paged formats are compiled above"),
+        };
+
+        // PNG and SVG have one file per page
+        if matches!(format, Format::Png | Format::Svg) && !is_stdio(&path) {
+            let first_page = pages.as_ref().map_or(1, |pages| *pages.start());
+            files.extend(
+                contents
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, page)| (page_path(&path, first_page + i), page)),
+            );
+        } else if contents.len() > 1 {
+            return Err(error(format!(
+                "only one {} page can be written to stdout; select it with --pages",
+                format.extension().to_uppercase()
+            )));
+        } else {
+            files.extend(
+                contents
+                    .into_iter()
+                    .map(|contents| (path.clone(), contents)),
+            );
+        }
+    }
+    Ok(Rendered {
+        files,
+        pages: doc.map(|doc| doc.pages.len()),
+        warnings,
+    })
+}
+
+/// Write a file, or to stdout for `-`.
+fn write_output(path: &Path, bytes: &[u8]) -> Result<(), Vec<Diagnostic>> {
+    let written = if is_stdio(path) {
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(bytes).and_then(|_| stdout.flush())
+    } else {
+        fs::write(path, bytes)
+    };
+    written.map_err(|e| {
+        vec![Diagnostic::error(format!(
+            "writing {}: {}",
+            display_output(path),
+            e
+        ))]
+    })
+}
+
+fn display_output(path: &Path) -> String {
+    if is_stdio(path) {
+        "<stdout>".to_string()
+    } else {
+        path.display().to_string()
+    }
+}
+
+/// Report pdf_core's warnings on stderr and, when `verbose`, its tracing spans
+/// with their duration when they close.
+fn init_output(verbose: bool) {
+    let level = if verbose {
+        LevelFilter::INFO
+    } else {
+        LevelFilter::WARN
     };
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(io::stderr)
+                .with_span_events(FmtSpan::CLOSE)
+                .with_target(false)
+                .without_time(),
+        )
+        .with(Targets::new().with_target("pdf_core", level))
+        .init();
+}
 
-    // Convert markdown to PDF
-    let pdf_bytes = match pdf_core::markdown_to_pdf_with_config(&markdown, &config) {
-        Ok(bytes) => bytes,
-        Err(e) => {
-            eprintln!("Error: {}", e);
-            std::process::exit(1);
+fn main() {
+    let cli = Cli::parse();
+    init_output(cli.verbose);
+    match &cli.command {
+        Some(Command::Build(args)) => {
+            let succeeded = batch::run(args, cli.json);
+            std::process::exit(if succeeded { 0 } else { 1 });
+        }
+        Some(Command::Check(args)) => {
+            let passed = check::run(args, cli.json);
+            std::process::exit(if passed { 0 } else { 1 });
         }
+        Some(Command::Diff(args)) => {
+            if let Err(e) = diff::run(args, cli.json) {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(Command::Serve(args)) => {
+            if let Err(e) = serve::run(args) {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        None => {}
+    }
+
+    let input_name = cli
+        .inputs
+        .iter()
+        .map(|path| display_name(path))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut outcome = Outcome::new(input_name);
+    let result = convert(&cli, &mut outcome);
+    outcome.finish(result);
+
+    if cli.json {
+        match serde_json::to_string_pretty(&outcome) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("Error: {}", e),
+        }
+    } else {
+        outcome.print_messages();
+    }
+    std::process::exit(if outcome.success { 0 } else { 1 });
+}
+
+/// Convert the inputs given on the command line, recording written files,
+/// the page count and warnings in `outcome`.
+fn convert(cli: &Cli, outcome: &mut Outcome) -> Result<(), Vec<Diagnostic>> {
+    let error = |message: String| vec![Diagnostic::error(message)];
+    let input = &cli.inputs[0];
+
+    // Read input files, each one starting on a new page unless disabled
+    let separator = if cli.no_page_breaks {
+        "\n\n"
+    } else {
+        "\n\n---pagebreak---\n\n"
     };
+    // Only the first input's frontmatter applies, and the others' images and
+    // includes are relative to their own directory rather than the first's
+    let first_dir = input.parent().unwrap_or(Path::new(""));
+    let mut parts = Vec::with_capacity(cli.inputs.len());
+    for (i, path) in cli.inputs.iter().enumerate() {
+        let content = read_input(path)
+            .map_err(|e| error(format!("reading {}: {}", display_name(path), e)))?;
+        if i == 0 {
+            parts.push(content);
+            continue;
+        }
+        let content = pdf_core::strip_frontmatter(&content);
+        let dir = path.parent().unwrap_or(Path::new(""));
+        if is_stdio(path) || dir == first_dir {
+            parts.push(content.to_string());
+        } else {
+            let dir = std::path::absolute(dir)
+                .map_err(|e| error(format!("reading {}: {}", display_name(path), e)))?;
+            parts.push(pdf_core::rebase_paths(content, &dir));
+        }
+    }
+    let markdown = parts.join(separator);
+
+    // The first input decides which config.toml and frontmatter apply
+    let config = load_config(input, &markdown, &cli.render.settings).map_err(error)?;
+    let markdown =
+        fill_template(markdown, cli.render.settings.data.as_deref(), &config).map_err(error)?;
+
+    let mut renderer = new_renderer(input, config, &cli.render.settings).map_err(error)?;
+    if let Some(path) = &cli.changes_from {
+        let previous = read_input(path)
+            .map_err(|e| error(format!("reading {}: {}", display_name(path), e)))?;
+        let previous = fill_template(
+            previous,
+            cli.render.settings.data.as_deref(),
+            renderer.config(),
+        )
+        .map_err(error)?;
+        renderer = renderer.with_changes_from(&previous);
+    }
+    if let Some(level) = cli.split_level {
+        return split::convert(cli, &renderer, &markdown, level, outcome);
+    }
 
     // Determine output path
-    let output = cli
-        .output
-        .unwrap_or_else(|| cli.input.with_extension("pdf"));
+    let output = cli.output.clone().unwrap_or_else(|| {
+        if is_stdio(input) {
+            PathBuf::from("-")
+        } else {
+            input.with_extension(cli.render.format[0].extension())
+        }
+    });
+    if cli.json && is_stdio(&output) {
+        return Err(error(
+            "--json prints to stdout, so the output needs a file name (-o)".to_string(),
+        ));
+    }
+
+    if let Some(typst_path) = &cli.emit_typst {
+        let typst_path = match typst_path {
+            Some(path) => path.clone(),
+            None if is_stdio(&output) => {
+                return Err(error(
+                    "pass a path to --emit-typst when writing to stdout".to_string(),
+                ));
+            }
+            None => output.with_extension("typ"),
+        };
+        write_output(&typst_path, renderer.render_typst(&markdown).as_bytes())?;
+        if !is_stdio(&typst_path) {
+            outcome.outputs.push(typst_path);
+        }
+    }
 
-    // Write PDF
-    if let Err(e) = fs::write(&output, pdf_bytes) {
-        eprintln!("Error writing {}: {}", output.display(), e);
-        std::process::exit(1);
+    let rendered = render(
+        &renderer,
+        &markdown,
+        &cli.render,
+        cli.pages.clone(),
+        &output,
+    )?;
+    outcome.pages = rendered.pages;
+    outcome.diagnostics = rendered.warnings;
+    for (path, bytes) in rendered.files {
+        write_output(&path, &bytes)?;
+        if !is_stdio(&path) {
+            outcome.outputs.push(path);
+        }
     }
+    Ok(())
+}
 
-    println!("Created {}", output.display());
+/// `out/report.png` becomes `out/report-3.png` for page 3.
+fn page_path(output: &Path, page: usize) -> PathBuf {
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = output.extension().unwrap_or_default().to_string_lossy();
+    output.with_file_name(format!("{}-{}.{}", stem, page, extension))
 }
@@ -0,0 +1,332 @@
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use clap::Args;
+use serde::Serialize;
+use tungstenite::handshake::server::{Callback, ErrorResponse, Request, Response};
+use tungstenite::http::{StatusCode, header};
+use tungstenite::{Message, WebSocket};
+
+use crate::{ConfigArgs, fill_template, load_config, new_renderer};
+
+/// How often the input and config files are checked for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How long a browser may take to accept an update before it is dropped, so
+/// one stalled client can't hold up the others
+const SEND_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Args)]
+pub struct ServeArgs {
+    /// Markdown file to preview
+    input: PathBuf,
+
+    /// Port for the preview server on localhost
+    #[arg(long, default_value_t = 3000)]
+    port: u16,
+
+    /// Don't open the preview in the default browser
+    #[arg(long)]
+    no_open: bool,
+
+    #[command(flatten)]
+    settings: ConfigArgs,
+}
+
+/// What the browser shows: the rendered pages, and the error that stopped the
+/// latest render (the previous pages stay visible below it).
+#[derive(Serialize, Default, Clone)]
+struct Preview {
+    pages: Vec<String>,
+    error: Option<String>,
+}
+
+type Clients = Mutex<Vec<WebSocket<TcpStream>>>;
+
+/// Serve a live SVG preview of the input until the process is stopped.
+pub fn run(args: &ServeArgs) -> Result<(), String> {
+    let markdown = fs::read_to_string(&args.input)
+        .map_err(|e| format!("reading {}: {}", args.input.display(), e))?;
+    let config = load_config(&args.input, &markdown, &args.settings)?;
+    let renderer = new_renderer(&args.input, config, &args.settings)?;
+    let listener = TcpListener::bind(("127.0.0.1", args.port))
+        .map_err(|e| format!("listening on port {}: {}", args.port, e))?;
+    let url = format!("http://127.0.0.1:{}/", args.port);
+    println!("Previewing {} at {}", args.input.display(), url);
+
+    let preview = Mutex::new(render_preview(args, &renderer, Vec::new()));
+    let clients = Clients::default();
+    if !args.no_open
+        && let Err(e) = open::that(&url)
+    {
+        eprintln!("Could not open a browser: {}", e);
+    }
+
+    thread::scope(|scope| {
+        scope.spawn(|| watch(args, renderer, &preview, &clients));
+        for stream in listener.incoming().filter_map(Result::ok) {
+            scope.spawn(|| handle(stream, args.port, &preview, &clients));
+        }
+    });
+    Ok(())
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Re-render whenever the input or one of its config files changes and push the
+/// result to every connected browser. The config is loaded again on each change,
+/// so edits to `config.toml` and the frontmatter apply right away. The renderer
+/// keeps Typst's caches between renders, so only the edited parts are laid out
+/// again.
+fn watch(
+    args: &ServeArgs,
+    mut renderer: pdf_core::Renderer,
+    preview: &Mutex<Preview>,
+    clients: &Clients,
+) {
+    let mut last_modified = watched_files(args);
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        let current = watched_files(args);
+        if current == last_modified {
+            continue;
+        }
+        last_modified = current;
+
+        let config = fs::read_to_string(&args.input)
+            .map_err(|e| format!("reading {}: {}", args.input.display(), e))
+            .and_then(|markdown| load_config(&args.input, &markdown, &args.settings));
+        let update = match config {
+            Ok(config) => {
+                renderer = renderer.with_config(config);
+                let previous_pages = lock(preview).pages.clone();
+                render_preview(args, &renderer, previous_pages)
+            }
+            Err(error) => Preview {
+                pages: lock(preview).pages.clone(),
+                error: Some(error),
+            },
+        };
+        if let Some(error) = &update.error {
+            eprintln!("Error: {}", error);
+        }
+        broadcast(clients, &update);
+        *lock(preview) = update;
+    }
+}
+
+/// The input and the config files that apply to it, with their modification
+/// times. Looked up on every poll, so a config file created later is noticed.
+fn watched_files(args: &ServeArgs) -> Vec<(PathBuf, Option<SystemTime>)> {
+    let modified = |path: &Path| fs::metadata(path).and_then(|meta| meta.modified()).ok();
+    [Some(args.input.clone())]
+        .into_iter()
+        .chain([
+            pdf_core::Config::user_config_path(),
+            pdf_core::Config::find_project_config(&args.input),
+            args.settings.config.clone(),
+        ])
+        .flatten()
+        .map(|path| {
+            let time = modified(&path);
+            (path, time)
+        })
+        .collect()
+}
+
+fn render_preview(
+    args: &ServeArgs,
+    renderer: &pdf_core::Renderer,
+    previous_pages: Vec<String>,
+) -> Preview {
+    let result = fs::read_to_string(&args.input)
+        .map_err(|e| format!("reading {}: {}", args.input.display(), e))
+        .and_then(|markdown| {
+            fill_template(markdown, args.settings.data.as_deref(), renderer.config())
+        })
+        .and_then(|markdown| renderer.render_svg(&markdown));
+    match result {
+        Ok(document) => Preview {
+            pages: document.pages,
+            error: None,
+        },
+        Err(error) => Preview {
+            pages: previous_pages,
+            error: Some(error),
+        },
+    }
+}
+
+/// Send the preview to all clients, dropping those that disconnected or are too
+/// slow to take it. The sends happen outside the lock, so clients can connect
+/// meanwhile.
+fn broadcast(clients: &Clients, preview: &Preview) {
+    let Ok(payload) = serde_json::to_string(preview) else {
+        return;
+    };
+    let mut sending = std::mem::take(&mut *lock(clients));
+    sending.retain_mut(|client| client.send(Message::text(payload.clone())).is_ok());
+    lock(clients).append(&mut sending);
+}
+
+/// Accepts a WebSocket handshake only from the preview page itself: the request
+/// must name this server, on its port, as its host, and a browser's origin must
+/// be the page. Keeps other websites, including ones that rebind their domain to
+/// 127.0.0.1, from reading the document.
+struct OwnPage(u16);
+
+impl Callback for OwnPage {
+    fn on_request(self, request: &Request, response: Response) -> Result<Response, ErrorResponse> {
+        let hosts = [
+            format!("127.0.0.1:{}", self.0),
+            format!("localhost:{}", self.0),
+        ];
+        let header = |name| {
+            request
+                .headers()
+                .get(name)
+                .map(|value| value.to_str().unwrap_or_default())
+        };
+        let host_ok =
+            header(header::HOST).is_some_and(|host| hosts.iter().any(|known| host == known));
+        let origin_ok = header(header::ORIGIN).is_none_or(|origin| {
+            hosts
+                .iter()
+                .any(|known| origin.strip_prefix("http://") == Some(known.as_str()))
+        });
+        if host_ok && origin_ok {
+            Ok(response)
+        } else {
+            let mut error = ErrorResponse::new(Some("Forbidden".to_string()));
+            *error.status_mut() = StatusCode::FORBIDDEN;
+            Err(error)
+        }
+    }
+}
+
+fn handle(stream: TcpStream, port: u16, preview: &Mutex<Preview>, clients: &Clients) {
+    // WebSocket upgrades and page requests share the port
+    let mut head = [0; 16];
+    let is_websocket = stream
+        .peek(&mut head)
+        .is_ok_and(|n| head[..n].starts_with(b"GET /ws "));
+    if !is_websocket {
+        serve_page(stream);
+        return;
+    }
+
+    let Ok(mut socket) = tungstenite::accept_hdr(stream, OwnPage(port)) else {
+        return;
+    };
+    if socket
+        .get_ref()
+        .set_write_timeout(Some(SEND_TIMEOUT))
+        .is_err()
+    {
+        return;
+    }
+    let Ok(payload) = serde_json::to_string(&*lock(preview)) else {
+        return;
+    };
+    if socket.send(Message::text(payload)).is_ok() {
+        lock(clients).push(socket);
+    }
+}
+
+fn serve_page(stream: TcpStream) {
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).is_err() {
+        return;
+    }
+    // Skip the headers, up to the blank line
+    let mut header = String::new();
+    while reader.read_line(&mut header).is_ok_and(|n| n > 2) {
+        header.clear();
+    }
+
+    let (status, body) = if request_line.starts_with("GET / ") {
+        ("200 OK", INDEX_HTML)
+    } else {
+        ("404 Not Found", "Not found")
+    };
+    let _ = write!(
+        &stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+}
+
+const INDEX_HTML: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Preview</title>
+<style>
+body { margin: 0; background: #e5e5e5; font-family: sans-serif; }
+#pages { display: flex; flex-direction: column; align-items: center; gap: 16px; padding: 16px; }
+#pages svg { background: white; box-shadow: 0 1px 4px rgba(0, 0, 0, 0.3); max-width: 100%; height: auto; }
+#error { display: none; position: sticky; top: 0; background: #b00020; color: white; padding: 8px 16px; white-space: pre-wrap; }
+</style>
+</head>
+<body>
+<div id="error"></div>
+<div id="pages"></div>
+<script>
+function connect() {
+  const socket = new WebSocket(`ws://${location.host}/ws`);
+  socket.onmessage = (event) => {
+    const preview = JSON.parse(event.data);
+    const error = document.getElementById("error");
+    error.textContent = preview.error || "";
+    error.style.display = preview.error ? "block" : "none";
+    document.getElementById("pages").innerHTML = preview.pages.join("");
+  };
+  // Reconnect after the server restarts
+  socket.onclose = () => setTimeout(connect, 1000);
+}
+connect();
+</script>
+</body>
+</html>
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::OwnPage;
+    use tungstenite::handshake::server::{Callback, Request, Response};
+
+    fn accepts(host: &str, origin: Option<&str>) -> bool {
+        let mut request = Request::builder().uri("/ws").header("Host", host);
+        if let Some(origin) = origin {
+            request = request.header("Origin", origin);
+        }
+        let request = request.body(()).unwrap();
+        OwnPage(3000)
+            .on_request(&request, Response::new(()))
+            .is_ok()
+    }
+
+    #[test]
+    fn websocket_only_from_the_preview_page() {
+        assert!(accepts("127.0.0.1:3000", Some("http://127.0.0.1:3000")));
+        assert!(accepts("localhost:3000", Some("http://localhost:3000")));
+        assert!(accepts("127.0.0.1:3000", None));
+        assert!(!accepts("127.0.0.1:3000", Some("https://example.com")));
+        // A rebound domain names itself as the host
+        assert!(!accepts(
+            "evil.example:3000",
+            Some("http://evil.example:3000")
+        ));
+        assert!(!accepts("127.0.0.1:8080", Some("http://127.0.0.1:8080")));
+    }
+}
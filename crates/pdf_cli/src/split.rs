@@ -0,0 +1,98 @@
+use std::fs;
+use std::path::PathBuf;
+
+use pdf_core::{Chapter, Diagnostic};
+
+use crate::{Cli, Outcome, is_stdio, render, write_output};
+
+/// Render each chapter of `markdown` to its own file in `--out-dir`, named like
+/// `01-introduction.pdf`, recording the files and total page count in `outcome`.
+pub fn convert(
+    cli: &Cli,
+    renderer: &pdf_core::Renderer,
+    markdown: &str,
+    level: u8,
+    outcome: &mut Outcome,
+) -> Result<(), Vec<Diagnostic>> {
+    let error = |message: String| vec![Diagnostic::error(message)];
+    let chapters = pdf_core::split_chapters(markdown, level);
+    if chapters.is_empty() {
+        return Err(error(format!(
+            "no headings of level {} or higher to split at",
+            level
+        )));
+    }
+
+    let out_dir = cli.out_dir.clone().unwrap_or_else(|| {
+        let input = &cli.inputs[0];
+        match input.parent() {
+            Some(dir) if !is_stdio(input) && !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        }
+    });
+    fs::create_dir_all(&out_dir)
+        .map_err(|e| error(format!("creating {}: {}", out_dir.display(), e)))?;
+
+    let extension = cli.render.format[0].extension();
+    let digits = chapters.len().to_string().len().max(2);
+    let mut next_page = renderer.config().page.first_number.unwrap_or(1);
+    for (i, chapter) in chapters.iter().enumerate() {
+        let name = format!("{:0digits$}-{}", i + 1, file_stem(&chapter.label));
+        let output = out_dir.join(name).with_extension(extension);
+
+        let continued;
+        let renderer = if cli.continue_page_numbers {
+            let mut config = renderer.config().clone();
+            config.page.first_number = Some(next_page);
+            continued = renderer.with_config(config);
+            &continued
+        } else {
+            renderer
+        };
+
+        let rendered = render(renderer, &chapter.markdown, &cli.render, None, &output)
+            .map_err(|diagnostics| in_document(diagnostics, chapter))?;
+        if let Some(pages) = rendered.pages {
+            next_page += pages;
+            *outcome.pages.get_or_insert(0) += pages;
+        }
+        outcome
+            .diagnostics
+            .extend(in_document(rendered.warnings, chapter));
+        for (path, bytes) in rendered.files {
+            write_output(&path, &bytes)?;
+            outcome.outputs.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Map lines in a chapter's diagnostics back to the whole document.
+fn in_document(diagnostics: Vec<Diagnostic>, chapter: &Chapter) -> Vec<Diagnostic> {
+    diagnostics
+        .into_iter()
+        .map(|diagnostic| {
+            let line = diagnostic.line.map(|line| line + chapter.line - 1);
+            diagnostic.at_line(line)
+        })
+        .collect()
+}
+
+/// A heading label made safe for file names on every platform.
+fn file_stem(label: &str) -> String {
+    let stem: String = label
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    if stem.is_empty() {
+        "chapter".to_string()
+    } else {
+        stem
+    }
+}
@@ -0,0 +1,194 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashSet};
+
+use crate::block::{Alignment, Block, List, Span};
+use crate::parser;
+
+/// Spell out the acronyms in `terms` on their first use in the running text,
+/// e.g. "Continuous Integration (CI)", and leave later uses short. An
+/// `@acronyms()` paragraph becomes a table of all of them. Blocks keep their
+/// positions, so a source line map still lines up.
+///
+/// Headings are left alone, so the outline stays short. Acronyms are whole
+/// words of letters and digits; code, links' URLs and sidenotes aren't touched.
+pub(crate) fn expand<'a>(
+    blocks: &'a [Block],
+    terms: &BTreeMap<String, String>,
+) -> Cow<'a, [Block]> {
+    if terms.is_empty() {
+        return Cow::Borrowed(blocks);
+    }
+    let mut used = HashSet::new();
+    let expanded = blocks
+        .iter()
+        .map(|block| {
+            if is_list_directive(block) {
+                return acronym_table(terms);
+            }
+            let mut block = block.clone();
+            expand_block(&mut block, terms, &mut used);
+            block
+        })
+        .collect();
+    Cow::Owned(expanded)
+}
+
+fn is_list_directive(block: &Block) -> bool {
+    match block {
+        Block::Paragraph { content } => match content.as_slice() {
+            [Span::Text(text)] => {
+                parser::parse_directive(text).is_some_and(|directive| directive.name == "acronyms")
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+fn acronym_table(terms: &BTreeMap<String, String>) -> Block {
+    Block::Table {
+        headers: vec![
+            vec![Span::Text("Acronym".to_string())],
+            vec![Span::Text("Meaning".to_string())],
+        ],
+        rows: terms
+            .iter()
+            .map(|(short, long)| {
+                vec![
+                    vec![Span::Text(short.clone())],
+                    vec![Span::Text(long.clone())],
+                ]
+            })
+            .collect(),
+        footer: 0,
+        align: vec![Alignment::Left, Alignment::Left],
+        caption: None,
+        label: None,
+        style: Vec::new(),
+    }
+}
+
+fn expand_block(block: &mut Block, terms: &BTreeMap<String, String>, used: &mut HashSet<String>) {
+    match block {
+        Block::Paragraph { content } => expand_spans(content, terms, used),
+        Block::List(list) => expand_list(list, terms, used),
+        Block::Table {
+            headers,
+            rows,
+            caption,
+            ..
+        } => {
+            for cell in headers.iter_mut().chain(rows.iter_mut().flatten()) {
+                expand_spans(cell, terms, used);
+            }
+            if let Some(caption) = caption {
+                expand_spans(caption, terms, used);
+            }
+        }
+        Block::Heading { .. }
+        | Block::CodeBlock { .. }
+        | Block::Image { .. }
+        | Block::Rule
+        | Block::PageBreak
+        | Block::MainMatter
+        | Block::RegionStart(_)
+        | Block::RegionEnd(_)
+        | Block::RestartNumbers { .. }
+        | Block::QrCode { .. }
+        | Block::Diagram { .. } => {}
+    }
+}
+
+fn expand_list(list: &mut List, terms: &BTreeMap<String, String>, used: &mut HashSet<String>) {
+    for item in &mut list.items {
+        expand_spans(&mut item.content, terms, used);
+        if let Some(nested) = &mut item.nested {
+            expand_list(nested, terms, used);
+        }
+    }
+}
+
+fn expand_spans(spans: &mut [Span], terms: &BTreeMap<String, String>, used: &mut HashSet<String>) {
+    for span in spans {
+        match span {
+            Span::Text(text) => *text = expand_text(text, terms, used),
+            Span::Bold(inner) | Span::Italic(inner) => expand_spans(inner, terms, used),
+            Span::Link { content, .. } => expand_spans(content, terms, used),
+            Span::Code(_) | Span::Reference(_) | Span::LineBreak | Span::Sidenote(_) => {}
+        }
+    }
+}
+
+fn expand_text(text: &str, terms: &BTreeMap<String, String>, used: &mut HashSet<String>) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut word_start = None;
+    for (i, ch) in text.char_indices().chain([(text.len(), ' ')]) {
+        if ch.is_alphanumeric() {
+            word_start.get_or_insert(i);
+            continue;
+        }
+        if let Some(start) = word_start.take() {
+            let word = &text[start..i];
+            match terms.get(word) {
+                Some(long) if used.insert(word.to_string()) => {
+                    out.push_str(&format!("{} ({})", long, word));
+                }
+                _ => out.push_str(word),
+            }
+        }
+        if i < text.len() {
+            out.push(ch);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use std::collections::BTreeMap;
+
+    use super::expand;
+    use crate::block::{Block, Span};
+    use crate::parser;
+
+    #[test]
+    fn first_use_is_spelled_out() {
+        let terms = BTreeMap::from([
+            ("CI".to_string(), "Continuous Integration".to_string()),
+            ("PR".to_string(), "Pull Request".to_string()),
+        ]);
+        let blocks = parser::parse(
+            "# CI\n\nOur CI runs on every PR.\n\n- CI, CIs and `CI`\n\n@acronyms()\n",
+        );
+        let expanded = expand(&blocks, &terms);
+
+        let Block::Heading { content, .. } = &expanded[0] else {
+            panic!("expected a heading");
+        };
+        assert!(matches!(content.as_slice(), [Span::Text(text)] if text == "CI"));
+        let Block::Paragraph { content } = &expanded[1] else {
+            panic!("expected a paragraph");
+        };
+        assert!(matches!(
+            content.as_slice(),
+            [Span::Text(text)] if text == "Our Continuous Integration (CI) runs on every Pull Request (PR)."
+        ));
+        let Block::List(list) = &expanded[2] else {
+            panic!("expected a list");
+        };
+        assert!(matches!(
+            list.items[0].content.as_slice(),
+            [Span::Text(text), Span::Code(code)] if text == "CI, CIs and " && code == "CI"
+        ));
+        let Block::Table { rows, .. } = &expanded[3] else {
+            panic!("expected the acronym table");
+        };
+        assert_eq!(rows.len(), 2);
+
+        assert!(matches!(
+            expand(&blocks, &BTreeMap::new()),
+            Cow::Borrowed(_)
+        ));
+    }
+}
@@ -5,8 +5,25 @@ pub enum Span {
     Bold(Vec<Span>),
     Italic(Vec<Span>),
     Code(String),
-    Link { url: String, content: Vec<Span> },
+    Link {
+        url: String,
+        content: Vec<Span>,
+    },
+    /// Cross-reference to a labelled heading, table or figure, e.g. `[@tbl:results]`
+    Reference(String),
     LineBreak,
+    /// Note in the page margin, from `^[margin: text]`
+    Sidenote(String),
+}
+
+/// Horizontal alignment of a table column
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Alignment {
+    #[default]
+    None,
+    Left,
+    Center,
+    Right,
 }
 
 /// A single list item, which can contain nested content
@@ -22,15 +39,34 @@ pub struct ListItem {
 #[derive(Debug, Clone)]
 pub struct List {
     pub ordered: bool,
+    /// Number of the first item of an ordered list
+    pub start: u64,
     pub items: Vec<ListItem>,
 }
 
+/// How the blocks between a pair of region markers are laid out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    /// Kept together on one page
+    Keep,
+    /// Spanning all columns of a multi-column page
+    Wide,
+    /// Marked with a change bar in the margin, e.g. edits since the last
+    /// approved version
+    Changed,
+    /// An HTML `<details>` element, set off from the text around it; its
+    /// `<summary>` is a bold paragraph at the start
+    Details,
+}
+
 /// Block-level elements parsed from Markdown
 #[derive(Debug, Clone)]
 pub enum Block {
     Heading {
         level: u8,
         content: Vec<Span>,
+        /// Explicit label from a `{#id}` attribute
+        id: Option<String>,
     },
     Paragraph {
         content: Vec<Span>,
@@ -39,12 +75,59 @@ pub enum Block {
         #[allow(dead_code)] // Reserved for future syntax highlighting
         language: Option<String>,
         content: String,
+        /// File name or label from a `title="..."` after the language
+        title: Option<String>,
     },
     List(List),
     Table {
         headers: Vec<Vec<Span>>,
         rows: Vec<Vec<Vec<Span>>>,
+        /// How many of the last rows are a footer, e.g. totals, repeated when
+        /// the table breaks across pages
+        footer: usize,
+        /// Per-column alignment (may be shorter than the column count)
+        align: Vec<Alignment>,
+        /// Caption from a following `Table: ...` paragraph
+        caption: Option<Vec<Span>>,
+        /// Label from a `{#tbl:id}` attribute on the caption
+        label: Option<String>,
+        /// `key=value` options of a `<!-- table: ... -->` comment right before
+        /// it, overriding `[tables]`
+        style: Vec<(String, String)>,
+    },
+    /// An image on a line of its own, e.g. `![Alt text](diagram.png "Caption")`
+    Image {
+        path: String,
+        alt: String,
+        /// Optional title, shown as a caption
+        title: Option<String>,
+        /// Label from a `{#fig:id}` attribute after it, making it a numbered figure
+        label: Option<String>,
     },
     Rule,
     PageBreak,
+    /// `---mainmatter---`: pages before it are numbered i, ii, iii, and the
+    /// numbering restarts at 1 on the new page it starts
+    MainMatter,
+    /// Start of a region written `<!-- keep -->`, `<!-- wide -->`, ...
+    RegionStart(Region),
+    /// End of a region, written `<!-- /keep -->`, `<!-- /wide -->`, ...
+    RegionEnd(Region),
+    /// `@restart_numbers(level)`: from here on, each heading of `level` or
+    /// higher starts a new page numbered 1
+    RestartNumbers {
+        level: u8,
+    },
+    /// QR code from an `@qrcode(data, size=3cm)` directive
+    QrCode {
+        data: String,
+        size: Option<String>,
+    },
+    /// A ```dot / ```graphviz code block as Graphviz rendered it, with
+    /// `diagrams.graphviz` on
+    Diagram {
+        /// The code block, shown under the error if Graphviz failed
+        code: Box<Block>,
+        svg: Result<String, String>,
+    },
 }
@@ -0,0 +1,101 @@
+use std::path::Path;
+use std::process::Command;
+
+use crate::config::BuildInfoConfig;
+
+/// The `[build_info]` footer line: today's date, the source file's name and
+/// the commit looked up for it beforehand.
+pub(crate) fn footer_line(config: &BuildInfoConfig) -> String {
+    let commit = config.commit.as_deref().filter(|_| config.git_commit);
+    let file_name = config.source.as_deref().map(|source| {
+        Path::new(source)
+            .file_name()
+            .map_or(source.into(), |name| name.to_string_lossy())
+    });
+    format_line(today().as_deref(), file_name.as_deref(), commit)
+}
+
+fn format_line(date: Option<&str>, source: Option<&str>, commit: Option<&str>) -> String {
+    let mut line = String::from("Generated");
+    if let Some(date) = date {
+        line.push_str(&format!(" {}", date));
+    }
+    if let Some(source) = source {
+        line.push_str(&format!(" from {}", source));
+    }
+    if let Some(commit) = commit {
+        line.push_str(&format!(" at commit {}", commit));
+    }
+    line
+}
+
+/// Today's UTC date as YYYY-MM-DD. The browser build has no clock to ask.
+#[cfg(not(target_arch = "wasm32"))]
+fn today() -> Option<String> {
+    let seconds = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()?
+        .as_secs();
+    let (year, month, day) = civil_date((seconds / 86_400) as i64);
+    Some(format!("{:04}-{:02}-{:02}", year, month, day))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn today() -> Option<String> {
+    None
+}
+
+/// Year, month and day of a day count since 1970-01-01 in the proleptic
+/// Gregorian calendar (Howard Hinnant's `civil_from_days`).
+fn civil_date(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Short hash of `HEAD` in the repository holding `source`, if `git` is
+/// installed and finds one.
+pub(crate) fn git_commit(source: &Path) -> Option<String> {
+    let dir = source
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()?;
+    let commit = String::from_utf8(output.stdout).ok()?;
+    (output.status.success() && !commit.trim().is_empty()).then(|| commit.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{civil_date, format_line};
+
+    #[test]
+    fn dates_and_lines() {
+        assert_eq!(civil_date(0), (1970, 1, 1));
+        assert_eq!(civil_date(11_016), (2000, 2, 29));
+        assert_eq!(civil_date(20_742), (2026, 10, 16));
+
+        assert_eq!(
+            format_line(Some("2026-10-16"), Some("spec.md"), Some("1a2b3c4")),
+            "Generated 2026-10-16 from spec.md at commit 1a2b3c4"
+        );
+        assert_eq!(format_line(None, None, None), "Generated");
+    }
+}
@@ -0,0 +1,136 @@
+use crate::block::{Alignment, Block, List, ListItem, Span};
+
+/// Builds a block AST in code, for rendering application data without writing markdown.
+///
+/// ```
+/// use pdf_core::DocumentBuilder;
+///
+/// let blocks = DocumentBuilder::new()
+///     .heading(1, "Report")
+///     .paragraph("Generated nightly.")
+///     .table(["Name", "Score"], [["Ada", "10"]])
+///     .build();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct DocumentBuilder {
+    blocks: Vec<Block>,
+}
+
+impl DocumentBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a heading (level is clamped to 1-6).
+    pub fn heading(self, level: u8, text: impl Into<String>) -> Self {
+        self.block(Block::Heading {
+            level: level.clamp(1, 6),
+            content: vec![Span::Text(text.into())],
+            id: None,
+        })
+    }
+
+    /// Add a paragraph of plain text.
+    pub fn paragraph(self, text: impl Into<String>) -> Self {
+        self.rich_paragraph(vec![Span::Text(text.into())])
+    }
+
+    /// Add a paragraph with formatted spans.
+    pub fn rich_paragraph(self, content: Vec<Span>) -> Self {
+        self.block(Block::Paragraph { content })
+    }
+
+    /// Add a code block. An empty language means no highlighting.
+    pub fn code(self, language: &str, content: impl Into<String>) -> Self {
+        let language = (!language.is_empty()).then(|| language.to_string());
+        self.block(Block::CodeBlock {
+            language,
+            content: content.into(),
+            title: None,
+        })
+    }
+
+    /// Add a flat list of plain-text items.
+    pub fn list<I, T>(self, ordered: bool, items: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        let items = items
+            .into_iter()
+            .map(|text| ListItem {
+                content: vec![Span::Text(text.into())],
+                nested: None,
+                checked: None,
+            })
+            .collect();
+        self.block(Block::List(List {
+            ordered,
+            start: 1,
+            items,
+        }))
+    }
+
+    /// Add a table of plain-text cells.
+    pub fn table<H, R, C, T>(self, headers: H, rows: R) -> Self
+    where
+        H: IntoIterator<Item = T>,
+        R: IntoIterator<Item = C>,
+        C: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        let cell = |text: T| vec![Span::Text(text.into())];
+        self.block(Block::Table {
+            headers: headers.into_iter().map(cell).collect(),
+            rows: rows
+                .into_iter()
+                .map(|row| row.into_iter().map(cell).collect())
+                .collect(),
+            footer: 0,
+            align: Vec::<Alignment>::new(),
+            caption: None,
+            label: None,
+            style: Vec::new(),
+        })
+    }
+
+    /// Add a horizontal rule.
+    pub fn rule(self) -> Self {
+        self.block(Block::Rule)
+    }
+
+    /// Add a manual page break.
+    pub fn page_break(self) -> Self {
+        self.block(Block::PageBreak)
+    }
+
+    /// Add any block, for content the shorthand methods don't cover.
+    pub fn block(mut self, block: Block) -> Self {
+        self.blocks.push(block);
+        self
+    }
+
+    pub fn build(self) -> Vec<Block> {
+        self.blocks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DocumentBuilder;
+    use crate::{Config, blocks_to_typst};
+
+    #[test]
+    fn builds_renderable_blocks() {
+        let blocks = DocumentBuilder::new()
+            .paragraph("Totals for *Q3*")
+            .code("rust", "let x = 1;")
+            .table(["Name", "Score"], [["Ada", "10"]])
+            .build();
+        let result = blocks_to_typst(&blocks, &Config::compiled_default());
+        // Text is escaped rather than interpreted as markdown
+        assert!(result.contains("Totals for \\*Q3\\*\n\n"));
+        assert!(result.contains("```rust\nlet x = 1;\n```"));
+        assert!(result.contains("  [*Name*],\n  [*Score*],\n  [Ada],\n  [10],\n"));
+    }
+}
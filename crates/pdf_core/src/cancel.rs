@@ -0,0 +1,53 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Error message returned by renders aborted through a [`CancellationToken`].
+pub const CANCELLED: &str = "Render cancelled";
+
+/// Shared flag for aborting a render from another thread, e.g. when a newer
+/// preview request supersedes one still in progress.
+///
+/// The flag is checked between pipeline phases and between exported pages.
+/// Typst can't be interrupted mid-layout: a compilation that has already
+/// started fails at the next file it reads, or else runs to completion.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Request cancellation of every render using this token (or a clone of it).
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Fail with [`CANCELLED`] once cancellation has been requested.
+    pub(crate) fn check(&self) -> Result<(), String> {
+        if self.is_cancelled() {
+            Err(CANCELLED.to_string())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CANCELLED, CancellationToken};
+
+    #[test]
+    fn clones_share_the_flag() {
+        let token = CancellationToken::new();
+        let handle = token.clone();
+        assert!(token.check().is_ok());
+        handle.cancel();
+        assert!(token.is_cancelled());
+        assert_eq!(token.check(), Err(CANCELLED.to_string()));
+    }
+}
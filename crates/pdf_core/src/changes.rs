@@ -0,0 +1,141 @@
+use crate::block::{Block, Region};
+
+/// Wrap the blocks that aren't in `previous` (added or edited since that
+/// version) in `Region::Changed`, which draws a change bar in the margin.
+///
+/// Blocks are matched whole along their longest common subsequence, so an
+/// edited paragraph counts as changed and a deleted one leaves no mark. Runs of
+/// changed blocks share one region; page breaks and region markers end a run,
+/// since they can't be inside the region's container. `lines` (the source line
+/// of each block, may be empty) gets the markers' lines inserted alongside.
+pub(crate) fn mark_changes(
+    previous: &[Block],
+    blocks: Vec<Block>,
+    lines: Vec<usize>,
+) -> (Vec<Block>, Vec<usize>) {
+    let changed = changed_blocks(previous, &blocks);
+    let has_lines = lines.len() == blocks.len();
+    let line_of = |i: usize| lines.get(i).copied().unwrap_or(0);
+
+    let mut marked = Vec::with_capacity(blocks.len());
+    let mut marked_lines = Vec::new();
+    let mut in_run = false;
+    for (i, block) in blocks.iter().enumerate() {
+        let wrap = changed[i] && is_content(block);
+        if wrap != in_run {
+            marked.push(if wrap {
+                Block::RegionStart(Region::Changed)
+            } else {
+                Block::RegionEnd(Region::Changed)
+            });
+            // The end marker belongs with the run it closes
+            marked_lines.push(line_of(if wrap { i } else { i - 1 }));
+            in_run = wrap;
+        }
+        marked.push(block.clone());
+        marked_lines.push(line_of(i));
+    }
+    if in_run {
+        marked.push(Block::RegionEnd(Region::Changed));
+        marked_lines.push(line_of(blocks.len() - 1));
+    }
+
+    if !has_lines {
+        marked_lines.clear();
+    }
+    (marked, marked_lines)
+}
+
+/// Whether a block is laid out as content, rather than a page break or marker.
+fn is_content(block: &Block) -> bool {
+    !matches!(
+        block,
+        Block::PageBreak
+            | Block::MainMatter
+            | Block::RegionStart(_)
+            | Block::RegionEnd(_)
+            | Block::RestartNumbers { .. }
+    )
+}
+
+/// For each block of `current`, whether it has no equal in `previous` along
+/// their longest common subsequence.
+fn changed_blocks(previous: &[Block], current: &[Block]) -> Vec<bool> {
+    let key = |block: &Block| format!("{:?}", block);
+    let old: Vec<String> = previous.iter().map(key).collect();
+    let new: Vec<String> = current.iter().map(key).collect();
+
+    // Unchanged head and tail, which is most of a document between versions
+    let head = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let tail = old[head..]
+        .iter()
+        .rev()
+        .zip(new[head..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_mid = &old[head..old.len() - tail];
+    let new_mid = &new[head..new.len() - tail];
+
+    // lengths[i][j]: common subsequence length of old_mid[i..] and new_mid[j..]
+    let mut lengths = vec![vec![0u32; new_mid.len() + 1]; old_mid.len() + 1];
+    for i in (0..old_mid.len()).rev() {
+        for j in (0..new_mid.len()).rev() {
+            lengths[i][j] = if old_mid[i] == new_mid[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut changed = vec![false; current.len()];
+    let (mut i, mut j) = (0, 0);
+    while j < new_mid.len() {
+        if i < old_mid.len() && old_mid[i] == new_mid[j] {
+            i += 1;
+            j += 1;
+        } else if i < old_mid.len() && lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            changed[head + j] = true;
+            j += 1;
+        }
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mark_changes;
+    use crate::block::{Block, Region};
+    use crate::parser;
+
+    fn regions(blocks: &[Block]) -> String {
+        blocks
+            .iter()
+            .map(|block| match block {
+                Block::RegionStart(Region::Changed) => '[',
+                Block::RegionEnd(Region::Changed) => ']',
+                Block::PageBreak => '|',
+                _ => '.',
+            })
+            .collect()
+    }
+
+    #[test]
+    fn changed_runs() {
+        let previous = parser::parse("# A\n\nOne\n\nTwo\n\nThree\n\nFour\n");
+        let (current, lines) = parser::parse_with_lines(
+            "# A\n\nOne\n\nTwo, edited\n\nNew\n\n---pagebreak---\n\nMore\n\nFour\n",
+        );
+        let (marked, marked_lines) = mark_changes(&previous, current, lines);
+        assert_eq!(regions(&marked), "..[..]|[.].");
+        assert_eq!(marked_lines.len(), marked.len());
+        // Markers take the lines of the blocks they enclose
+        assert_eq!(&marked_lines[2..6], &[5, 5, 7, 7]);
+
+        let (unchanged, lines) = mark_changes(&previous, previous.clone(), Vec::new());
+        assert_eq!(regions(&unchanged), ".....");
+        assert!(lines.is_empty());
+    }
+}
@@ -0,0 +1,86 @@
+use crate::block::Block;
+use crate::parser;
+use crate::typst::{collect_span_text, heading_to_label};
+
+/// A part of a markdown document starting at a heading, from [`crate::split_chapters`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chapter {
+    /// Text of the heading the chapter starts with
+    pub title: String,
+    /// The heading's label (`{#id}` or derived from its text), usable in file names
+    pub label: String,
+    /// Markdown source of the chapter, heading included
+    pub markdown: String,
+    /// 1-based line in the document where `markdown` starts
+    pub line: usize,
+}
+
+/// Split markdown before every heading of `level` or higher (1 = only `#`).
+/// Content before the first such heading is kept with the first chapter;
+/// the frontmatter is left out.
+pub fn split_chapters(markdown: &str, level: u8) -> Vec<Chapter> {
+    let (blocks, lines) = parser::parse_with_lines(markdown);
+    let starts: Vec<(usize, &Block)> = blocks
+        .iter()
+        .zip(lines)
+        .filter(|(block, _)| matches!(block, Block::Heading { level: l, .. } if *l <= level))
+        .map(|(block, line)| (line, block))
+        .collect();
+
+    let line_offsets: Vec<usize> = std::iter::once(0)
+        .chain(markdown.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let offset_of = |line: usize| {
+        line_offsets
+            .get(line - 1)
+            .copied()
+            .unwrap_or(markdown.len())
+    };
+    let body_start = markdown.len() - parser::strip_frontmatter(markdown).len();
+
+    let mut chapters = Vec::new();
+    for (i, (line, block)) in starts.iter().enumerate() {
+        let Block::Heading { content, id, .. } = block else {
+            continue;
+        };
+        let start = if i == 0 { body_start } else { offset_of(*line) };
+        let first_line = markdown[..start].matches('\n').count() + 1;
+        let end = starts
+            .get(i + 1)
+            .map_or(markdown.len(), |(next, _)| offset_of(*next));
+        let mut title = String::new();
+        collect_span_text(content, &mut title);
+        chapters.push(Chapter {
+            title,
+            label: id.clone().unwrap_or_else(|| heading_to_label(content)),
+            markdown: markdown[start..end].trim_end().to_string() + "\n",
+            line: first_line,
+        });
+    }
+    chapters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::split_chapters;
+
+    #[test]
+    fn splits_at_headings() {
+        let markdown = "---\ntitle: Handbook\n---\nPreface.\n\n# Intro\n\nText.\n\n## Details\n\nMore.\n\n# Setup {#install}\n\nSteps.\n";
+        let chapters = split_chapters(markdown, 1);
+        assert_eq!(chapters.len(), 2);
+        assert_eq!(chapters[0].title, "Intro");
+        assert_eq!(chapters[0].label, "intro");
+        assert_eq!(
+            chapters[0].markdown,
+            "Preface.\n\n# Intro\n\nText.\n\n## Details\n\nMore.\n"
+        );
+        assert_eq!(chapters[0].line, 4);
+        assert_eq!(chapters[1].label, "install");
+        assert_eq!(chapters[1].line, 14);
+        assert_eq!(chapters[1].markdown, "# Setup {#install}\n\nSteps.\n");
+
+        assert_eq!(split_chapters(markdown, 2).len(), 3);
+        assert!(split_chapters("No headings.", 1).is_empty());
+    }
+}
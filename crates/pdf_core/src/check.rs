@@ -0,0 +1,298 @@
+use std::collections::HashSet;
+
+use pulldown_cmark::{Event, Parser, Tag};
+
+use crate::block::{Block, List, Span};
+use crate::config::{Config, TablesConfig};
+use crate::data_uri;
+use crate::diagnostic::Diagnostic;
+use crate::parser;
+use crate::remote;
+use crate::resource::ResourceResolver;
+use crate::typst::heading_to_label;
+
+/// Find problems that don't need a layout: internal links and references to
+/// labels that don't exist, images that can't be read, and markdown that the
+/// converter drops or simplifies.
+pub(crate) fn check_blocks(
+    markdown: &str,
+    blocks: &[Block],
+    lines: &[usize],
+    config: &Config,
+    resolver: &dyn ResourceResolver,
+) -> Vec<Diagnostic> {
+    let mut diagnostics = unsupported_markdown(markdown);
+
+    let labels: HashSet<String> = blocks
+        .iter()
+        .filter_map(|block| match block {
+            Block::Heading { content, id, .. } => {
+                Some(id.clone().unwrap_or_else(|| heading_to_label(content)))
+            }
+            Block::Table { label, .. } | Block::Image { label, .. } => label.clone(),
+            _ => None,
+        })
+        .collect();
+
+    for (i, block) in blocks.iter().enumerate() {
+        let line = lines.get(i).copied();
+        if let Block::Heading {
+            id: Some(label), ..
+        }
+        | Block::Table {
+            label: Some(label), ..
+        }
+        | Block::Image {
+            label: Some(label), ..
+        } = block
+            && !parser::is_label(label)
+        {
+            diagnostics.push(
+                Diagnostic::error(format!(
+                    "label {{#{}}} can only have letters, digits and : _ - . (left out)",
+                    label
+                ))
+                .at_line(line),
+            );
+        }
+        for spans in block_spans(block) {
+            check_targets(spans, &labels, line, &mut diagnostics);
+        }
+        if let Block::Image { path, .. } = block
+            && let Err(e) = check_image(path, config, resolver)
+        {
+            diagnostics.push(Diagnostic::error(format!("image {}: {}", path, e)).at_line(line));
+        }
+    }
+
+    if let Some(path) = &config.page.background_image
+        && let Err(e) = resolver.resolve(path)
+    {
+        diagnostics.push(Diagnostic::error(format!(
+            "background image {}: {}",
+            path, e
+        )));
+    }
+
+    diagnostics.sort_by_key(|diagnostic| diagnostic.line);
+    diagnostics
+}
+
+/// All runs of inline content in a block.
+fn block_spans(block: &Block) -> Vec<&[Span]> {
+    match block {
+        Block::Heading { content, .. } | Block::Paragraph { content } => vec![content],
+        Block::List(list) => {
+            let mut spans = Vec::new();
+            list_spans(list, &mut spans);
+            spans
+        }
+        Block::Table {
+            headers,
+            rows,
+            caption,
+            ..
+        } => headers
+            .iter()
+            .chain(rows.iter().flatten())
+            .chain(caption)
+            .map(Vec::as_slice)
+            .collect(),
+        Block::CodeBlock { .. }
+        | Block::Image { .. }
+        | Block::Rule
+        | Block::PageBreak
+        | Block::MainMatter
+        | Block::RegionStart(_)
+        | Block::RestartNumbers { .. }
+        | Block::RegionEnd(_)
+        | Block::QrCode { .. }
+        | Block::Diagram { .. } => Vec::new(),
+    }
+}
+
+fn list_spans<'a>(list: &'a List, out: &mut Vec<&'a [Span]>) {
+    for item in &list.items {
+        out.push(&item.content);
+        if let Some(nested) = &item.nested {
+            list_spans(nested, out);
+        }
+    }
+}
+
+fn check_targets(
+    spans: &[Span],
+    labels: &HashSet<String>,
+    line: Option<usize>,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    for span in spans {
+        match span {
+            Span::Link { url, content } => {
+                if let Some(anchor) = url.strip_prefix('#')
+                    && !labels.contains(anchor)
+                {
+                    diagnostics.push(
+                        Diagnostic::error(format!("link to missing heading #{}", anchor))
+                            .at_line(line),
+                    );
+                }
+                check_targets(content, labels, line, diagnostics);
+            }
+            Span::Reference(label) if !labels.contains(label) => {
+                diagnostics.push(
+                    Diagnostic::error(format!("reference to missing label @{}", label))
+                        .at_line(line),
+                );
+            }
+            Span::Bold(inner) | Span::Italic(inner) => {
+                check_targets(inner, labels, line, diagnostics);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn check_image(path: &str, config: &Config, resolver: &dyn ResourceResolver) -> Result<(), String> {
+    if data_uri::is_data_uri(path) {
+        data_uri::decode(path).map(|_| ())
+    } else if remote::is_remote(path) {
+        // Not downloaded here; only whether the config allows it
+        if config.images.allow_remote {
+            Ok(())
+        } else {
+            Err("remote images are disabled (set images.allow_remote = true)".to_string())
+        }
+    } else {
+        resolver.resolve(path).map(|_| ())
+    }
+}
+
+/// Warn about markdown constructs that are not rendered as written.
+fn unsupported_markdown(markdown: &str) -> Vec<Diagnostic> {
+    let body = parser::strip_frontmatter(markdown);
+    let body_offset = markdown.len() - body.len();
+    let line_of = |offset: usize| markdown[..body_offset + offset].matches('\n').count() + 1;
+
+    let mut diagnostics = Vec::new();
+    // Inside an HTML table, which is rendered
+    let mut in_table = false;
+    for (event, range) in Parser::new_ext(body, parser::markdown_options()).into_offset_iter() {
+        if let Event::Html(html) = &event
+            && (in_table || parser::is_table_html(html))
+        {
+            in_table = !html.to_ascii_lowercase().contains("</table");
+            continue;
+        }
+        if let Event::Html(html) = &event
+            && let Some(options) = parser::table_style(html)
+        {
+            let mut tables = TablesConfig::default();
+            for (key, value) in options {
+                if let Err(e) = tables.set(&key, &value) {
+                    diagnostics.push(Diagnostic::warning(e).at_line(Some(line_of(range.start))));
+                }
+            }
+            continue;
+        }
+        let message = match event {
+            Event::Html(html)
+                if parser::region_marker(&html).is_some() || parser::is_details_html(&html) =>
+            {
+                continue;
+            }
+            Event::Html(_) | Event::InlineHtml(_) => "raw HTML is not rendered",
+            Event::Start(Tag::BlockQuote(_)) => "block quotes are rendered as plain paragraphs",
+            _ => continue,
+        };
+        diagnostics.push(Diagnostic::warning(message).at_line(Some(line_of(range.start))));
+    }
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check_blocks;
+    use crate::config::Config;
+    use crate::diagnostic::{Diagnostic, Severity};
+    use crate::resource::ResourceResolver;
+
+    struct Files;
+    impl ResourceResolver for Files {
+        fn resolve(&self, path: &str) -> Result<Vec<u8>, String> {
+            match path {
+                "logo.png" => Ok(vec![0]),
+                _ => Err("not found".to_string()),
+            }
+        }
+    }
+
+    #[test]
+    fn finds_broken_targets_and_images() {
+        let markdown = "# Intro\n\nSee [setup](#setup) and [intro](#intro).\n\n![Logo](logo.png)\n\n![Chart](chart.png)\n\n<div>x</div>\n\n<details><summary>More</summary>\n</details>\n\n<table>\n<tr><td>x</td></tr>\n</table>\n";
+        let (blocks, lines) = crate::parser::parse_with_resolver(markdown, &Files);
+        let diagnostics = check_blocks(
+            markdown,
+            &blocks,
+            &lines,
+            &Config::compiled_default(),
+            &Files,
+        );
+        assert_eq!(
+            diagnostics,
+            vec![
+                Diagnostic::error("link to missing heading #setup").at_line(Some(3)),
+                Diagnostic::error("image chart.png: not found").at_line(Some(7)),
+                Diagnostic::warning("raw HTML is not rendered").at_line(Some(9)),
+            ]
+        );
+        assert!(diagnostics.iter().all(|d| d.line.is_some()));
+        assert_eq!(diagnostics[2].severity, Severity::Warning);
+    }
+
+    #[test]
+    fn invalid_labels() {
+        let markdown = "![Logo](logo.png){#fig:logo}\n\n| A |\n|---|\n| 1 |\n\nTable: Results {#tbl results}\n\nSee [@fig:logo].\n";
+        let (blocks, lines) = crate::parser::parse_with_resolver(markdown, &Files);
+        let diagnostics = check_blocks(
+            markdown,
+            &blocks,
+            &lines,
+            &Config::compiled_default(),
+            &Files,
+        );
+        assert_eq!(
+            diagnostics,
+            vec![
+                Diagnostic::error(
+                    "label {#tbl results} can only have letters, digits and : _ - . (left out)"
+                )
+                .at_line(Some(3)),
+            ]
+        );
+    }
+
+    #[test]
+    fn checks_table_options() {
+        let markdown =
+            "<!-- table: striped=yes width=full color=red font-size=big -->\n| A |\n|---|\n| 1 |\n";
+        let (blocks, lines) = crate::parser::parse_with_resolver(markdown, &Files);
+        let diagnostics = check_blocks(
+            markdown,
+            &blocks,
+            &lines,
+            &Config::compiled_default(),
+            &Files,
+        );
+        assert_eq!(
+            diagnostics,
+            vec![
+                Diagnostic::warning("table option striped is true or false, not \"yes\"")
+                    .at_line(Some(1)),
+                Diagnostic::warning("unknown table option \"color\"").at_line(Some(1)),
+                Diagnostic::warning("table option font-size is a length like 8pt, not \"big\"")
+                    .at_line(Some(1)),
+            ]
+        );
+    }
+}
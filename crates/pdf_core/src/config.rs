@@ -1,17 +1,40 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+use crate::parser;
 
 // Embed default config at compile time
 static DEFAULT_CONFIG: &str = include_str!("default_config.toml");
 
-#[derive(Debug, Deserialize, Default)]
+/// File name of a project's config, looked up next to the input and in its parents
+pub const PROJECT_CONFIG: &str = "config.toml";
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(default)]
 pub struct Config {
     pub links: LinksConfig,
     pub page: PageConfig,
+    pub print: PrintConfig,
+    pub document: DocumentConfig,
+    pub letter: LetterConfig,
     pub font: FontConfig,
+    pub typography: TypographyConfig,
     pub layout: LayoutConfig,
+    pub lists: ListsConfig,
+    pub tables: TablesConfig,
+    pub footnotes: FootnotesConfig,
+    pub code: CodeConfig,
+    pub overrides: OverridesConfig,
+    pub diagrams: DiagramsConfig,
+    pub template: TemplateConfig,
+    pub html: HtmlConfig,
+    pub header: HeaderConfig,
+    pub acronyms: AcronymsConfig,
+    pub build_info: BuildInfoConfig,
+    pub limits: LimitsConfig,
+    pub images: ImagesConfig,
 }
 
 impl Config {
@@ -21,11 +44,12 @@ impl Config {
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct LinksConfig {
     pub color: String,
     pub underline: bool,
+    pub print_urls: PrintUrls,
 }
 
 impl Default for LinksConfig {
@@ -33,23 +57,256 @@ impl Default for LinksConfig {
         Self {
             color: "#1a4f8b".to_string(),
             underline: true,
+            print_urls: PrintUrls::None,
         }
     }
 }
 
-#[derive(Debug, Deserialize, Default)]
+/// How external link URLs are shown in print, where links can't be clicked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PrintUrls {
+    /// URLs are only reachable by clicking the link
+    #[default]
+    None,
+    /// URL appended in parentheses after the link text
+    Inline,
+    /// URL emitted as a footnote on the link text
+    Footnote,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(default)]
 pub struct PageConfig {
+    /// Paper size name understood by Typst, e.g. "a4", "us-letter" or "a5"
+    pub size: Option<String>,
     pub numbers: bool,
+    /// Number of the first page, e.g. to continue the numbering of a previous part
+    pub first_number: Option<usize>,
+    /// Image (SVG/PNG/JPG) drawn behind the content, e.g. a letterhead
+    pub background_image: Option<String>,
+    /// Only draw the background image on the first page
+    pub background_first_page_only: bool,
+    /// Text printed faintly across every page, e.g. "DRAFT" or "CONFIDENTIAL"
+    pub watermark: Option<String>,
+    /// Number of text columns; `<!-- wide -->` regions span all of them
+    pub columns: Option<usize>,
+    /// Lay out for double-sided printing: a wider inside margin, and headers,
+    /// footers and sidenotes on the outside edge of odd and even pages
+    pub two_sided: bool,
+    /// Extra space around the trimmed page that backgrounds extend into, as a
+    /// Typst length, e.g. "3mm" for a professional printer
+    pub bleed: Option<String>,
+    /// Draw trim marks at the corners of the page, outside the bleed
+    pub crop_marks: bool,
+}
+
+/// Adjustments for printing on paper.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct PrintConfig {
+    /// Turn links, code highlighting, change bars and other colors to gray,
+    /// for monochrome printers and photocopies (images keep their colors)
+    pub grayscale: bool,
+    /// Save toner on drafts: no background fills behind code, tables and
+    /// other blocks, and thin rules, even where `[overrides]` set them
+    pub ink_saver: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct DocumentConfig {
+    /// Title in the PDF metadata (and on the title page)
+    pub title: Option<String>,
+    /// Author in the PDF metadata (and on the title page)
+    pub author: Option<String>,
+    /// Date shown with the title, e.g. "March 2025" (not interpreted)
+    pub date: Option<String>,
+    /// Summary shown in a narrower block below the title (on the title page
+    /// if there is one, else at the top); blank lines separate paragraphs
+    pub r#abstract: Option<String>,
+    /// Overall layout, e.g. `letter`
+    pub theme: Theme,
+    /// `slides` to turn the document into a presentation
+    pub mode: Mode,
+    /// Start the document with a page showing the title and author
+    pub title_page: bool,
+    /// Start the document with the title, author and date above the content,
+    /// without a page of their own (ignored with `title_page`)
+    pub title_block: bool,
+    /// List the headings with their page numbers before the content
+    pub toc: bool,
+    /// Leave `<details>` elements out, e.g. for a printed version without
+    /// the optional parts
+    pub omit_details: bool,
+}
+
+/// Overall layout of a document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    #[default]
+    Default,
+    /// Business letter: sender, recipient, date and subject above the text
+    /// and space for a signature below it, from `[letter]` and `document.date`
+    Letter,
+    /// Resume/CV: the leading `# Name` and contact line side by side, compact
+    /// headings and tight lists
+    Resume,
+}
+
+/// What the pages are for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    /// Pages to read or print
+    #[default]
+    Document,
+    /// Landscape 16:9 pages with large text, each `#` or `##` heading
+    /// starting a new slide
+    Slides,
 }
 
-#[derive(Debug, Deserialize, Default)]
+/// Addresses and subject of a `letter` themed document.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct LetterConfig {
+    /// Sender's name and address, one line per line
+    pub sender: Option<String>,
+    /// Recipient's name and address, one line per line
+    pub recipient: Option<String>,
+    pub subject: Option<String>,
+    /// Name printed below the space for the signature
+    pub signature: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(default)]
 pub struct FontConfig {
     pub sans: bool,
+    /// Body font family, e.g. one loaded with `--fonts-dir` (takes precedence over `sans`)
+    pub family: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct TypographyConfig {
+    /// Number the lines of body text in the margin, e.g. for contracts
+    pub line_numbers: bool,
+    /// Only print the number of every nth line (default 1, every line)
+    pub line_number_step: Option<usize>,
+    /// Replace exotic spaces and non-breaking hyphens with plain ones and drop
+    /// zero-width characters, which copy-pasted text often brings along
+    pub clean_characters: bool,
+    /// Start the first paragraph after each level 1 heading with a large
+    /// initial letter spanning several lines, as in books
+    pub drop_caps: bool,
+    /// Letter-spacing of body text as a Typst length, e.g. "0.01em"
+    pub tracking: Option<String>,
+    pub h1_tracking: Option<String>,
+    pub h2_tracking: Option<String>,
+    pub h3_tracking: Option<String>,
+    pub h4_tracking: Option<String>,
+    pub h5_tracking: Option<String>,
+    pub h6_tracking: Option<String>,
+    /// OpenType features per element
+    pub features: FontFeaturesConfig,
+}
+
+impl TypographyConfig {
+    /// Get the letter-spacing for a heading level, if set.
+    pub fn tracking_for_heading(&self, level: u8) -> Option<&str> {
+        match level {
+            1 => self.h1_tracking.as_deref(),
+            2 => self.h2_tracking.as_deref(),
+            3 => self.h3_tracking.as_deref(),
+            4 => self.h4_tracking.as_deref(),
+            5 => self.h5_tracking.as_deref(),
+            6 => self.h6_tracking.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Refuse letter-spacings that aren't lengths, which would otherwise break
+    /// the Typst markup they're pasted into.
+    fn check(&self) -> Result<(), String> {
+        let headings = (1..=6).map(|level| {
+            (
+                format!("h{}_tracking", level),
+                self.tracking_for_heading(level),
+            )
+        });
+        for (key, value) in
+            std::iter::once(("tracking".to_string(), self.tracking.as_deref())).chain(headings)
+        {
+            if let Some(value) = value
+                && !is_length(value.strip_prefix('-').unwrap_or(value))
+            {
+                return Err(format!(
+                    "typography.{} is a length like 0.02em or -0.5pt, not {:?}",
+                    key, value
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// OpenType feature tags to turn on per element, e.g. "smcp" (small caps),
+/// "onum" (oldstyle numerals) or "tnum" (tabular figures).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FontFeaturesConfig {
+    /// Standard and contextual ligatures ("liga", "calt") in text outside code
+    pub ligatures: bool,
+    /// The same in code, where fonts like Fira Code turn `!=` into `≠`
+    pub code_ligatures: bool,
+    pub body: Vec<String>,
+    /// All headings
+    pub headings: Vec<String>,
+    /// Top-level headings only
+    pub h1: Vec<String>,
+    pub h2: Vec<String>,
+    pub h3: Vec<String>,
+    pub tables: Vec<String>,
+    pub code: Vec<String>,
+}
+
+impl Default for FontFeaturesConfig {
+    fn default() -> Self {
+        Self {
+            ligatures: true,
+            code_ligatures: true,
+            body: Vec::new(),
+            headings: Vec::new(),
+            h1: Vec::new(),
+            h2: Vec::new(),
+            h3: Vec::new(),
+            tables: Vec::new(),
+            code: Vec::new(),
+        }
+    }
+}
+
+impl FontFeaturesConfig {
+    /// Typst selectors paired with their features, for the elements that have any.
+    pub fn show_rules(&self) -> Vec<(&'static str, &[String])> {
+        [
+            ("heading", &self.headings),
+            ("heading.where(level: 1)", &self.h1),
+            ("heading.where(level: 2)", &self.h2),
+            ("heading.where(level: 3)", &self.h3),
+            ("table", &self.tables),
+            ("raw", &self.code),
+        ]
+        .into_iter()
+        .filter(|(_, features)| !features.is_empty())
+        .map(|(selector, features)| (selector, features.as_slice()))
+        .collect()
+    }
 }
 
-#[derive(Debug, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(default)]
 pub struct LayoutConfig {
     pub h1_min_space: Option<String>,
@@ -96,12 +353,897 @@ impl LayoutConfig {
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct ListsConfig {
+    /// Bullets of unordered lists, one per nesting level, repeating from the
+    /// first past the last. Empty keeps Typst's.
+    pub bullets: Vec<String>,
+    /// Number a numbered list starting at 1 on from the one before it, when
+    /// only paragraphs, code or the like come between them
+    pub continue_numbering: bool,
+}
+
+/// Look of tables; a `<!-- table: striped=true width=full -->` comment right
+/// before a table overrides it for that table.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct TablesConfig {
+    /// Shade every other row
+    pub striped: bool,
+    pub width: TableWidth,
+    /// Text size in tables, e.g. "9pt"; unset keeps the body text's
+    pub font_size: Option<String>,
+}
+
+impl TablesConfig {
+    /// Set one `key=value` option of a `<!-- table: ... -->` comment.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "striped" => {
+                self.striped = value.parse().map_err(|_| {
+                    format!("table option striped is true or false, not {:?}", value)
+                })?
+            }
+            "width" => {
+                self.width = match value {
+                    "auto" => TableWidth::Auto,
+                    "full" => TableWidth::Full,
+                    _ => {
+                        return Err(format!(
+                            "table option width is auto or full, not {:?}",
+                            value
+                        ));
+                    }
+                }
+            }
+            // Pasted into the Typst markup, so it has to be a length
+            "font-size" | "font_size" if is_length(value) => {
+                self.font_size = Some(value.to_string())
+            }
+            "font-size" | "font_size" => {
+                return Err(format!(
+                    "table option font-size is a length like 8pt, not {:?}",
+                    value
+                ));
+            }
+            _ => return Err(format!("unknown table option {:?}", key)),
+        }
+        Ok(())
+    }
+
+    /// These settings with a table's options applied; invalid ones are left
+    /// out (`check` reports them).
+    pub fn with_options(&self, options: &[(String, String)]) -> TablesConfig {
+        let mut tables = self.clone();
+        for (key, value) in options {
+            let _ = tables.set(key, value);
+        }
+        tables
+    }
+}
+
+/// Whether `value` is a Typst length like `8pt`, `0.9em` or `3mm`.
+pub(crate) fn is_length(value: &str) -> bool {
+    ["pt", "mm", "cm", "in", "em"].iter().any(|unit| {
+        value.strip_suffix(unit).is_some_and(|number| {
+            number.chars().all(|c| c.is_ascii_digit() || c == '.') && number.parse::<f64>().is_ok()
+        })
+    })
+}
+
+/// How wide tables are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TableWidth {
+    /// As wide as their content
+    #[default]
+    Auto,
+    /// The width of the text, columns sharing it equally
+    Full,
+}
+
+/// Look of footnotes, e.g. the URLs of links with `links.print_urls = "footnote"`.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct FootnotesConfig {
+    pub numbering: FootnoteNumbering,
+    /// Text size of the notes at the bottom of the page, e.g. "8pt"
+    pub font_size: Option<String>,
+    /// Length of the rule above the notes, e.g. "30%" of the text width or
+    /// "5cm"; "0pt" leaves it out
+    pub separator_length: Option<String>,
+    /// Number the notes from the first again on every page, instead of on
+    /// through the document
+    pub per_page: bool,
+}
+
+impl FootnotesConfig {
+    /// Refuse sizes that aren't lengths, which would otherwise break the
+    /// Typst markup they're pasted into.
+    fn check(&self) -> Result<(), String> {
+        if let Some(size) = &self.font_size
+            && !is_length(size)
+        {
+            return Err(format!(
+                "footnotes.font_size is a length like 8pt, not {:?}",
+                size
+            ));
+        }
+        if let Some(length) = &self.separator_length
+            && !is_length(length)
+            && !length
+                .strip_suffix('%')
+                .is_some_and(|number| number.parse::<f64>().is_ok_and(|n| n >= 0.0))
+        {
+            return Err(format!(
+                "footnotes.separator_length is a length like 5cm or 30%, not {:?}",
+                length
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// How footnotes are marked in the text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FootnoteNumbering {
+    /// 1, 2, 3
+    #[default]
+    Numbers,
+    /// *, †, ‡, §, ¶, ‖, then doubled: **, ††
+    Symbols,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct CodeConfig {
+    /// Make code blocks with a `title="..."` numbered listings, captioned
+    /// "Listing 1: ..." above the code, instead of just labelling them
+    pub number_listings: bool,
+    /// What to do with code lines too long for the page; unset leaves them
+    /// to wrap at spaces, and run into the margin where there are none
+    pub overflow: Option<CodeOverflow>,
+    /// Expand tabs to the next multiple of this many columns
+    pub tab_width: Option<usize>,
+}
+
+/// How code blocks fit lines too long for the page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CodeOverflow {
+    /// Break lines anywhere at the width of the page, marking where they continue
+    Wrap,
+    /// Scale the block's text down until its longest line fits
+    Shrink,
+    /// Leave the lines as they are, with a warning about the block
+    ScrollNote,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiagramsConfig {
+    /// Render ```dot / ```graphviz code blocks as diagrams
+    pub graphviz: bool,
+    /// Graphviz executable used for rendering
+    pub dot_path: String,
+}
+
+impl Default for DiagramsConfig {
+    fn default() -> Self {
+        Self {
+            graphviz: false,
+            dot_path: "dot".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct TemplateConfig {
+    /// JSON/YAML file whose values fill `{{...}}` placeholders in the markdown
+    pub data: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct HtmlConfig {
+    /// Start HTML output with a table of contents linking to each heading
+    pub toc: bool,
+}
+
+/// Running header and footer lines, e.g. the document title and a
+/// confidentiality notice.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct HeaderConfig {
+    /// Line at the top of every page; `{section}` stands for the heading of
+    /// the current section, e.g. "Chapter 3 — {section}"
+    pub text: Option<String>,
+    /// Line at the bottom of every page, below the page number (also takes `{section}`)
+    pub footer: Option<String>,
+    /// Heading level `{section}` follows (default 1)
+    pub section_level: Option<u8>,
+    /// Leave the header and footer off the first page, e.g. a title page
+    pub skip_first: bool,
+    /// Header on the first page instead of `text`
+    pub first_page_header: Option<String>,
+    /// Footer on the first page instead of `footer`
+    pub first_page_footer: Option<String>,
+}
+
+/// Acronyms spelled out on first use, e.g. `CI = "Continuous Integration"`.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct AcronymsConfig {
+    /// Long form of each acronym
+    pub terms: BTreeMap<String, String>,
+}
+
+/// A footer line tracing a printout back to its source, e.g. for controlled
+/// documents.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct BuildInfoConfig {
+    /// Print the generation date and source file at the bottom of every page
+    pub footer: bool,
+    /// Add the commit checked out in the source file's git repository
+    pub git_commit: bool,
+    /// Source file named in the footer; the CLI sets this to the input file
+    pub source: Option<String>,
+    /// Commit named in the footer with `git_commit`; the CLI and app look it up
+    /// once per document with [`crate::git_commit`], not on every render
+    pub commit: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImagesConfig {
+    /// Download images referenced by `http://` or `https://` URLs
+    pub allow_remote: bool,
+    /// Hosts remote images may come from (subdomains included); empty allows any host
+    pub allowed_domains: Vec<String>,
+    /// Where downloaded images are kept (defaults to `~/.cache/pdf/images`)
+    pub cache_dir: Option<String>,
+    /// Give up on a download after this many seconds
+    pub timeout_secs: u64,
+}
+
+impl Default for ImagesConfig {
+    fn default() -> Self {
+        Self {
+            allow_remote: false,
+            allowed_domains: Vec::new(),
+            cache_dir: None,
+            timeout_secs: 10,
+        }
+    }
+}
+
+/// Caps for rendering untrusted input, e.g. in a web service. Unset means unlimited.
+///
+/// Memory isn't capped: Typst has no hook for it, so a service should run
+/// renders in a process or container with a memory limit of its own.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct LimitsConfig {
+    /// Largest accepted markdown input, in bytes
+    pub max_input_bytes: Option<usize>,
+    /// Most pages a document may lay out to
+    pub max_pages: Option<usize>,
+    /// Longest a compilation may take, in milliseconds. Typst can't be interrupted,
+    /// so a render stops waiting for it at the limit; the compilation fails at
+    /// the next file it reads or finishes unseen. While two of those are still
+    /// running, renders are refused.
+    pub max_compile_ms: Option<u64>,
+}
+
+/// Which limit from [`LimitsConfig`] a render exceeded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitExceeded {
+    InputBytes {
+        actual: usize,
+        limit: usize,
+    },
+    Pages {
+        actual: usize,
+        limit: usize,
+    },
+    CompileMs {
+        actual: u64,
+        limit: u64,
+    },
+    /// Compilations that ran over `max_compile_ms` are still running
+    Runaway {
+        running: usize,
+    },
+}
+
+impl std::fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LimitExceeded::InputBytes { actual, limit } => write!(
+                f,
+                "Input is {} bytes, more than the limit of {}",
+                actual, limit
+            ),
+            LimitExceeded::Pages { actual, limit } => write!(
+                f,
+                "Document has {} pages, more than the limit of {}",
+                actual, limit
+            ),
+            LimitExceeded::CompileMs { actual, limit } => write!(
+                f,
+                "Compilation took {} ms, more than the limit of {}",
+                actual, limit
+            ),
+            LimitExceeded::Runaway { running } => write!(
+                f,
+                "{} compilations that ran over the time limit are still running",
+                running
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
+
+impl LimitsConfig {
+    pub fn check_input(&self, markdown: &str) -> Result<(), LimitExceeded> {
+        match self.max_input_bytes {
+            Some(limit) if markdown.len() > limit => Err(LimitExceeded::InputBytes {
+                actual: markdown.len(),
+                limit,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn check_pages(&self, pages: usize) -> Result<(), LimitExceeded> {
+        match self.max_pages {
+            Some(limit) if pages > limit => Err(LimitExceeded::Pages {
+                actual: pages,
+                limit,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn check_compile_ms(&self, elapsed_ms: u64) -> Result<(), LimitExceeded> {
+        match self.max_compile_ms {
+            Some(limit) if elapsed_ms > limit => Err(LimitExceeded::CompileMs {
+                actual: elapsed_ms,
+                limit,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Raw Typst show-rule bodies, emitted as `#show <element>: <body>`.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct OverridesConfig {
+    pub heading: Option<String>,
+    pub raw: Option<String>,
+    pub table: Option<String>,
+    pub link: Option<String>,
+    /// Applies to both bullet lists and numbered lists
+    pub list: Option<String>,
+}
+
+impl OverridesConfig {
+    /// Typst selectors paired with the configured show-rule body, in emission order.
+    pub fn show_rules(&self) -> Vec<(&'static str, &str)> {
+        let mut rules = Vec::new();
+        let entries = [
+            ("heading", &self.heading),
+            ("raw", &self.raw),
+            ("table", &self.table),
+            ("link", &self.link),
+            ("list", &self.list),
+            ("enum", &self.list),
+        ];
+        for (selector, body) in entries {
+            if let Some(body) = body.as_deref() {
+                rules.push((selector, body));
+            }
+        }
+        rules
+    }
+}
+
 impl Config {
-    /// Load config from a TOML file, or return defaults if not found.
+    /// Load config from a TOML file on top of the compiled defaults,
+    /// or return defaults if not found or invalid.
     pub fn load(path: &Path) -> Self {
-        match fs::read_to_string(path) {
-            Ok(content) => toml::from_str(&content).unwrap_or_else(|_| Self::compiled_default()),
+        let mut config = Self::compiled_default();
+        match config.merge_file(path) {
+            Ok(()) => config,
             Err(_) => Self::compiled_default(),
         }
     }
+
+    /// Resolve the config for an input file by merging, in order: compiled defaults,
+    /// the user config (`~/.config/pdf/config.toml`), and the nearest `config.toml`
+    /// found walking up from the input file's directory.
+    pub fn discover(input: &Path) -> Result<Self, String> {
+        let mut config = Self::compiled_default();
+        if let Some(path) = Self::user_config_path().filter(|path| path.is_file()) {
+            config.merge_file(&path)?;
+        }
+        if let Some(path) = Self::find_project_config(input) {
+            config.merge_project_file(&path)?;
+        }
+        Ok(config)
+    }
+
+    /// Location of the per-user config file, honoring `XDG_CONFIG_HOME`.
+    pub fn user_config_path() -> Option<PathBuf> {
+        let config_home = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(config_home.join("pdf").join("config.toml"))
+    }
+
+    /// Find the nearest `config.toml` in the input file's directory or a parent, up
+    /// to the root of the git repository holding the input, if there is one.
+    pub fn find_project_config(input: &Path) -> Option<PathBuf> {
+        let input = input.canonicalize().unwrap_or_else(|_| input.to_path_buf());
+        for dir in input.ancestors().skip(1) {
+            let path = dir.join(PROJECT_CONFIG);
+            if path.is_file() {
+                return Some(path);
+            }
+            if dir.join(".git").exists() {
+                break;
+            }
+        }
+        None
+    }
+
+    /// Merge a config found by [`Self::find_project_config`]. One with sections
+    /// this config doesn't have belongs to some other tool, so it's skipped with
+    /// a warning rather than failing the render.
+    fn merge_project_file(&mut self, path: &Path) -> Result<(), String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read config {}: {}", path.display(), e))?;
+        let layer: toml::Table = toml::from_str(&content)
+            .map_err(|e| format!("Invalid config {}: {}", path.display(), e))?;
+        let sections =
+            toml::Table::try_from(Self::compiled_default()).map_err(|e| e.to_string())?;
+        if let Some(section) = layer.keys().find(|key| !sections.contains_key(*key)) {
+            tracing::warn!(
+                path = %path.display(),
+                section = %section,
+                "skipping a config file with a section that isn't ours"
+            );
+            return Ok(());
+        }
+        self.merge(&layer)
+            .map_err(|e| format!("Invalid config {}: {}", path.display(), e))
+    }
+
+    /// Merge a TOML config file on top of this config.
+    pub fn merge_file(&mut self, path: &Path) -> Result<(), String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read config {}: {}", path.display(), e))?;
+        self.merge_toml(&content)
+            .map_err(|e| format!("Invalid config {}: {}", path.display(), e))
+    }
+
+    /// Merge config given as TOML text on top of this config.
+    pub fn merge_toml(&mut self, content: &str) -> Result<(), String> {
+        let layer: toml::Table = toml::from_str(content).map_err(|e| e.to_string())?;
+        self.merge(&layer)
+    }
+
+    /// Merge a (possibly partial) config table on top of this config.
+    /// Keys present in `layer` replace existing values; nested tables merge key by key.
+    pub fn merge(&mut self, layer: &toml::Table) -> Result<(), String> {
+        let mut merged = toml::Value::try_from(&*self).map_err(|e| e.to_string())?;
+        if let toml::Value::Table(base) = &mut merged {
+            merge_tables(base, layer);
+        }
+        let merged: Config = merged
+            .try_into()
+            .map_err(|e: toml::de::Error| e.to_string())?;
+        merged.typography.check()?;
+        merged.footnotes.check()?;
+        *self = merged;
+        Ok(())
+    }
+
+    /// Merge settings from the markdown's YAML frontmatter on top of this config:
+    /// the `pdf:` section, which holds config sections like a config file
+    /// (`pdf: {page: {size: a5}}`) but only those about the document's looks,
+    /// then the `title:`, `author:`, `date:`, `abstract:` and `acronyms:` fields
+    /// and the letter's `sender:`, `recipient:`, `subject:` and `signature:`.
+    ///
+    /// The markdown is checked against `[limits]` first, as the YAML is parsed.
+    pub fn merge_frontmatter(&mut self, markdown: &str) -> Result<(), String> {
+        let Some(yaml) = parser::frontmatter(markdown) else {
+            return Ok(());
+        };
+        self.limits
+            .check_input(markdown)
+            .map_err(|e| e.to_string())?;
+        let frontmatter: serde_yaml::Value =
+            serde_yaml::from_str(yaml).map_err(|e| format!("Invalid frontmatter: {}", e))?;
+        if let Some(settings) = frontmatter.get("pdf") {
+            let layer = toml::Table::try_from(settings)
+                .map_err(|e| format!("Invalid pdf settings in frontmatter: {}", e))?;
+            check_frontmatter_settings(&layer)?;
+            self.merge(&layer)
+                .map_err(|e| format!("Invalid pdf settings in frontmatter: {}", e))?;
+        }
+        if let Some(title) = frontmatter.get("title").and_then(yaml_text) {
+            self.document.title = Some(title);
+        }
+        if let Some(author) = frontmatter.get("author").and_then(yaml_text) {
+            self.document.author = Some(author);
+        }
+        if let Some(serde_yaml::Value::Mapping(acronyms)) = frontmatter.get("acronyms") {
+            for (short, long) in acronyms {
+                if let (Some(short), Some(long)) = (short.as_str(), long.as_str()) {
+                    self.acronyms
+                        .terms
+                        .insert(short.to_string(), long.to_string());
+                }
+            }
+        }
+        if let Some(date) = frontmatter.get("date").and_then(yaml_text) {
+            self.document.date = Some(date);
+        }
+        let letter = &mut self.letter;
+        for (key, field) in [
+            ("sender", &mut letter.sender),
+            ("recipient", &mut letter.recipient),
+            ("subject", &mut letter.subject),
+            ("signature", &mut letter.signature),
+        ] {
+            if let Some(value) = frontmatter.get(key).and_then(yaml_lines) {
+                *field = Some(value);
+            }
+        }
+        if let Some(text) = frontmatter
+            .get("abstract")
+            .and_then(serde_yaml::Value::as_str)
+        {
+            self.document.r#abstract = Some(text.to_string());
+        }
+        Ok(())
+    }
+
+    /// Override a single value by its dotted key, e.g. `set("links.color", "#ff0000")`.
+    /// The value is read as TOML (`true`, `12`, `[1, 2]`), falling back to a plain string.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let value = toml::from_str::<toml::Table>(&format!("value = {}", value))
+            .ok()
+            .and_then(|mut table| table.remove("value"))
+            .unwrap_or_else(|| toml::Value::String(value.to_string()));
+
+        let path: Vec<&str> = key.split('.').collect();
+        if path.iter().any(|part| part.is_empty()) {
+            return Err(format!("Invalid config key '{}'", key));
+        }
+        let (leaf, sections) = path.split_last().unwrap_or((&"", &[]));
+
+        // Catch typos in section names here and in leaf keys below, once an unset
+        // optional value would have shown up
+        let current = toml::Value::try_from(&*self).map_err(|e| e.to_string())?;
+        let mut section = &current;
+        for part in sections {
+            section = section
+                .get(part)
+                .filter(|value| value.is_table())
+                .ok_or_else(|| format!("Unknown config section in '{}'", key))?;
+        }
+
+        let mut layer = toml::Table::from_iter([(leaf.to_string(), value)]);
+        for part in sections.iter().rev() {
+            layer = toml::Table::from_iter([(part.to_string(), toml::Value::Table(layer))]);
+        }
+        let mut updated = self.clone();
+        updated
+            .merge(&layer)
+            .map_err(|e| format!("Invalid value for {}: {}", key, e))?;
+        let known = toml::Value::try_from(&updated)
+            .ok()
+            .and_then(|merged| {
+                sections
+                    .iter()
+                    .try_fold(merged, |section, part| section.get(part).cloned())
+            })
+            .is_some_and(|section| section.get(leaf).is_some());
+        if !known {
+            return Err(format!("Unknown config key '{}'", key));
+        }
+        *self = updated;
+        Ok(())
+    }
+}
+
+/// Config sections a document's frontmatter may set: how it looks. The
+/// markdown may come from anyone, so nothing that reads files, runs programs,
+/// fetches URLs, pastes raw Typst or lifts `[limits]` is among them.
+const FRONTMATTER_SECTIONS: &[&str] = &[
+    "links",
+    "page",
+    "print",
+    "document",
+    "letter",
+    "font",
+    "typography",
+    "layout",
+    "lists",
+    "tables",
+    "footnotes",
+    "code",
+    "html",
+    "header",
+    "acronyms",
+];
+
+/// Keys in [`FRONTMATTER_SECTIONS`] that read files, left to config files.
+const FRONTMATTER_DENIED_KEYS: &[(&str, &str)] = &[("page", "background_image")];
+
+fn check_frontmatter_settings(layer: &toml::Table) -> Result<(), String> {
+    for (section, values) in layer {
+        if !FRONTMATTER_SECTIONS.contains(&section.as_str()) {
+            return Err(format!(
+                "The frontmatter can't set [{}], only a config file can",
+                section
+            ));
+        }
+        for (denied_section, key) in FRONTMATTER_DENIED_KEYS {
+            if section == denied_section && values.get(key).is_some() {
+                return Err(format!(
+                    "The frontmatter can't set {}.{}, only a config file can",
+                    section, key
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A frontmatter string, or a list of strings as lines (e.g. an address).
+fn yaml_lines(value: &serde_yaml::Value) -> Option<String> {
+    match value {
+        serde_yaml::Value::Sequence(items) => Some(
+            items
+                .iter()
+                .filter_map(serde_yaml::Value::as_str)
+                .collect::<Vec<_>>()
+                .join("\n"),
+        ),
+        _ => yaml_text(value),
+    }
+}
+
+/// A frontmatter string, or a list of strings joined with commas (e.g. several authors).
+fn yaml_text(value: &serde_yaml::Value) -> Option<String> {
+    match value {
+        serde_yaml::Value::String(text) => Some(text.clone()),
+        serde_yaml::Value::Sequence(items) => Some(
+            items
+                .iter()
+                .filter_map(serde_yaml::Value::as_str)
+                .collect::<Vec<_>>()
+                .join(", "),
+        ),
+        _ => None,
+    }
+}
+
+fn merge_tables(base: &mut toml::Table, layer: &toml::Table) {
+    for (key, value) in layer {
+        match (base.get_mut(key), value) {
+            (Some(toml::Value::Table(base_table)), toml::Value::Table(layer_table)) => {
+                merge_tables(base_table, layer_table);
+            }
+            _ => {
+                base.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Config, LimitExceeded, PROJECT_CONFIG};
+
+    #[test]
+    fn project_config() {
+        let root = std::env::temp_dir().join("pdf_core_project_config");
+        let _ = std::fs::remove_dir_all(&root);
+        let docs = root.join("repo").join("docs");
+        std::fs::create_dir_all(&docs).unwrap();
+        std::fs::create_dir_all(root.join("repo").join(".git")).unwrap();
+        let input = docs.join("a.md");
+        std::fs::write(&input, "# A").unwrap();
+
+        // Outside the repository holding the input
+        std::fs::write(root.join(PROJECT_CONFIG), "[links]\ncolor = \"#ff0000\"").unwrap();
+        assert_eq!(Config::find_project_config(&input), None);
+
+        let path = root.join("repo").join(PROJECT_CONFIG);
+        std::fs::write(&path, "[links]\ncolor = \"#00ff00\"").unwrap();
+        let found = Config::find_project_config(&input).unwrap();
+        assert_eq!(found, path.canonicalize().unwrap());
+        let mut config = Config::compiled_default();
+        config.merge_project_file(&found).unwrap();
+        assert_eq!(config.links.color, "#00ff00");
+
+        // Another tool's file is skipped
+        std::fs::write(
+            &path,
+            "[tool]\nname = \"other\"\n[links]\ncolor = \"#0000ff\"",
+        )
+        .unwrap();
+        let mut config = Config::compiled_default();
+        config.merge_project_file(&path).unwrap();
+        assert_eq!(config.links.color, Config::compiled_default().links.color);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn merge_keeps_unset_values() {
+        let mut config = Config::compiled_default();
+        let layer: toml::Table = toml::from_str("[links]\ncolor = \"#ff0000\"").unwrap();
+        config.merge(&layer).unwrap();
+        assert_eq!(config.links.color, "#ff0000");
+        assert!(config.links.underline);
+        // Sections missing from the layer keep their defaults
+        assert_eq!(config.layout.h1_min_space.as_deref(), Some("30%"));
+    }
+
+    #[test]
+    fn set_dotted_keys() {
+        let mut config = Config::compiled_default();
+        config.set("links.color", "#ff0000").unwrap();
+        config.set("limits.max_pages", "20").unwrap();
+        config.set("html.toc", "true").unwrap();
+        assert_eq!(config.links.color, "#ff0000");
+        assert_eq!(config.limits.max_pages, Some(20));
+        assert!(config.html.toc);
+
+        assert!(config.set("html.toc", "maybe").is_err());
+        assert!(config.set("linkz.color", "red").is_err());
+        assert!(config.set("links..color", "red").is_err());
+        assert!(config.set("page.sise", "a5").is_err());
+        assert!(config.set("limits.max_pagez", "5").is_err());
+        assert_eq!(config.limits.max_pages, Some(20));
+    }
+
+    #[test]
+    fn frontmatter_metadata() {
+        let mut config = Config::compiled_default();
+        config
+            .merge_toml("[document]\nauthor = \"Docs team\"")
+            .unwrap();
+        config
+            .merge_frontmatter(
+                "---\ntitle: Handbook\nauthor: [Ann, Bo]\ndate: 2025-03-01\n---\n# Intro\n",
+            )
+            .unwrap();
+        assert_eq!(config.document.date.as_deref(), Some("2025-03-01"));
+
+        config
+            .merge_frontmatter("---\nrecipient: [Acme Corp, Street 2]\nsubject: Offer\n---\n")
+            .unwrap();
+        assert_eq!(
+            config.letter.recipient.as_deref(),
+            Some("Acme Corp\nStreet 2")
+        );
+        assert_eq!(config.letter.subject.as_deref(), Some("Offer"));
+
+        config
+            .merge_frontmatter("---\nacronyms:\n  CI: Continuous Integration\n---\n")
+            .unwrap();
+        assert_eq!(
+            config.acronyms.terms.get("CI").map(String::as_str),
+            Some("Continuous Integration")
+        );
+        assert_eq!(config.document.title.as_deref(), Some("Handbook"));
+        assert_eq!(config.document.author.as_deref(), Some("Ann, Bo"));
+
+        config
+            .merge_frontmatter("---\nabstract: |\n  We measure.\n\n  Then we report.\n---\n")
+            .unwrap();
+        assert_eq!(
+            config.document.r#abstract.as_deref(),
+            Some("We measure.\n\nThen we report.")
+        );
+
+        let markdown = "---\ntitle: Notes\npdf:\n  page:\n    size: a5\n    numbers: true\n  document:\n    toc: true\n---\n";
+        config.merge_frontmatter(markdown).unwrap();
+        assert_eq!(config.page.size.as_deref(), Some("a5"));
+        assert!(config.page.numbers && config.document.toc);
+        assert_eq!(config.document.title.as_deref(), Some("Notes"));
+        assert!(
+            config
+                .merge_frontmatter("---\npdf:\n  page:\n    numbers: maybe\n---\n")
+                .is_err()
+        );
+
+        // Nothing that reaches beyond the document's looks
+        for settings in [
+            "limits:\n    max_pages: 100000",
+            "diagrams:\n    dot_path: /bin/sh",
+            "overrides:\n    heading: \"x\"",
+            "images:\n    allow_remote: true",
+            "page:\n    background_image: /etc/passwd",
+        ] {
+            let markdown = format!("---\npdf:\n  {}\n---\n", settings);
+            assert!(
+                Config::compiled_default()
+                    .merge_frontmatter(&markdown)
+                    .is_err(),
+                "{}",
+                settings
+            );
+        }
+        let mut config = Config::compiled_default();
+        config.limits.max_input_bytes = Some(16);
+        assert!(
+            config
+                .merge_frontmatter("---\ntitle: A long title\n---\n")
+                .is_err()
+        );
+
+        let mut config = Config::compiled_default();
+        config.merge_frontmatter("# No frontmatter\n").unwrap();
+        assert_eq!(config.document.title, None);
+        assert!(config.merge_frontmatter("---\ntitle: [\n---\n").is_err());
+    }
+
+    #[test]
+    fn limits() {
+        let mut config = Config::compiled_default();
+        assert!(config.limits.check_input(&"x".repeat(1 << 20)).is_ok());
+        config
+            .merge_toml("[limits]\nmax_input_bytes = 4\nmax_pages = 2")
+            .unwrap();
+        assert!(config.limits.check_input("1234").is_ok());
+        assert_eq!(
+            config.limits.check_input("12345"),
+            Err(LimitExceeded::InputBytes {
+                actual: 5,
+                limit: 4
+            })
+        );
+        assert!(config.limits.check_pages(2).is_ok());
+        assert!(config.limits.check_pages(3).is_err());
+        assert!(config.limits.check_compile_ms(u64::MAX).is_ok());
+    }
+
+    #[test]
+    fn merge_rejects_invalid_values() {
+        let mut config = Config::compiled_default();
+        let layer: toml::Table = toml::from_str("[page]\nnumbers = \"yes\"").unwrap();
+        assert!(config.merge(&layer).is_err());
+
+        // Lengths pasted into the Typst markup
+        assert_eq!(
+            config.set("typography.tracking", "0.02 em").unwrap_err(),
+            "Invalid value for typography.tracking: typography.tracking is a length like 0.02em or -0.5pt, not \"0.02 em\""
+        );
+        assert!(config.set("typography.h2_tracking", "wide").is_err());
+        assert!(
+            config
+                .merge_frontmatter("---\npdf:\n  typography:\n    h1_tracking: 1pt)\n---\n")
+                .is_err()
+        );
+        config.set("typography.tracking", "-0.01em").unwrap();
+        config.set("typography.h1_tracking", "0.5pt").unwrap();
+        assert!(config.set("footnotes.font_size", "8").is_err());
+        assert!(config.set("footnotes.separator_length", "30 %").is_err());
+        config.set("footnotes.font_size", "7.5pt").unwrap();
+        config.set("footnotes.separator_length", "30%").unwrap();
+        config.set("footnotes.separator_length", "0pt").unwrap();
+    }
 }
@@ -0,0 +1,89 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+
+use crate::remote;
+
+/// Whether an image path is an inline `data:` URI rather than a file.
+pub fn is_data_uri(path: &str) -> bool {
+    path.starts_with("data:")
+}
+
+/// Stable virtual file name under which the decoded bytes are given to Typst,
+/// since the URI itself is not a usable path. The extension follows the media
+/// type so Typst picks the right image format.
+pub fn file_name(uri: &str) -> String {
+    let media_type = uri["data:".len()..]
+        .split([';', ','])
+        .next()
+        .unwrap_or_default();
+    let extension = match media_type.to_ascii_lowercase().as_str() {
+        "image/jpeg" | "image/jpg" => "jpg",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        "image/svg+xml" => "svg",
+        _ => "png",
+    };
+    format!("data-uri-{}.{}", remote::cache_key(uri), extension)
+}
+
+/// Decode the payload of a `data:[<media type>][;base64],<data>` URI.
+pub fn decode(uri: &str) -> Result<Vec<u8>, String> {
+    let (header, data) = uri["data:".len()..]
+        .split_once(',')
+        .ok_or_else(|| "malformed data URI (missing ',')".to_string())?;
+    if header.ends_with(";base64") {
+        // Line breaks and spaces are common in URIs copied out of other documents
+        let data: String = data.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+        STANDARD
+            .decode(data)
+            .map_err(|e| format!("invalid base64 in data URI: {}", e))
+    } else {
+        Ok(percent_decode(data))
+    }
+}
+
+fn percent_decode(data: &str) -> Vec<u8> {
+    let bytes = data.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| data.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                out.push(byte);
+                i += 3;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, file_name};
+
+    #[test]
+    fn decodes_base64_and_percent_encoding() {
+        assert_eq!(
+            decode("data:image/png;base64,iVBO\nRw==").unwrap(),
+            b"\x89PNG"
+        );
+        assert_eq!(
+            decode("data:image/svg+xml,%3Csvg%2F%3E").unwrap(),
+            b"<svg/>"
+        );
+        assert!(decode("data:image/png;base64").is_err());
+        assert!(decode("data:image/png;base64,***").is_err());
+
+        let name = file_name("data:image/jpeg;base64,/9j/");
+        assert!(name.starts_with("data-uri-") && name.ends_with(".jpg"));
+        assert_eq!(name, file_name("data:image/jpeg;base64,/9j/"));
+    }
+}
@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::{LazyLock, Mutex, PoisonError};
+
+use qrcode::QrCode;
+use qrcode::render::svg;
+
+use crate::block::Block;
+use crate::config::DiagramsConfig;
+use crate::remote;
+
+/// SVGs Graphviz rendered, by [`remote::cache_key`] of the executable and the
+/// DOT source, so re-rendering an edited document only runs it for new diagrams
+static RENDERED: LazyLock<Mutex<HashMap<String, String>>> = LazyLock::new(Mutex::default);
+
+/// Diagrams kept in [`RENDERED`] before it starts over
+const MAX_RENDERED: usize = 64;
+
+/// Turn ```dot / ```graphviz code blocks into [`Block::Diagram`]s when
+/// `diagrams.graphviz` is on.
+pub(crate) fn render_diagrams(blocks: Vec<Block>, config: &DiagramsConfig) -> Vec<Block> {
+    if !config.graphviz {
+        return blocks;
+    }
+    blocks
+        .into_iter()
+        .map(|block| match &block {
+            Block::CodeBlock {
+                language: Some(language),
+                content,
+                ..
+            } if matches!(language.as_str(), "dot" | "graphviz") => {
+                let svg = cached_dot(content, &config.dot_path);
+                Block::Diagram {
+                    code: Box::new(block),
+                    svg,
+                }
+            }
+            _ => block,
+        })
+        .collect()
+}
+
+/// [`render_dot`], reusing the SVG of an earlier render of the same source.
+fn cached_dot(source: &str, dot_path: &str) -> Result<String, String> {
+    let key = remote::cache_key(&format!("{}\n{}", dot_path, source));
+    if let Some(svg) = RENDERED
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(&key)
+    {
+        return Ok(svg.clone());
+    }
+    // Failures aren't kept: Graphviz may be installed by the next render
+    let svg = render_dot(source, dot_path)?;
+    let mut rendered = RENDERED.lock().unwrap_or_else(PoisonError::into_inner);
+    if rendered.len() >= MAX_RENDERED {
+        rendered.clear();
+    }
+    rendered.insert(key, svg.clone());
+    Ok(svg)
+}
+
+/// Render Graphviz DOT source to an SVG string using the `dot` executable.
+pub fn render_dot(source: &str, dot_path: &str) -> Result<String, String> {
+    let mut child = Command::new(dot_path)
+        .arg("-Tsvg")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run {}: {}", dot_path, e))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(source.as_bytes())
+            .map_err(|e| format!("Failed to write to {}: {}", dot_path, e))?;
+    }
+
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("Failed to run {}: {}", dot_path, e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("Graphviz failed: {}", stderr.trim()));
+    }
+
+    String::from_utf8(output.stdout).map_err(|e| format!("Graphviz produced invalid SVG: {}", e))
+}
+
+/// Render data as a QR code SVG string.
+pub fn render_qr(data: &str) -> Result<String, String> {
+    let code = QrCode::new(data.as_bytes()).map_err(|e| format!("Invalid QR code data: {}", e))?;
+    Ok(code.render::<svg::Color>().build())
+}
@@ -0,0 +1,120 @@
+use rayon::prelude::*;
+use serde::Serialize;
+use typst_library::layout::PagedDocument;
+
+/// How a page differs between two versions of a document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PageChange {
+    Unchanged,
+    Changed,
+    /// Only in the new version
+    Added,
+    /// Only in the old version
+    Removed,
+}
+
+/// One page of a visual diff from [`crate::diff_documents`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PageDiff {
+    /// 1-based page number
+    pub page: usize,
+    pub change: PageChange,
+    /// Share of the page's pixels that differ, from 0.0 to 1.0
+    pub changed_ratio: f64,
+    /// PNG of the page with changed pixels in red and the rest faded, or the
+    /// whole page when it was added or removed; `None` when unchanged
+    #[serde(skip)]
+    pub image: Option<Vec<u8>>,
+}
+
+/// Share of the distance to white that unchanged pixels are faded by
+const FADE: u32 = 70;
+
+/// Rasterize both documents and compare them page by page.
+pub fn diff_documents(
+    old: &PagedDocument,
+    new: &PagedDocument,
+    dpi: f32,
+) -> Result<Vec<PageDiff>, String> {
+    let _span = tracing::info_span!("diff", pages = new.pages.len()).entered();
+    let pixel_per_pt = dpi / 72.0;
+    let page_count = old.pages.len().max(new.pages.len());
+    (0..page_count)
+        .into_par_iter()
+        .map(|i| {
+            let old = old
+                .pages
+                .get(i)
+                .map(|page| typst_render::render(page, pixel_per_pt));
+            let new = new
+                .pages
+                .get(i)
+                .map(|page| typst_render::render(page, pixel_per_pt));
+            let (change, changed_ratio, image) = match (old, new) {
+                (Some(old), Some(mut new)) => {
+                    let same_size = old.width() == new.width() && old.height() == new.height();
+                    let changed = if same_size {
+                        highlight_changes(old.data(), new.data_mut())
+                    } else {
+                        new.data().len() / 4
+                    };
+                    if changed == 0 {
+                        (PageChange::Unchanged, 0.0, None)
+                    } else {
+                        let ratio = changed as f64 / (new.data().len() / 4) as f64;
+                        (PageChange::Changed, ratio, Some(new))
+                    }
+                }
+                (None, Some(new)) => (PageChange::Added, 1.0, Some(new)),
+                (Some(old), None) => (PageChange::Removed, 1.0, Some(old)),
+                (None, None) => unreachable!("page index is below one document's count"),
+            };
+            let image = image
+                .map(|pixmap| pixmap.encode_png())
+                .transpose()
+                .map_err(|e| format!("PNG encoding failed: {}", e))?;
+            Ok(PageDiff {
+                page: i + 1,
+                change,
+                changed_ratio,
+                image,
+            })
+        })
+        .collect()
+}
+
+/// Compare RGBA pixels, marking those that differ red in `new` and fading
+/// the others towards white. Returns the number of changed pixels.
+fn highlight_changes(old: &[u8], new: &mut [u8]) -> usize {
+    let mut changed = 0;
+    for (old, new) in old.chunks_exact(4).zip(new.chunks_exact_mut(4)) {
+        if old == new {
+            for channel in &mut new[..3] {
+                let value = u32::from(*channel);
+                *channel = (value + (255 - value) * FADE / 100) as u8;
+            }
+            new[3] = 255;
+        } else {
+            new.copy_from_slice(&[255, 0, 0, 255]);
+            changed += 1;
+        }
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::highlight_changes;
+
+    #[test]
+    fn highlights_changed_pixels() {
+        let old = [0, 0, 0, 255, 255, 255, 255, 255];
+        let mut new = [0, 0, 0, 255, 0, 0, 0, 255];
+        assert_eq!(highlight_changes(&old, &mut new), 1);
+        assert_eq!(new, [178, 178, 178, 255, 255, 0, 0, 255]);
+
+        let mut same = old;
+        assert_eq!(highlight_changes(&old, &mut same), 0);
+    }
+}
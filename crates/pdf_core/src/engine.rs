@@ -0,0 +1,287 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::atomic::AtomicUsize;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex, OnceLock, PoisonError, RwLock};
+use std::time::Duration;
+
+use typst_as_lib::file_resolver::FileResolver;
+#[cfg(feature = "system-fonts")]
+use typst_as_lib::typst_kit_options::TypstKitFontOptions;
+use typst_as_lib::{TypstAsLibError, TypstEngine};
+use typst_library::diag::{FileError, FileResult, Warned};
+use typst_library::foundations::Bytes;
+use typst_library::layout::PagedDocument;
+use typst_library::text::Font;
+use typst_syntax::{FileId, Source, VirtualPath};
+
+use crate::cancel::{CANCELLED, CancellationToken};
+
+/// Number of compilations a memoized result may go unused before comemo evicts it.
+/// Keeping results around lets a re-render after a small edit reuse the layout of
+/// everything that didn't change.
+const CACHE_MAX_AGE: usize = 10;
+
+/// Compilations that ran over their time limit and are still going, past which
+/// renders with a time limit are refused instead of starting another one.
+#[cfg(not(target_arch = "wasm32"))]
+const MAX_RUNAWAY: usize = 2;
+
+/// Typst's world for a [`crate::Renderer`]: its fonts, the font book and the
+/// standard library, set up on the first render and reused by every later one,
+/// including those of renderers derived with `with_config`.
+///
+/// The generated source and the files it uses change from render to render,
+/// so each render hands them over before compiling and renders sharing an
+/// engine take turns.
+pub(crate) struct Engine {
+    fonts: Arc<[Font]>,
+    /// Also search the fonts installed on the system
+    system_fonts: bool,
+    /// Replaced when a compilation that ran over its time limit is left
+    /// holding the turn, so later renders don't wait for it
+    slot: Mutex<Arc<Slot>>,
+    /// Compilations given up on that haven't finished yet
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    runaway: Arc<AtomicUsize>,
+}
+
+/// One Typst setup and the render it is compiling.
+#[derive(Default)]
+struct Slot {
+    typst: OnceLock<TypstEngine>,
+    inputs: Arc<RwLock<Inputs>>,
+    turn: Mutex<()>,
+}
+
+/// What the render in progress compiles: the main source and the images and
+/// other files it refers to.
+#[derive(Default)]
+struct Inputs {
+    main: Option<Source>,
+    files: HashMap<FileId, Bytes>,
+    /// Once any of these is cancelled, Typst's requests for files fail, which
+    /// ends a compilation that still reads any
+    cancel: Vec<CancellationToken>,
+}
+
+/// Why [`Engine::compile`] has no result.
+#[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+pub(crate) enum Unfinished {
+    /// It ran over the time limit and was left to finish on its own
+    TimedOut,
+    /// This many compilations that ran over earlier are still going
+    Runaway(usize),
+}
+
+/// Serves the render in progress' [`Inputs`] to Typst.
+struct InputResolver(Arc<RwLock<Inputs>>);
+
+impl FileResolver for InputResolver {
+    fn resolve_binary(&self, id: FileId) -> FileResult<Cow<'_, Bytes>> {
+        let inputs = self.0.read().unwrap_or_else(PoisonError::into_inner);
+        inputs.check_cancelled()?;
+        match inputs.files.get(&id) {
+            Some(bytes) => Ok(Cow::Owned(bytes.clone())),
+            None => Err(not_found(id)),
+        }
+    }
+
+    fn resolve_source(&self, id: FileId) -> FileResult<Cow<'_, Source>> {
+        let inputs = self.0.read().unwrap_or_else(PoisonError::into_inner);
+        inputs.check_cancelled()?;
+        match &inputs.main {
+            Some(main) if main.id() == id => Ok(Cow::Owned(main.clone())),
+            _ => Err(not_found(id)),
+        }
+    }
+}
+
+impl Inputs {
+    fn check_cancelled(&self) -> FileResult<()> {
+        if self.cancel.iter().any(CancellationToken::is_cancelled) {
+            return Err(FileError::Other(Some(CANCELLED.into())));
+        }
+        Ok(())
+    }
+}
+
+fn not_found(id: FileId) -> FileError {
+    FileError::NotFound(id.vpath().as_rootless_path().to_path_buf())
+}
+
+impl Slot {
+    fn compile(
+        &self,
+        fonts: &[Font],
+        system_fonts: bool,
+        main: Source,
+        files: Vec<(String, Vec<u8>)>,
+        cancel: Vec<CancellationToken>,
+    ) -> Warned<Result<PagedDocument, TypstAsLibError>> {
+        let _turn = self.turn.lock().unwrap_or_else(PoisonError::into_inner);
+        let id = main.id();
+        *self.inputs.write().unwrap_or_else(PoisonError::into_inner) = Inputs {
+            main: Some(main),
+            files: files
+                .into_iter()
+                .map(|(path, bytes)| (FileId::new(None, VirtualPath::new(path)), Bytes::new(bytes)))
+                .collect(),
+            cancel,
+        };
+        let typst = self.typst.get_or_init(|| {
+            tracing::info!(faces = fonts.len(), system_fonts, "setting up Typst");
+            let builder = TypstEngine::builder()
+                .fonts(fonts.iter().cloned())
+                .add_file_resolver(InputResolver(Arc::clone(&self.inputs)))
+                .comemo_evict_max_age(Some(CACHE_MAX_AGE));
+            #[cfg(feature = "system-fonts")]
+            let builder = if system_fonts {
+                // Typst's embedded fonts are among ours already
+                builder.search_fonts_with(
+                    TypstKitFontOptions::new()
+                        .include_system_fonts(true)
+                        .include_embedded_fonts(false),
+                )
+            } else {
+                builder
+            };
+            builder.build()
+        });
+        let compiled = typst.compile(id);
+        // Nothing but the next render needs the files
+        *self.inputs.write().unwrap_or_else(PoisonError::into_inner) = Inputs::default();
+        compiled
+    }
+}
+
+impl Engine {
+    pub(crate) fn new(fonts: Vec<Font>, system_fonts: bool) -> Self {
+        Self {
+            fonts: fonts.into(),
+            system_fonts,
+            slot: Mutex::default(),
+            runaway: Arc::default(),
+        }
+    }
+
+    pub(crate) fn fonts(&self) -> &[Font] {
+        &self.fonts
+    }
+
+    pub(crate) fn system_fonts(&self) -> bool {
+        self.system_fonts
+    }
+
+    /// Compile `main` with `files` (path as written in the source, contents)
+    /// available to it, giving up after `timeout`.
+    ///
+    /// Typst can't be interrupted: a compilation given up on, or one whose
+    /// `cancel` token is triggered, fails at its next file request, or else
+    /// finishes unseen on its own thread. Later renders get a Typst setup of
+    /// their own meanwhile. Without threads (wasm32) there is no timeout.
+    pub(crate) fn compile(
+        &self,
+        main: Source,
+        files: Vec<(String, Vec<u8>)>,
+        cancel: Option<&CancellationToken>,
+        timeout: Option<Duration>,
+    ) -> Result<Warned<Result<PagedDocument, TypstAsLibError>>, Unfinished> {
+        let slot = Arc::clone(&self.slot.lock().unwrap_or_else(PoisonError::into_inner));
+        let cancel: Vec<CancellationToken> = cancel.into_iter().cloned().collect();
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(timeout) = timeout {
+            let running = self.runaway.load(Ordering::Acquire);
+            if running >= MAX_RUNAWAY {
+                return Err(Unfinished::Runaway(running));
+            }
+            let expired = CancellationToken::new();
+            let mut cancel = cancel;
+            cancel.push(expired.clone());
+            let thread_slot = Arc::clone(&slot);
+            let fonts = Arc::clone(&self.fonts);
+            let system_fonts = self.system_fonts;
+            let compile = move || thread_slot.compile(&fonts, system_fonts, main, files, cancel);
+            if let Some(compiled) = run_with_timeout(compile, timeout, &self.runaway) {
+                return Ok(compiled);
+            }
+            expired.cancel();
+            let mut current = self.slot.lock().unwrap_or_else(PoisonError::into_inner);
+            if Arc::ptr_eq(&current, &slot) {
+                *current = Arc::default();
+            }
+            return Err(Unfinished::TimedOut);
+        }
+
+        let _ = timeout;
+        Ok(slot.compile(&self.fonts, self.system_fonts, main, files, cancel))
+    }
+}
+
+/// Run `work` on a thread of its own, waiting at most `timeout` for it. Work
+/// given up on counts in `runaway` until it finishes.
+#[cfg(not(target_arch = "wasm32"))]
+fn run_with_timeout<T: Send + 'static>(
+    work: impl FnOnce() -> T + Send + 'static,
+    timeout: Duration,
+    runaway: &Arc<AtomicUsize>,
+) -> Option<T> {
+    use std::sync::atomic::AtomicBool;
+
+    // Set by whichever of the work and the wait ends first
+    let settled = Arc::new(AtomicBool::new(false));
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let thread_settled = Arc::clone(&settled);
+    let thread_runaway = Arc::clone(runaway);
+    std::thread::spawn(move || {
+        let done = work();
+        if thread_settled.swap(true, Ordering::AcqRel) {
+            thread_runaway.fetch_sub(1, Ordering::AcqRel);
+        } else {
+            let _ = sender.send(done);
+        }
+    });
+
+    if let Ok(done) = receiver.recv_timeout(timeout) {
+        return Some(done);
+    }
+    runaway.fetch_add(1, Ordering::AcqRel);
+    if settled.swap(true, Ordering::AcqRel) {
+        // It finished after all, just now
+        runaway.fetch_sub(1, Ordering::AcqRel);
+        return receiver.recv().ok();
+    }
+    None
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use super::run_with_timeout;
+
+    #[test]
+    fn stops_waiting_at_the_time_limit() {
+        let runaway = Arc::new(AtomicUsize::new(0));
+        let slow = || {
+            std::thread::sleep(Duration::from_millis(300));
+            1
+        };
+        assert_eq!(
+            run_with_timeout(slow, Duration::from_millis(10), &runaway),
+            None
+        );
+        assert_eq!(runaway.load(Ordering::Acquire), 1);
+        assert_eq!(
+            run_with_timeout(|| 1, Duration::from_secs(10), &runaway),
+            Some(1)
+        );
+        // The slow one is no longer counted once it's done
+        std::thread::sleep(Duration::from_millis(600));
+        assert_eq!(runaway.load(Ordering::Acquire), 0);
+    }
+}
@@ -0,0 +1,84 @@
+use std::collections::BTreeMap;
+
+use typst_library::layout::{Frame, FrameItem, PagedDocument};
+use typst_syntax::Source;
+
+use crate::diagnostic::Diagnostic;
+use crate::typst::SourceMap;
+
+/// Warn about characters that none of the loaded fonts has a glyph for, which
+/// the PDF shows as empty boxes ("tofu"). One warning per markdown line, listing
+/// its characters in the order they first appear.
+pub(crate) fn missing_glyphs(
+    doc: &PagedDocument,
+    source: &Source,
+    source_map: &SourceMap,
+) -> Vec<Diagnostic> {
+    let mut missing: BTreeMap<Option<usize>, Vec<char>> = BTreeMap::new();
+    for page in &doc.pages {
+        collect_missing(&page.frame, &mut |span, chars| {
+            let line = source
+                .range(span)
+                .and_then(|range| source_map.line_for_offset(range.start));
+            let found = missing.entry(line).or_default();
+            for ch in chars.chars().filter(|ch| !ch.is_whitespace()) {
+                if !found.contains(&ch) {
+                    found.push(ch);
+                }
+            }
+        });
+    }
+    missing
+        .into_iter()
+        .filter(|(_, chars)| !chars.is_empty())
+        .map(|(line, chars)| Diagnostic::warning(missing_message(&chars)).at_line(line))
+        .collect()
+}
+
+/// Call `found` with the text of every glyph the font lacks (glyph 0,
+/// `.notdef`), and where in the Typst source it came from.
+fn collect_missing(frame: &Frame, found: &mut impl FnMut(typst_syntax::Span, &str)) {
+    for (_, item) in frame.items() {
+        match item {
+            FrameItem::Group(group) => collect_missing(&group.frame, found),
+            FrameItem::Text(text) => {
+                for glyph in text.glyphs.iter().filter(|glyph| glyph.id == 0) {
+                    if let Some(chars) = text.text.get(glyph.range()) {
+                        found(glyph.span.0, chars);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn missing_message(chars: &[char]) -> String {
+    let listed: Vec<String> = chars
+        .iter()
+        .map(|ch| format!("'{}' (U+{:04X})", ch, *ch as u32))
+        .collect();
+    format!(
+        "no font has {} {}, shown as empty boxes; load a font that has them",
+        if chars.len() == 1 {
+            "a glyph for"
+        } else {
+            "glyphs for"
+        },
+        listed.join(", ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::missing_message;
+
+    #[test]
+    fn lists_characters() {
+        assert_eq!(
+            missing_message(&['字', '😀']),
+            "no font has glyphs for '字' (U+5B57), '😀' (U+1F600), shown as empty boxes; load a font that has them"
+        );
+        assert!(missing_message(&['ÿ']).starts_with("no font has a glyph for 'ÿ' (U+00FF),"));
+    }
+}
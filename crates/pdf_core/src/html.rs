@@ -0,0 +1,495 @@
+use std::collections::HashMap;
+
+use crate::acronyms;
+use crate::block::{Alignment, Block, List, Region, Span};
+use crate::config::{Config, TableWidth};
+use crate::diagram;
+use crate::lists;
+use crate::typst::{abstract_paragraphs, collect_span_text, expand_tabs, heading_to_label};
+
+/// Convert blocks to a standalone HTML document styled from the config.
+pub fn blocks_to_html(blocks: &[Block], config: &Config) -> String {
+    let blocks = &*acronyms::expand(blocks, &config.acronyms.terms);
+    let blocks = &*lists::continue_numbering(blocks, config.lists.continue_numbering);
+    let references = reference_targets(blocks);
+    let mut out = String::new();
+
+    out.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    out.push_str("<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n");
+    let title = config
+        .document
+        .title
+        .clone()
+        .or_else(|| document_title(blocks));
+    if let Some(title) = title {
+        out.push_str("<title>");
+        escape_html(&title, &mut out);
+        out.push_str("</title>\n");
+    }
+    if let Some(author) = &config.document.author {
+        out.push_str("<meta name=\"author\" content=\"");
+        escape_html(author, &mut out);
+        out.push_str("\">\n");
+    }
+    out.push_str("<style>\n");
+    out.push_str(&stylesheet(config));
+    out.push_str("</style>\n</head>\n<body>\n");
+
+    if let Some(text) = &config.document.r#abstract {
+        out.push_str("<section class=\"abstract\">\n<h2>Abstract</h2>\n");
+        for paragraph in abstract_paragraphs(text) {
+            out.push_str("<p>");
+            escape_html(&paragraph, &mut out);
+            out.push_str("</p>\n");
+        }
+        out.push_str("</section>\n");
+    }
+
+    if config.html.toc {
+        emit_toc(blocks, &mut out);
+    }
+
+    let mut table_number = 0;
+    // Inside a `<details>` element that's left out
+    let mut omitting = false;
+    for block in blocks {
+        match block {
+            Block::RegionStart(Region::Details) if config.document.omit_details => omitting = true,
+            Block::RegionEnd(Region::Details) if omitting => omitting = false,
+            _ if omitting => {}
+            _ => emit_block(block, config, &references, &mut table_number, &mut out),
+        }
+    }
+
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+/// CSS mirroring the PDF output's look
+fn stylesheet(config: &Config) -> String {
+    let fallback = if config.font.sans {
+        "\"Open Sans\", sans-serif"
+    } else {
+        "\"Libertinus Serif\", serif"
+    };
+    let font = match &config.font.family {
+        Some(family) => format!("\"{}\", {}", family.replace('"', ""), fallback),
+        None => fallback.to_string(),
+    };
+    let decoration = if config.links.underline {
+        "underline"
+    } else {
+        "none"
+    };
+    format!(
+        "body {{ font-family: {}; max-width: 48em; margin: 2em auto; padding: 0 1em; line-height: 1.5; }}\n\
+         a {{ color: {}; text-decoration: {}; }}\n\
+         pre {{ background: #f5f5f5; padding: 0.75em; overflow-x: auto; }}\n\
+         .code-title {{ background: #e8e8e8; padding: 0.3em 0.75em; font-family: monospace; font-size: 0.8em; }}\n\
+         .code-title + pre {{ margin-top: 0; }}\n\
+         table {{ border-collapse: collapse; }}\n\
+         th, td {{ border: 1px solid #000; padding: 0.25em 0.5em; }}\n\
+         table.striped tbody tr:nth-child(odd) {{ background: #f2f2f2; }}\n\
+         tfoot td {{ font-weight: bold; border-top-width: 2px; }}\n\
+         caption {{ caption-side: bottom; padding-top: 0.5em; }}\n\
+         li.task {{ list-style: none; }}\n\
+         nav.toc ul {{ list-style: none; padding-left: 0; }}\n\
+         .page-break {{ break-after: page; }}\n\
+         .keep {{ break-inside: avoid; }}\n\
+         .details {{ border-left: 1px solid #888; padding-left: 1em; }}\n\
+         .changed {{ border-left: 2px solid #d33; margin-left: -0.8em; padding-left: calc(0.8em - 2px); }}\n\
+         .abstract {{ max-width: 80%; margin: 0 auto 2em; font-size: 0.9em; text-align: justify; }}\n\
+         .abstract h2 {{ font-size: 1em; text-align: center; }}\n\
+         .sidenote {{ float: right; clear: right; width: 12em; margin-right: -14em; font-size: 0.8em; }}\n",
+        font, config.links.color, decoration
+    )
+}
+
+/// Text of the first level-1 heading, used as the page title
+fn document_title(blocks: &[Block]) -> Option<String> {
+    blocks.iter().find_map(|block| match block {
+        Block::Heading {
+            level: 1, content, ..
+        } => {
+            let mut text = String::new();
+            collect_span_text(content, &mut text);
+            Some(text)
+        }
+        _ => None,
+    })
+}
+
+fn heading_id(content: &[Span], id: &Option<String>) -> String {
+    id.clone().unwrap_or_else(|| heading_to_label(content))
+}
+
+/// Display text for each cross-reference label, matching what Typst prints:
+/// the heading text for headings and "Table N" for captioned tables.
+fn reference_targets(blocks: &[Block]) -> HashMap<String, String> {
+    let mut targets = HashMap::new();
+    let mut table_number = 0;
+    let mut figure_number = 0;
+    for block in blocks {
+        match block {
+            Block::Heading { content, id, .. } => {
+                let mut text = String::new();
+                collect_span_text(content, &mut text);
+                targets.insert(heading_id(content, id), text);
+            }
+            Block::Table {
+                caption: Some(_),
+                label,
+                ..
+            } => {
+                table_number += 1;
+                if let Some(label) = label {
+                    targets.insert(label.clone(), format!("Table {}", table_number));
+                }
+            }
+            // Numbered like Typst's figures: captioned or labeled images
+            Block::Image { title, label, .. } if title.is_some() || label.is_some() => {
+                figure_number += 1;
+                if let Some(label) = label {
+                    targets.insert(label.clone(), format!("Figure {}", figure_number));
+                }
+            }
+            _ => {}
+        }
+    }
+    targets
+}
+
+fn emit_toc(blocks: &[Block], out: &mut String) {
+    out.push_str("<nav class=\"toc\">\n<ul>\n");
+    for block in blocks {
+        if let Block::Heading { level, content, id } = block {
+            let mut text = String::new();
+            collect_span_text(content, &mut text);
+            out.push_str(&format!(
+                "<li style=\"margin-left: {}em\"><a href=\"#",
+                (level - 1) * 2
+            ));
+            escape_html(&heading_id(content, id), out);
+            out.push_str("\">");
+            escape_html(&text, out);
+            out.push_str("</a></li>\n");
+        }
+    }
+    out.push_str("</ul>\n</nav>\n");
+}
+
+fn emit_block(
+    block: &Block,
+    config: &Config,
+    references: &HashMap<String, String>,
+    table_number: &mut usize,
+    out: &mut String,
+) {
+    match block {
+        Block::Heading { level, content, id } => {
+            out.push_str(&format!("<h{} id=\"", level));
+            escape_html(&heading_id(content, id), out);
+            out.push_str("\">");
+            spans_to_html(content, references, out);
+            out.push_str(&format!("</h{}>\n", level));
+        }
+        Block::Paragraph { content } => {
+            out.push_str("<p>");
+            spans_to_html(content, references, out);
+            out.push_str("</p>\n");
+        }
+        Block::CodeBlock {
+            language,
+            content,
+            title,
+        } => {
+            if let Some(title) = title {
+                out.push_str("<div class=\"code-title\">");
+                escape_html(title, out);
+                out.push_str("</div>\n");
+            }
+            out.push_str("<pre><code");
+            if let Some(lang) = language {
+                out.push_str(" class=\"language-");
+                escape_html(lang, out);
+                out.push('"');
+            }
+            out.push('>');
+            escape_html(&expand_tabs(content, config.code.tab_width), out);
+            out.push_str("</code></pre>\n");
+        }
+        Block::List(list) => list_to_html(list, references, out),
+        Block::Table {
+            headers,
+            rows,
+            footer,
+            align,
+            caption,
+            label,
+            style,
+        } => {
+            let tables = config.tables.with_options(style);
+            out.push_str("<table");
+            if let Some(label) = label {
+                out.push_str(" id=\"");
+                escape_html(label, out);
+                out.push('"');
+            }
+            if tables.striped {
+                out.push_str(" class=\"striped\"");
+            }
+            let mut css = String::new();
+            if tables.width == TableWidth::Full {
+                css.push_str("width: 100%;");
+            }
+            if let Some(size) = &tables.font_size {
+                css.push_str(&format!(" font-size: {};", size));
+            }
+            if !css.is_empty() {
+                out.push_str(" style=\"");
+                escape_html(css.trim_start(), out);
+                out.push('"');
+            }
+            out.push_str(">\n");
+            if let Some(caption) = caption {
+                // Captioned tables are numbered like Typst figures
+                *table_number += 1;
+                out.push_str(&format!("<caption>Table {}: ", table_number));
+                spans_to_html(caption, references, out);
+                out.push_str("</caption>\n");
+            }
+            out.push_str("<thead>\n");
+            table_row_to_html(headers, "th", align, references, out);
+            out.push_str("</thead>\n<tbody>\n");
+            let (body, foot) = rows.split_at(rows.len().saturating_sub(*footer));
+            for row in body {
+                table_row_to_html(row, "td", align, references, out);
+            }
+            out.push_str("</tbody>\n");
+            if !foot.is_empty() {
+                out.push_str("<tfoot>\n");
+                for row in foot {
+                    table_row_to_html(row, "td", align, references, out);
+                }
+                out.push_str("</tfoot>\n");
+            }
+            out.push_str("</table>\n");
+        }
+        Block::Image {
+            path,
+            alt,
+            title,
+            label,
+        } => {
+            out.push_str("<figure");
+            if let Some(label) = label {
+                out.push_str(" id=\"");
+                escape_html(label, out);
+                out.push('"');
+            }
+            out.push_str("><img src=\"");
+            escape_html(path, out);
+            out.push_str("\" alt=\"");
+            escape_html(alt, out);
+            out.push_str("\">");
+            if let Some(title) = title {
+                out.push_str("<figcaption>");
+                escape_html(title, out);
+                out.push_str("</figcaption>");
+            }
+            out.push_str("</figure>\n");
+        }
+        Block::Rule => out.push_str("<hr>\n"),
+        Block::PageBreak | Block::MainMatter => out.push_str("<div class=\"page-break\"></div>\n"),
+        Block::RegionStart(Region::Keep) => out.push_str("<div class=\"keep\">\n"),
+        Block::RegionStart(Region::Wide) => out.push_str("<div class=\"wide\">\n"),
+        Block::RegionStart(Region::Changed) => out.push_str("<div class=\"changed\">\n"),
+        Block::RegionStart(Region::Details) => out.push_str("<div class=\"details\">\n"),
+        Block::RegionEnd(_) => out.push_str("</div>\n"),
+        Block::RestartNumbers { .. } => {}
+        Block::QrCode { data, size } => match diagram::render_qr(data) {
+            Ok(svg) => {
+                out.push_str(&format!(
+                    "<div style=\"width: {}\">",
+                    size.as_deref().unwrap_or("3cm")
+                ));
+                out.push_str(&svg);
+                out.push_str("</div>\n");
+            }
+            Err(e) => {
+                out.push_str("<p style=\"color: red\">");
+                escape_html(&e, out);
+                out.push_str("</p>\n");
+            }
+        },
+        Block::Diagram { code, svg } => match svg {
+            Ok(svg) => {
+                out.push_str("<figure>");
+                out.push_str(svg);
+                out.push_str("</figure>\n");
+            }
+            Err(e) => {
+                // Show the error above the source instead of dropping the diagram
+                out.push_str("<p style=\"color: red\">");
+                escape_html(e, out);
+                out.push_str("</p>\n");
+                emit_block(code, config, references, table_number, out);
+            }
+        },
+    }
+}
+
+fn table_row_to_html(
+    cells: &[Vec<Span>],
+    tag: &str,
+    align: &[Alignment],
+    references: &HashMap<String, String>,
+    out: &mut String,
+) {
+    out.push_str("<tr>");
+    for (i, cell) in cells.iter().enumerate() {
+        out.push('<');
+        out.push_str(tag);
+        match align.get(i) {
+            Some(Alignment::Left) => out.push_str(" style=\"text-align: left\""),
+            Some(Alignment::Center) => out.push_str(" style=\"text-align: center\""),
+            Some(Alignment::Right) => out.push_str(" style=\"text-align: right\""),
+            Some(Alignment::None) | None => {}
+        }
+        out.push('>');
+        spans_to_html(cell, references, out);
+        out.push_str(&format!("</{}>", tag));
+    }
+    out.push_str("</tr>\n");
+}
+
+fn list_to_html(list: &List, references: &HashMap<String, String>, out: &mut String) {
+    let tag = if list.ordered { "ol" } else { "ul" };
+    if list.ordered && list.start != 1 {
+        out.push_str(&format!("<ol start=\"{}\">\n", list.start));
+    } else {
+        out.push_str(&format!("<{}>\n", tag));
+    }
+    for item in &list.items {
+        match item.checked {
+            Some(checked) => {
+                out.push_str("<li class=\"task\"><input type=\"checkbox\" disabled");
+                if checked {
+                    out.push_str(" checked");
+                }
+                out.push_str("> ");
+            }
+            None => out.push_str("<li>"),
+        }
+        spans_to_html(&item.content, references, out);
+        if let Some(ref nested) = item.nested {
+            out.push('\n');
+            list_to_html(nested, references, out);
+        }
+        out.push_str("</li>\n");
+    }
+    out.push_str(&format!("</{}>\n", tag));
+}
+
+fn spans_to_html(spans: &[Span], references: &HashMap<String, String>, out: &mut String) {
+    for span in spans {
+        match span {
+            Span::Text(text) => escape_html(text, out),
+            Span::Bold(inner) => {
+                out.push_str("<strong>");
+                spans_to_html(inner, references, out);
+                out.push_str("</strong>");
+            }
+            Span::Italic(inner) => {
+                out.push_str("<em>");
+                spans_to_html(inner, references, out);
+                out.push_str("</em>");
+            }
+            Span::Code(text) => {
+                out.push_str("<code>");
+                escape_html(text, out);
+                out.push_str("</code>");
+            }
+            Span::Link { url, content } => {
+                out.push_str("<a href=\"");
+                escape_html(url, out);
+                out.push_str("\">");
+                spans_to_html(content, references, out);
+                out.push_str("</a>");
+            }
+            Span::Reference(label) => {
+                out.push_str("<a href=\"#");
+                escape_html(label, out);
+                out.push_str("\">");
+                escape_html(references.get(label).unwrap_or(label), out);
+                out.push_str("</a>");
+            }
+            Span::LineBreak => out.push_str("<br>\n"),
+            Span::Sidenote(text) => {
+                out.push_str("<span class=\"sidenote\">");
+                escape_html(text, out);
+                out.push_str("</span>");
+            }
+        }
+    }
+}
+
+/// Escape text for use in HTML content and attribute values
+fn escape_html(text: &str, out: &mut String) {
+    for ch in text.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(ch),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::markdown_to_html_with_config;
+
+    #[test]
+    fn standalone_document() {
+        let mut config = Config::compiled_default();
+        config.links.color = "#ff0000".to_string();
+        let result = markdown_to_html_with_config(
+            "# Intro & Setup\n\nSee [docs](https://example.com) and **`a<b`**.",
+            &config,
+        );
+        assert!(result.starts_with("<!DOCTYPE html>\n"));
+        assert!(result.contains("<title>Intro &amp; Setup</title>"));
+        assert!(result.contains("a { color: #ff0000; text-decoration: underline; }"));
+        assert!(result.contains("<h1 id=\"intro--setup\">Intro &amp; Setup</h1>\n"));
+        assert!(result.contains(
+            "<p>See <a href=\"https://example.com\">docs</a> and <strong><code>a&lt;b</code></strong>.</p>\n"
+        ));
+        assert!(!result.contains("<nav"));
+    }
+
+    #[test]
+    fn toc_and_references() {
+        let mut config = Config::compiled_default();
+        config.html.toc = true;
+        let markdown = "# Results {#res}\n\n| A | B |\n|:--|--:|\n| 1 | 2 |\n\nTable: Scores {#tbl:scores}\n\nSee [@tbl:scores] in [@res].\n\n- [x] done";
+        let result = markdown_to_html_with_config(markdown, &config);
+        assert!(result.contains(
+            "<nav class=\"toc\">\n<ul>\n<li style=\"margin-left: 0em\"><a href=\"#res\">Results</a></li>\n</ul>\n</nav>\n"
+        ));
+        assert!(result.contains("<table id=\"tbl:scores\">\n<caption>Table 1: Scores</caption>\n"));
+        assert!(result.contains(
+            "<tr><th style=\"text-align: left\">A</th><th style=\"text-align: right\">B</th></tr>\n"
+        ));
+        assert!(result.contains(
+            "<p>See <a href=\"#tbl:scores\">Table 1</a> in <a href=\"#res\">Results</a>.</p>"
+        ));
+        assert!(
+            result.contains(
+                "<li class=\"task\"><input type=\"checkbox\" disabled checked> done</li>"
+            )
+        );
+    }
+}
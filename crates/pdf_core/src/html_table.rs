@@ -0,0 +1,423 @@
+use crate::block::{Alignment, Block, Span};
+use crate::parser::push_text;
+
+/// A tag or a run of text in a piece of HTML.
+enum Token<'a> {
+    /// Lowercase tag name, and the attributes as written
+    Open(String, &'a str),
+    Close(String),
+    Text(&'a str),
+}
+
+/// Build a table from an HTML `<table>`, as exporters write when a cell spans
+/// several columns. Rows come from `<tr>`, cells from `<td>` and `<th>`; the
+/// header is a `<thead>`, or else a first row of only `<th>` cells, and the
+/// rows of a `<tfoot>` are the footer, wherever it's written. A cell
+/// spanning columns is followed by empty ones, as a table can't merge them.
+/// The first row's `align` attributes or `text-align` styles set the column
+/// alignment, and a `<caption>` the caption. In cells, `<b>`, `<i>`, `<code>`,
+/// `<a href>` and `<br>` keep their formatting; other tags are dropped.
+pub(crate) fn parse_table(html: &str) -> Option<Block> {
+    let mut rows: Vec<Vec<Vec<Span>>> = Vec::new();
+    let mut header_rows = 0;
+    let mut all_headers = Vec::new();
+    // Whether each row is in the `<tfoot>`
+    let mut in_footer = Vec::new();
+    let mut align = Vec::new();
+    let mut caption = None;
+
+    let mut in_head = false;
+    let mut in_foot = false;
+    // Formatting open in the current cell, with the spans before it
+    let mut stack: Vec<(Span, Vec<Span>)> = Vec::new();
+    let mut cell: Option<Vec<Span>> = None;
+    let mut span = 1;
+    let mut in_code = false;
+
+    for token in tokens(html) {
+        match token {
+            Token::Open(name, attrs) => match name.as_str() {
+                "thead" => in_head = true,
+                "tfoot" => in_foot = true,
+                "tr" => {
+                    finish_cell(&mut cell, &mut stack, span, &mut rows);
+                    rows.push(Vec::new());
+                    all_headers.push(true);
+                    in_footer.push(in_foot);
+                    if in_head {
+                        header_rows = rows.len();
+                    }
+                }
+                "td" | "th" | "caption" => {
+                    finish_cell(&mut cell, &mut stack, span, &mut rows);
+                    cell = Some(Vec::new());
+                    span = attribute(attrs, "colspan")
+                        .and_then(|n| n.parse().ok())
+                        .unwrap_or(1usize)
+                        .max(1);
+                    if name == "caption" {
+                        continue;
+                    }
+                    if rows.is_empty() {
+                        rows.push(Vec::new());
+                        all_headers.push(true);
+                        in_footer.push(in_foot);
+                    }
+                    if name == "td"
+                        && let Some(all) = all_headers.last_mut()
+                    {
+                        *all = false;
+                    }
+                    if rows.len() == 1 {
+                        let alignment = cell_alignment(attrs);
+                        align.extend(std::iter::repeat_n(alignment, span));
+                    }
+                }
+                _ if cell.is_none() => {}
+                "b" | "strong" => stack.push((Span::Bold(Vec::new()), take(&mut cell))),
+                "i" | "em" => stack.push((Span::Italic(Vec::new()), take(&mut cell))),
+                "a" => {
+                    let url = attribute(attrs, "href").unwrap_or_default().to_string();
+                    stack.push((
+                        Span::Link {
+                            url,
+                            content: Vec::new(),
+                        },
+                        take(&mut cell),
+                    ));
+                }
+                "code" => {
+                    in_code = true;
+                    if let Some(cell) = &mut cell {
+                        cell.push(Span::Code(String::new()));
+                    }
+                }
+                "br" => {
+                    if let Some(cell) = &mut cell {
+                        cell.push(Span::LineBreak);
+                    }
+                }
+                _ => {}
+            },
+            Token::Close(name) => match name.as_str() {
+                "thead" => in_head = false,
+                "tfoot" => in_foot = false,
+                "caption" => {
+                    close_formatting(&mut cell, &mut stack);
+                    caption = cell
+                        .take()
+                        .map(trim_spans)
+                        .filter(|spans| !spans.is_empty());
+                }
+                "td" | "th" => finish_cell(&mut cell, &mut stack, span, &mut rows),
+                "b" | "strong" | "i" | "em" | "a" => {
+                    if let Some((mut formatting, before)) = stack.pop() {
+                        let inner = cell.replace(before);
+                        if let (Some(inner), Some(cell)) = (inner, &mut cell) {
+                            if let Span::Bold(spans)
+                            | Span::Italic(spans)
+                            | Span::Link { content: spans, .. } = &mut formatting
+                            {
+                                *spans = inner;
+                            }
+                            cell.push(formatting);
+                        }
+                    }
+                }
+                "code" => in_code = false,
+                "table" => break,
+                _ => {}
+            },
+            Token::Text(text) => {
+                let text = decode_entities(text);
+                match &mut cell {
+                    Some(cell) if in_code => {
+                        if let Some(Span::Code(code)) = cell.last_mut() {
+                            code.push_str(&text);
+                        }
+                    }
+                    Some(cell) => push_text(cell, &collapse_whitespace(&text)),
+                    None => {}
+                }
+            }
+        }
+    }
+    finish_cell(&mut cell, &mut stack, span, &mut rows);
+
+    // Rows without cells, e.g. from whitespace between tags, aren't rows
+    let mut headers = Vec::new();
+    let mut body = Vec::new();
+    let mut footer = Vec::new();
+    for (i, ((row, all_th), foot)) in rows.into_iter().zip(all_headers).zip(in_footer).enumerate() {
+        if row.is_empty() {
+            continue;
+        }
+        if foot {
+            footer.push(row);
+            continue;
+        }
+        let header = if header_rows > 0 {
+            i < header_rows
+        } else {
+            all_th
+        };
+        if header && headers.is_empty() && body.is_empty() {
+            headers = row;
+        } else {
+            body.push(row);
+        }
+    }
+    let footer_rows = footer.len();
+    let mut rows = body;
+    rows.extend(footer);
+    let columns = rows
+        .iter()
+        .chain(std::iter::once(&headers))
+        .map(Vec::len)
+        .max()
+        .filter(|&columns| columns > 0)?;
+    headers.resize(columns, Vec::new());
+    for row in &mut rows {
+        row.resize(columns, Vec::new());
+    }
+    align.truncate(columns);
+    if align.iter().all(|a| *a == Alignment::None) {
+        align.clear();
+    }
+
+    Some(Block::Table {
+        headers,
+        rows,
+        footer: footer_rows,
+        align,
+        caption,
+        label: None,
+        style: Vec::new(),
+    })
+}
+
+/// Collapse runs of whitespace to a single space, as HTML shows them.
+fn collapse_whitespace(text: &str) -> String {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    if words.is_empty() {
+        return if text.is_empty() { "" } else { " " }.to_string();
+    }
+    let mut collapsed = words.join(" ");
+    if text.starts_with(char::is_whitespace) {
+        collapsed.insert(0, ' ');
+    }
+    if text.ends_with(char::is_whitespace) {
+        collapsed.push(' ');
+    }
+    collapsed
+}
+
+/// The spans of the current cell, leaving it empty.
+fn take(cell: &mut Option<Vec<Span>>) -> Vec<Span> {
+    cell.as_mut().map(std::mem::take).unwrap_or_default()
+}
+
+/// Close formatting tags left open in the current cell.
+fn close_formatting(cell: &mut Option<Vec<Span>>, stack: &mut Vec<(Span, Vec<Span>)>) {
+    while let Some((mut formatting, mut before)) = stack.pop() {
+        if let Span::Bold(spans) | Span::Italic(spans) | Span::Link { content: spans, .. } =
+            &mut formatting
+        {
+            *spans = take(cell);
+        }
+        before.push(formatting);
+        if let Some(cell) = cell {
+            *cell = before;
+        }
+    }
+}
+
+/// Add the current cell, if any, to the last row, with empty cells after it
+/// for the other columns it spans.
+fn finish_cell(
+    cell: &mut Option<Vec<Span>>,
+    stack: &mut Vec<(Span, Vec<Span>)>,
+    span: usize,
+    rows: &mut [Vec<Vec<Span>>],
+) {
+    close_formatting(cell, stack);
+    let Some(spans) = cell.take() else {
+        return;
+    };
+    if let Some(row) = rows.last_mut() {
+        row.push(trim_spans(spans));
+        row.extend(std::iter::repeat_n(Vec::new(), span - 1));
+    }
+}
+
+/// Trim the whitespace HTML leaves around a cell's text.
+fn trim_spans(mut spans: Vec<Span>) -> Vec<Span> {
+    if let Some(Span::Text(text)) = spans.first_mut() {
+        *text = text.trim_start().to_string();
+    }
+    if let Some(Span::Text(text)) = spans.last_mut() {
+        *text = text.trim_end().to_string();
+    }
+    spans.retain(|span| !matches!(span, Span::Text(text) if text.is_empty()));
+    spans
+}
+
+fn cell_alignment(attrs: &str) -> Alignment {
+    let style = attribute(attrs, "style")
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let value = attribute(attrs, "align")
+        .map(str::to_ascii_lowercase)
+        .or_else(|| {
+            let (_, rest) = style.split_once("text-align")?;
+            let value = rest.trim_start().strip_prefix(':')?;
+            Some(value.split(';').next()?.trim().to_string())
+        });
+    match value.as_deref() {
+        Some("left") => Alignment::Left,
+        Some("center") => Alignment::Center,
+        Some("right") => Alignment::Right,
+        _ => Alignment::None,
+    }
+}
+
+/// The value of an attribute, quoted or not.
+fn attribute<'a>(attrs: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = attrs;
+    while let Some(eq) = rest.find('=') {
+        let key = rest[..eq].split_whitespace().last().unwrap_or("");
+        let after = rest[eq + 1..].trim_start();
+        let (value, next) = match after.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                let end = after[1..].find(quote).map_or(after.len(), |end| end + 1);
+                (&after[1..end], after.get(end + 1..).unwrap_or(""))
+            }
+            _ => {
+                let end = after.find(char::is_whitespace).unwrap_or(after.len());
+                (&after[..end], &after[end..])
+            }
+        };
+        if key.eq_ignore_ascii_case(name) {
+            return Some(value);
+        }
+        rest = next;
+    }
+    None
+}
+
+fn tokens(html: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut rest = html;
+    while !rest.is_empty() {
+        let Some(start) = rest.find('<') else {
+            tokens.push(Token::Text(rest));
+            break;
+        };
+        if start > 0 {
+            tokens.push(Token::Text(&rest[..start]));
+        }
+        rest = &rest[start..];
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.split_once("-->").map_or("", |(_, after)| after);
+            continue;
+        }
+        let Some(end) = rest.find('>') else {
+            tokens.push(Token::Text(rest));
+            break;
+        };
+        let tag = rest[1..end].trim_end_matches('/');
+        rest = &rest[end + 1..];
+        let (name, attrs) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+        match name.strip_prefix('/') {
+            Some(name) => tokens.push(Token::Close(name.to_ascii_lowercase())),
+            None => tokens.push(Token::Open(name.to_ascii_lowercase(), attrs)),
+        }
+    }
+    tokens
+}
+
+/// Replace the common named entities and numeric character references.
+fn decode_entities(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let decoded = rest.find(';').and_then(|end| {
+            let ch = match &rest[1..end] {
+                "amp" => '&',
+                "lt" => '<',
+                "gt" => '>',
+                "quot" => '"',
+                "apos" => '\'',
+                "nbsp" => '\u{a0}',
+                entity => {
+                    let number = entity.strip_prefix('#')?;
+                    let code = match number.strip_prefix(['x', 'X']) {
+                        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                        None => number.parse().ok()?,
+                    };
+                    char::from_u32(code)?
+                }
+            };
+            Some((ch, end))
+        });
+        match decoded {
+            Some((ch, end)) => {
+                out.push(ch);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::block::{Alignment, Block, Span};
+    use crate::parser;
+
+    #[test]
+    fn parses_html_tables() {
+        let markdown = "<table>\n<caption>Totals</caption>\n<thead>\n<tr><th align=\"left\">Name</th><th style=\"text-align: right\">Amount</th></tr>\n</thead>\n<tr><td><b>Rent</b> &amp; <a href=\"https://example.com\">fees</a></td><td>1,200</td></tr>\n<tr><td colspan=\"2\">No <code>x &lt; y</code></td></tr>\n</table>\n\nAfter\n";
+        let blocks = parser::parse(markdown);
+        let Block::Table {
+            headers,
+            rows,
+            align,
+            caption,
+            ..
+        } = &blocks[0]
+        else {
+            panic!("expected a table, got {:?}", blocks[0]);
+        };
+        assert!(matches!(headers[0].as_slice(), [Span::Text(name)] if name == "Name"));
+        assert_eq!(align, &[Alignment::Left, Alignment::Right]);
+        assert!(matches!(caption.as_deref(), Some([Span::Text(text)]) if text == "Totals"));
+        assert_eq!(rows.len(), 2);
+        assert!(matches!(
+            rows[0][0].as_slice(),
+            [Span::Bold(_), Span::Text(and), Span::Link { url, .. }] if and == " & " && url == "https://example.com"
+        ));
+        // A spanning cell is followed by an empty one
+        assert!(
+            matches!(rows[1][0].as_slice(), [Span::Text(_), Span::Code(code)] if code == "x < y")
+        );
+        assert!(rows[1][1].is_empty());
+        assert!(matches!(&blocks[1], Block::Paragraph { .. }));
+
+        // Without a header row the header is empty
+        let blocks = parser::parse("<table><tr><td>a</td><td>b</td></tr></table>");
+        let Block::Table { headers, rows, .. } = &blocks[0] else {
+            panic!("expected a table");
+        };
+        assert_eq!(headers.len(), 2);
+        assert!(headers.iter().all(Vec::is_empty));
+        assert_eq!(rows.len(), 1);
+    }
+}
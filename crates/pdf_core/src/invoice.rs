@@ -0,0 +1,213 @@
+use serde::Deserialize;
+
+use crate::block::{Alignment, Block, Span};
+use crate::parser::Directive;
+use crate::resource::ResourceResolver;
+
+/// What an `@invoice(...)` directive lists: line items, and optionally the tax
+/// rate (in percent) and currency.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Invoice {
+    items: Vec<Item>,
+    tax: Option<f64>,
+    currency: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Item {
+    description: String,
+    #[serde(default = "one")]
+    quantity: f64,
+    /// Price of one unit
+    price: f64,
+}
+
+fn one() -> f64 {
+    1.0
+}
+
+/// Build the table of an `@invoice(...)` directive, with amounts per item and
+/// subtotal, tax and total rows below them as its footer.
+///
+/// Items come from the file named by the first argument (CSV with
+/// `description`, `quantity` and `price` columns, or JSON/YAML with a list of
+/// them, alone or as `items`), or else from the frontmatter's `invoice:`
+/// section, which can set `tax` and `currency` too. Options: `tax=21` adds 21%
+/// tax, `currency=EUR` prefixes amounts.
+pub(crate) fn invoice_table(
+    directive: &Directive,
+    frontmatter: Option<&str>,
+    resolver: &dyn ResourceResolver,
+) -> Result<Block, String> {
+    let mut invoice = match directive.arg.as_deref() {
+        Some(path) => load_items(path, resolver)?,
+        None => {
+            let frontmatter: serde_yaml::Value = serde_yaml::from_str(frontmatter.unwrap_or(""))
+                .map_err(|e| format!("Invalid frontmatter: {}", e))?;
+            match frontmatter.get("invoice") {
+                Some(section) => serde_yaml::from_value(section.clone())
+                    .map_err(|e| format!("Invalid invoice in frontmatter: {}", e))?,
+                None => return Err("No invoice items in the frontmatter".to_string()),
+            }
+        }
+    };
+    if let Some(tax) = directive.option("tax") {
+        invoice.tax = Some(
+            tax.trim_end_matches('%')
+                .parse()
+                .map_err(|_| format!("Invalid tax rate {}", tax))?,
+        );
+    }
+    if let Some(currency) = directive.option("currency") {
+        invoice.currency = Some(currency.to_string());
+    }
+    Ok(table(&invoice))
+}
+
+fn load_items(path: &str, resolver: &dyn ResourceResolver) -> Result<Invoice, String> {
+    let data = resolver
+        .resolve(path)
+        .map_err(|e| format!("Failed to read invoice items {}: {}", path, e))?;
+    if path.ends_with(".csv") {
+        let items = csv::Reader::from_reader(data.as_slice())
+            .deserialize()
+            .collect::<Result<_, _>>()
+            .map_err(|e| format!("Invalid CSV {}: {}", path, e))?;
+        return Ok(Invoice {
+            items,
+            ..Invoice::default()
+        });
+    }
+    // YAML reads JSON too
+    let value: serde_yaml::Value =
+        serde_yaml::from_slice(&data).map_err(|e| format!("Invalid data in {}: {}", path, e))?;
+    let invoice = if value.is_sequence() {
+        serde_yaml::from_value(value).map(|items| Invoice {
+            items,
+            ..Invoice::default()
+        })
+    } else {
+        serde_yaml::from_value(value)
+    };
+    invoice.map_err(|e| format!("Invalid invoice items in {}: {}", path, e))
+}
+
+fn table(invoice: &Invoice) -> Block {
+    let money = |amount: f64| match &invoice.currency {
+        Some(currency) => format!("{} {:.2}", currency, amount),
+        None => format!("{:.2}", amount),
+    };
+    let text = |text: String| vec![Span::Text(text)];
+    let bold = |text: String| vec![Span::Bold(vec![Span::Text(text)])];
+
+    let mut rows = Vec::new();
+    let mut subtotal = 0.0;
+    for item in &invoice.items {
+        let amount = round_cents(item.quantity * item.price);
+        subtotal += amount;
+        rows.push(vec![
+            text(item.description.clone()),
+            text(item.quantity.to_string()),
+            text(money(item.price)),
+            text(money(amount)),
+        ]);
+    }
+
+    // Subtotal, tax and total form the footer
+    let items = rows.len();
+    let mut total = subtotal;
+    if let Some(rate) = invoice.tax {
+        let tax = round_cents(subtotal * rate / 100.0);
+        total += tax;
+        rows.push(vec![
+            text("Subtotal".into()),
+            vec![],
+            vec![],
+            text(money(subtotal)),
+        ]);
+        rows.push(vec![
+            text(format!("Tax ({}%)", rate)),
+            vec![],
+            vec![],
+            text(money(tax)),
+        ]);
+    }
+    rows.push(vec![
+        bold("Total".into()),
+        vec![],
+        vec![],
+        bold(money(total)),
+    ]);
+
+    Block::Table {
+        headers: ["Description", "Quantity", "Unit price", "Amount"]
+            .map(|header| text(header.to_string()))
+            .to_vec(),
+        footer: rows.len() - items,
+        rows,
+        align: vec![
+            Alignment::Left,
+            Alignment::Right,
+            Alignment::Right,
+            Alignment::Right,
+        ],
+        caption: None,
+        label: None,
+        style: Vec::new(),
+    }
+}
+
+fn round_cents(amount: f64) -> f64 {
+    (amount * 100.0).round() / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::block::{Block, Span};
+    use crate::parser;
+
+    fn cells(block: &Block) -> Vec<Vec<String>> {
+        let Block::Table { rows, .. } = block else {
+            panic!("expected a table, got {:?}", block);
+        };
+        rows.iter()
+            .map(|row| {
+                row.iter()
+                    .map(|cell| match cell.as_slice() {
+                        [] => String::new(),
+                        [Span::Text(text)] => text.clone(),
+                        [Span::Bold(inner)] => match inner.as_slice() {
+                            [Span::Text(text)] => format!("*{}*", text),
+                            _ => unreachable!(),
+                        },
+                        _ => unreachable!(),
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn totals_from_frontmatter() {
+        let markdown = "---\ninvoice:\n  currency: EUR\n  tax: 21\n  items:\n    - description: Design\n      quantity: 3\n      price: 80\n    - description: Hosting\n      price: 12.5\n---\n\n@invoice()\n";
+        let blocks = parser::parse(markdown);
+        assert_eq!(
+            cells(&blocks[0]),
+            [
+                ["Design", "3", "EUR 80.00", "EUR 240.00"],
+                ["Hosting", "1", "EUR 12.50", "EUR 12.50"],
+                ["Subtotal", "", "", "EUR 252.50"],
+                ["Tax (21%)", "", "", "EUR 53.03"],
+                ["*Total*", "", "", "*EUR 305.53*"],
+            ]
+        );
+
+        // Problems show up in the document
+        let blocks = parser::parse("@invoice()");
+        assert!(matches!(
+            &blocks[0],
+            Block::Paragraph { content } if matches!(content.as_slice(), [Span::Text(text)] if text.starts_with("[No invoice items"))
+        ));
+    }
+}
@@ -1,26 +1,104 @@
+mod acronyms;
 mod block;
+mod build_info;
+mod builder;
+mod cancel;
+mod changes;
+mod chapters;
+mod check;
 mod config;
+mod data_uri;
+mod diagnostic;
+mod diagram;
+mod diff;
+mod engine;
+mod glyphs;
+mod html;
+mod html_table;
+mod invoice;
+mod lists;
+mod outline;
+mod overflow;
 mod parser;
+mod positions;
+mod remote;
+mod render;
+mod resource;
+mod search;
+mod stats;
+mod svg;
+mod template;
+mod text;
 mod typst;
-
-pub use block::{Block, List, ListItem, Span};
-pub use config::Config;
-
-use typst_as_lib::TypstEngine;
-use typst_as_lib::typst_kit_options::TypstKitFontOptions;
-use typst_pdf::PdfOptions;
-
-// Bundled Open Sans font for sans-serif
-static OPEN_SANS_REGULAR: &[u8] = include_bytes!("../fonts/OpenSans-Regular.ttf");
-static OPEN_SANS_BOLD: &[u8] = include_bytes!("../fonts/OpenSans-Bold.ttf");
-static OPEN_SANS_ITALIC: &[u8] = include_bytes!("../fonts/OpenSans-Italic.ttf");
-static OPEN_SANS_BOLD_ITALIC: &[u8] = include_bytes!("../fonts/OpenSans-BoldItalic.ttf");
+mod unicode;
+
+pub use block::{Alignment, Block, List, ListItem, Region, Span};
+pub use builder::DocumentBuilder;
+pub use cancel::{CANCELLED, CancellationToken};
+pub use chapters::Chapter;
+pub use config::{
+    CodeOverflow, Config, FootnoteNumbering, LimitExceeded, Mode, PROJECT_CONFIG, PrintUrls,
+    TableWidth, Theme,
+};
+pub use diagnostic::{Diagnostic, Severity};
+pub use diff::{PageChange, PageDiff};
+pub use outline::OutlineEntry;
+pub use positions::SourcePosition;
+pub use render::{CompileReport, Renderer, SvgDocument};
+pub use resource::{FsResolver, ResourceResolver};
+pub use search::{BoundingBox, SearchMatch};
+pub use stats::DocStats;
+pub use text::TextRun;
+pub use typst_library::layout::PagedDocument;
+
+use std::ops::RangeInclusive;
+use std::path::Path;
 
 /// Parse markdown text into a vector of blocks.
 pub fn parse(markdown: &str) -> Vec<Block> {
     parser::parse(markdown)
 }
 
+/// The markdown without its YAML frontmatter, if it has any.
+pub fn strip_frontmatter(markdown: &str) -> &str {
+    parser::strip_frontmatter(markdown)
+}
+
+/// Point the relative image, `@csv(...)` and `@invoice(...)` paths in
+/// markdown into `dir`, e.g. before joining files from several directories
+/// into one document.
+pub fn rebase_paths(markdown: &str, dir: &Path) -> String {
+    parser::rebase_paths(markdown, dir)
+}
+
+/// Mark the blocks added or edited since `previous` (an earlier version of the
+/// document) with change bars in the margin.
+pub fn mark_changes(previous: &[Block], current: &[Block]) -> Vec<Block> {
+    changes::mark_changes(previous, current.to_vec(), Vec::new()).0
+}
+
+/// Split markdown into chapters at headings of `level` or higher, e.g. to
+/// render each chapter of a handbook as its own file.
+pub fn split_chapters(markdown: &str, level: u8) -> Vec<Chapter> {
+    chapters::split_chapters(markdown, level)
+}
+
+/// Load a JSON or YAML file to use as template data.
+pub fn load_data(path: &Path) -> Result<serde_json::Value, String> {
+    template::load_data(path)
+}
+
+/// Expand `{{path.to.value}}` and `{{#each list}}...{{/each}}` in markdown using template data.
+pub fn apply_template(markdown: &str, data: &serde_json::Value) -> Result<String, String> {
+    template::render(markdown, data)
+}
+
+/// Short hash of `HEAD` in the git repository holding `source`, for
+/// `build_info.commit`. Runs `git`, so look it up once per document.
+pub fn git_commit(source: &Path) -> Option<String> {
+    build_info::git_commit(source)
+}
+
 /// Convert markdown to Typst markup using default config.
 pub fn markdown_to_typst(markdown: &str) -> String {
     markdown_to_typst_with_config(markdown, &Config::compiled_default())
@@ -28,56 +106,107 @@ pub fn markdown_to_typst(markdown: &str) -> String {
 
 /// Convert markdown to Typst markup with custom config.
 pub fn markdown_to_typst_with_config(markdown: &str, config: &Config) -> String {
-    let blocks = parse(markdown);
+    let blocks = parse(&unicode::clean(markdown, config));
+    let blocks = diagram::render_diagrams(blocks, &config.diagrams);
     typst::blocks_to_typst(&blocks, config)
 }
 
+/// Convert already-parsed (or programmatically built) blocks to Typst markup.
+pub fn blocks_to_typst(blocks: &[Block], config: &Config) -> String {
+    let blocks = diagram::render_diagrams(blocks.to_vec(), &config.diagrams);
+    typst::blocks_to_typst(&blocks, config)
+}
+
+/// Flatten markdown to readable plain text, e.g. for indexing, search or diffing.
+pub fn extract_text(markdown: &str) -> String {
+    text::blocks_to_text(&parse(markdown))
+}
+
+/// Flatten blocks to readable plain text.
+pub fn blocks_to_text(blocks: &[Block]) -> String {
+    text::blocks_to_text(blocks)
+}
+
+/// Extract the text of a compiled document page by page (see [`Renderer::compile`]).
+pub fn document_text(doc: &PagedDocument) -> String {
+    text::document_text(doc)
+}
+
+/// Compile markdown and list its headings with the pages they start on,
+/// e.g. for a table-of-contents sidebar.
+pub fn document_outline(markdown: &str, config: &Config) -> Result<Vec<OutlineEntry>, String> {
+    Renderer::new(config.clone()).outline(markdown)
+}
+
+/// Compare two compiled documents page by page at the given resolution, e.g. to
+/// review how an edit changed the layout (see [`Renderer::compile`]).
+pub fn diff_documents(
+    old: &PagedDocument,
+    new: &PagedDocument,
+    dpi: f32,
+) -> Result<Vec<PageDiff>, String> {
+    diff::diff_documents(old, new, dpi)
+}
+
+/// Count words, characters, headings and code blocks, and estimate the page count
+/// without laying out the document (see [`Renderer::stats`] for the real count).
+pub fn stats(markdown: &str) -> DocStats {
+    stats::stats(&parse(markdown))
+}
+
+/// Find all occurrences of `query` (ASCII case-insensitive) in a compiled document,
+/// with page-relative coordinates for highlighting.
+pub fn search(doc: &PagedDocument, query: &str) -> Vec<SearchMatch> {
+    search::search(doc, query)
+}
+
+/// Convert markdown to a standalone HTML document using default config.
+pub fn markdown_to_html(markdown: &str) -> String {
+    markdown_to_html_with_config(markdown, &Config::compiled_default())
+}
+
+/// Convert markdown to a standalone HTML document with custom config.
+pub fn markdown_to_html_with_config(markdown: &str, config: &Config) -> String {
+    let blocks = parse(&unicode::clean(markdown, config));
+    let blocks = diagram::render_diagrams(blocks, &config.diagrams);
+    html::blocks_to_html(&blocks, config)
+}
+
+/// Convert blocks to a standalone HTML document.
+pub fn blocks_to_html(blocks: &[Block], config: &Config) -> String {
+    let blocks = diagram::render_diagrams(blocks.to_vec(), &config.diagrams);
+    html::blocks_to_html(&blocks, config)
+}
+
 /// Convert markdown to PDF bytes using default config.
 pub fn markdown_to_pdf(markdown: &str) -> Result<Vec<u8>, String> {
     markdown_to_pdf_with_config(markdown, &Config::compiled_default())
 }
 
-/// Compile markdown to a Typst document.
-fn compile_document(
-    markdown: &str,
-    config: &Config,
-) -> Result<typst_library::layout::PagedDocument, String> {
-    let typst_content = markdown_to_typst_with_config(markdown, config);
-
-    let font_options = TypstKitFontOptions::new()
-        .include_embedded_fonts(true)
-        .include_system_fonts(false);
-
-    let engine = TypstEngine::builder()
-        .main_file(typst_content)
-        .fonts([
-            OPEN_SANS_REGULAR,
-            OPEN_SANS_BOLD,
-            OPEN_SANS_ITALIC,
-            OPEN_SANS_BOLD_ITALIC,
-        ])
-        .search_fonts_with(font_options)
-        .build();
-
-    engine
-        .compile()
-        .output
-        .map_err(|e| format!("Typst compilation failed: {:?}", e))
-}
-
 /// Convert markdown to PDF bytes with custom config.
 pub fn markdown_to_pdf_with_config(markdown: &str, config: &Config) -> Result<Vec<u8>, String> {
-    let doc = compile_document(markdown, config)?;
+    Renderer::new(config.clone()).render_pdf(markdown)
+}
 
-    typst_pdf::pdf(&doc, &PdfOptions::default())
-        .map_err(|e| format!("PDF generation failed: {:?}", e))
+/// Convert blocks to PDF bytes.
+pub fn blocks_to_pdf(blocks: &[Block], config: &Config) -> Result<Vec<u8>, String> {
+    let blocks = diagram::render_diagrams(blocks.to_vec(), &config.diagrams);
+    let doc = Renderer::new(config.clone()).compile_blocks(&blocks, &[])?;
+    render::export_pdf(&doc)
 }
 
-/// Result of rendering markdown to SVG pages.
-pub struct SvgDocument {
-    pub pages: Vec<String>,
-    pub width_pt: f64,
-    pub height_pt: f64,
+/// Convert markdown to one PNG image per page using default config.
+pub fn markdown_to_png(markdown: &str, dpi: f32) -> Result<Vec<Vec<u8>>, String> {
+    markdown_to_png_with_config(markdown, &Config::compiled_default(), dpi)
+}
+
+/// Convert markdown to one PNG image per page with custom config.
+pub fn markdown_to_png_with_config(
+    markdown: &str,
+    config: &Config,
+    dpi: f32,
+) -> Result<Vec<Vec<u8>>, String> {
+    Renderer::new(config.clone()).render_png(markdown, dpi)
 }
 
 /// Convert markdown to SVG pages using default config.
@@ -87,21 +216,21 @@ pub fn markdown_to_svg(markdown: &str) -> Result<SvgDocument, String> {
 
 /// Convert markdown to SVG pages with custom config.
 pub fn markdown_to_svg_with_config(markdown: &str, config: &Config) -> Result<SvgDocument, String> {
-    let doc = compile_document(markdown, config)?;
-
-    let pages: Vec<String> = doc.pages.iter().map(|page| typst_svg::svg(page)).collect();
-
-    // Get dimensions from first page (assuming all pages same size)
-    let (width_pt, height_pt) = if let Some(first_page) = doc.pages.first() {
-        let size = first_page.frame.size();
-        (size.x.to_pt(), size.y.to_pt())
-    } else {
-        (595.0, 842.0) // A4 default
-    };
-
-    Ok(SvgDocument {
-        pages,
-        width_pt,
-        height_pt,
-    })
+    Renderer::new(config.clone()).render_svg(markdown)
+}
+
+/// Convert markdown to SVG, serializing only the given pages (1-based, inclusive).
+pub fn markdown_to_svg_pages(
+    markdown: &str,
+    config: &Config,
+    pages: RangeInclusive<usize>,
+) -> Result<SvgDocument, String> {
+    Renderer::new(config.clone()).render_svg_pages(markdown, pages)
+}
+
+/// Convert blocks to SVG pages.
+pub fn blocks_to_svg(blocks: &[Block], config: &Config) -> Result<SvgDocument, String> {
+    let blocks = diagram::render_diagrams(blocks.to_vec(), &config.diagrams);
+    let doc = Renderer::new(config.clone()).compile_blocks(&blocks, &[])?;
+    render::export_svg(&doc)
 }
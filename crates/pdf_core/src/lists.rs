@@ -0,0 +1,59 @@
+use std::borrow::Cow;
+
+use crate::block::Block;
+
+/// Continue the numbering of ordered lists interrupted by other blocks, e.g.
+/// the paragraphs or code blocks between the steps of a guide: a list starting
+/// at 1 picks up where the ordered list before it left off. Lists that start
+/// at another number keep it. A heading, rule or page break starts counting
+/// afresh.
+pub(crate) fn continue_numbering(blocks: &[Block], enabled: bool) -> Cow<'_, [Block]> {
+    if !enabled {
+        return Cow::Borrowed(blocks);
+    }
+    let mut blocks = blocks.to_vec();
+    let mut next = None;
+    for block in &mut blocks {
+        match block {
+            Block::List(list) if list.ordered => {
+                if list.start == 1
+                    && let Some(next) = next
+                {
+                    list.start = next;
+                }
+                next = Some(list.start + list.items.len() as u64);
+            }
+            Block::Heading { .. } | Block::Rule | Block::PageBreak | Block::MainMatter => {
+                next = None
+            }
+            _ => {}
+        }
+    }
+    Cow::Owned(blocks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::continue_numbering;
+    use crate::block::Block;
+    use crate::parser;
+
+    fn starts(blocks: &[Block]) -> Vec<u64> {
+        blocks
+            .iter()
+            .filter_map(|block| match block {
+                Block::List(list) => Some(list.start),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn continues_after_interruptions() {
+        let blocks = parser::parse(
+            "1. One\n2. Two\n\nA note.\n\n```\ncode\n```\n\n1. Three\n\nThen:\n\n7. Seven\n\n# Next\n\n1. One\n",
+        );
+        assert_eq!(starts(&continue_numbering(&blocks, true)), [1, 3, 7, 1]);
+        assert_eq!(starts(&continue_numbering(&blocks, false)), [1, 1, 7, 1]);
+    }
+}
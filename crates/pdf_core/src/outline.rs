@@ -0,0 +1,47 @@
+use typst_library::foundations::{Element, Selector, Value};
+use typst_library::layout::PagedDocument;
+use typst_library::model::HeadingElem;
+
+use crate::block::Block;
+use crate::typst::collect_span_text;
+
+/// A heading in the rendered document and the page it landed on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutlineEntry {
+    pub level: u8,
+    pub title: String,
+    /// 1-based page number
+    pub page: usize,
+}
+
+/// Pair the headings in `blocks` with their pages in the compiled document.
+///
+/// Every markdown heading is emitted as exactly one Typst heading, in order,
+/// so the n-th outlined heading element in the layout is the n-th heading
+/// block. Headings Typst adds itself, like the table of contents' title, are
+/// not outlined.
+pub fn document_outline(blocks: &[Block], doc: &PagedDocument) -> Vec<OutlineEntry> {
+    let headings = doc
+        .introspector
+        .query(&Selector::Elem(Element::of::<HeadingElem>(), None));
+    let headings = headings
+        .iter()
+        .filter(|element| !matches!(element.get_by_name("outlined"), Ok(Value::Bool(false))));
+
+    blocks
+        .iter()
+        .filter_map(|block| match block {
+            Block::Heading { level, content, .. } => Some((*level, content)),
+            _ => None,
+        })
+        .zip(headings)
+        .map(|((level, content), element)| {
+            let mut title = String::new();
+            collect_span_text(content, &mut title);
+            let page = element
+                .location()
+                .map_or(1, |location| doc.introspector.page(location).get());
+            OutlineEntry { level, title, page }
+        })
+        .collect()
+}
@@ -0,0 +1,36 @@
+use typst_library::foundations::{Element, Selector, Value};
+use typst_library::introspection::MetadataElem;
+use typst_library::layout::PagedDocument;
+use typst_syntax::Source;
+
+use crate::diagnostic::Diagnostic;
+use crate::typst::{CODE_OVERFLOW_MARKER, SourceMap};
+
+/// Warn about code blocks with lines too long for the page, for
+/// `code.overflow = "scroll-note"`. Laying such a block out places a
+/// `metadata` element in the document, made where the block is emitted;
+/// other metadata, e.g. from `[overrides]`, is left alone.
+pub(crate) fn overflowing_code(
+    doc: &PagedDocument,
+    source: &Source,
+    source_map: &SourceMap,
+) -> Vec<Diagnostic> {
+    doc.introspector
+        .query(&Selector::Elem(Element::of::<MetadataElem>(), None))
+        .iter()
+        .filter(|element| {
+            element
+                .to_packed::<MetadataElem>()
+                .is_some_and(|metadata| metadata.value == Value::Str(CODE_OVERFLOW_MARKER.into()))
+        })
+        .map(|element| {
+            let line = source
+                .range(element.span())
+                .and_then(|range| source_map.line_for_offset(range.start));
+            Diagnostic::warning(
+                "code block has lines too long for the page, which wrap or run into the margin; set code.overflow to \"wrap\" or \"shrink\" to fit them",
+            )
+            .at_line(line)
+        })
+        .collect()
+}
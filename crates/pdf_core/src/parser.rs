@@ -1,9 +1,19 @@
+use std::borrow::Cow;
+use std::ops::Range;
+use std::path::Path;
+
 use pulldown_cmark::{Event, HeadingLevel, Options, Parser, Tag, TagEnd};
 
-use crate::block::{Block, List, ListItem, Span};
+use crate::block::{Alignment, Block, List, ListItem, Region, Span};
+use crate::config::is_length;
+use crate::html_table;
+use crate::invoice;
+use crate::resource::{FsResolver, ResourceResolver};
+use crate::typst::collect_span_text;
+use crate::unicode;
 
 /// Strip YAML frontmatter from the beginning of markdown content
-fn strip_frontmatter(markdown: &str) -> &str {
+pub(crate) fn strip_frontmatter(markdown: &str) -> &str {
     if !markdown.starts_with("---") {
         return markdown;
     }
@@ -17,21 +27,162 @@ fn strip_frontmatter(markdown: &str) -> &str {
     }
 }
 
-/// Parse markdown text into a list of blocks
-pub fn parse(markdown: &str) -> Vec<Block> {
-    let markdown = strip_frontmatter(markdown);
+/// The YAML between the `---` lines at the start of the markdown, if any
+pub(crate) fn frontmatter(markdown: &str) -> Option<&str> {
+    let rest = markdown.strip_prefix("---")?;
+    rest.find("\n---").map(|end| &rest[..end])
+}
+
+/// Point the relative image, `@csv` and `@invoice` paths in `markdown` into
+/// `dir`, so the markdown can be joined with a document from another directory.
+pub(crate) fn rebase_paths(markdown: &str, dir: &Path) -> String {
+    let body = strip_frontmatter(markdown);
+    let offset = markdown.len() - body.len();
+    let rebase = |path: &str| dir.join(path).display().to_string();
+    let mut edits: Vec<(Range<usize>, String)> = Vec::new();
+    for (event, range) in Parser::new_ext(body, markdown_options()).into_offset_iter() {
+        let source = &body[range.clone()];
+        match event {
+            Event::Start(Tag::Image { dest_url, .. }) if is_relative_path(&dest_url) => {
+                // The destination as written, possibly in angle brackets
+                let Some((at, bracketed)) = source
+                    .find(&format!("]({}", dest_url))
+                    .map(|at| (at + 2, false))
+                    .or_else(|| {
+                        source
+                            .find(&format!("](<{}", dest_url))
+                            .map(|at| (at + 3, true))
+                    })
+                else {
+                    continue;
+                };
+                let path = rebase(&dest_url);
+                let path = match !bracketed && path.contains([' ', '(', ')']) {
+                    true => format!("<{}>", path),
+                    false => path,
+                };
+                let start = range.start + at;
+                edits.push((start..start + dest_url.len(), path));
+            }
+            Event::Start(Tag::Paragraph) if !source.trim().contains('\n') => {
+                let Some(directive) = parse_directive(source) else {
+                    continue;
+                };
+                let Some(path) = directive.arg.as_ref().filter(|path| is_relative_path(path))
+                else {
+                    continue;
+                };
+                if !matches!(directive.name.as_str(), "csv" | "invoice") {
+                    continue;
+                }
+                let Some(at) = source
+                    .find('(')
+                    .and_then(|open| source[open..].find(path.as_str()).map(|at| open + at))
+                else {
+                    continue;
+                };
+                let start = range.start + at;
+                edits.push((start..start + path.len(), rebase(path)));
+            }
+            _ => {}
+        }
+    }
+
+    let mut out = markdown[..offset].to_string();
+    let mut last = 0;
+    for (range, path) in edits {
+        out.push_str(&body[last..range.start]);
+        out.push_str(&path);
+        last = range.end;
+    }
+    out.push_str(&body[last..]);
+    out
+}
+
+/// Whether `path` is a file path relative to the document, not a URL.
+fn is_relative_path(path: &str) -> bool {
+    !path.is_empty()
+        && !path.contains("://")
+        && !path.starts_with("data:")
+        && Path::new(path).is_relative()
+}
+
+/// The markdown extensions the converter understands
+pub(crate) fn markdown_options() -> Options {
     let mut options = Options::empty();
     options.insert(Options::ENABLE_TABLES);
     options.insert(Options::ENABLE_TASKLISTS);
-    let parser = Parser::new_ext(markdown, options);
+    options.insert(Options::ENABLE_HEADING_ATTRIBUTES);
+    options
+}
+
+/// Parse markdown text into a list of blocks
+pub fn parse(markdown: &str) -> Vec<Block> {
+    parse_with_lines(markdown).0
+}
+
+/// Parse markdown text into blocks, along with the 1-based source line each block starts on.
+pub fn parse_with_lines(markdown: &str) -> (Vec<Block>, Vec<usize>) {
+    parse_with_resolver(markdown, &FsResolver::default())
+}
+
+/// Like [`parse_with_lines`], reading included files (e.g. `@csv(...)`) through `resolver`.
+pub fn parse_with_resolver(
+    markdown: &str,
+    resolver: &dyn ResourceResolver,
+) -> (Vec<Block>, Vec<usize>) {
+    let body = strip_frontmatter(markdown);
+    // Offsets from the parser are relative to the body, lines are reported for the whole file
+    let body_offset = markdown.len() - body.len();
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(markdown.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let line_of = |offset: usize| line_starts.partition_point(|&start| start <= offset);
+
+    let parser = Parser::new_ext(body, markdown_options()).into_offset_iter();
     let mut blocks = Vec::new();
-    let mut state = ParseState::default();
+    let mut lines = Vec::new();
+    let mut state = ParseState {
+        frontmatter: frontmatter(markdown).map(str::to_string),
+        ..ParseState::default()
+    };
+
+    // Nesting depth, to find where each top-level block starts
+    let mut depth = 0usize;
+    let mut block_start = 0;
+
+    for (event, range) in parser {
+        match &event {
+            Event::Start(_) => {
+                if depth == 0 {
+                    block_start = range.start;
+                }
+                depth += 1;
+            }
+            Event::End(_) => depth = depth.saturating_sub(1),
+            _ if depth == 0 => block_start = range.start,
+            _ => {}
+        }
+
+        process_event(event, &mut state, &mut blocks, resolver);
+
+        while lines.len() < blocks.len() {
+            lines.push(line_of(body_offset + block_start));
+        }
+    }
+
+    // As do tables and regions still open
+    finish_html_table(&mut state, &mut blocks);
+    close_regions(&mut state, &mut blocks);
+    while lines.len() < blocks.len() {
+        lines.push(line_of(markdown.len()));
+    }
 
-    for event in parser {
-        process_event(event, &mut state, &mut blocks);
+    for block in &mut blocks {
+        resolve_references(block);
     }
 
-    blocks
+    (blocks, lines)
 }
 
 #[derive(Default)]
@@ -45,24 +196,52 @@ struct ParseState {
 
     // Current heading level (if in a heading)
     heading_level: Option<u8>,
+    heading_id: Option<String>,
 
     // Code block state
     in_code_block: bool,
     code_language: Option<String>,
+    code_title: Option<String>,
     code_content: String,
 
     // Link state
     link_url: Option<String>,
 
+    // Image state: (path, title) while inside an image, and an image that
+    // started the current paragraph (a block if nothing else follows)
+    image: Option<(String, String)>,
+    standalone_image: Option<Block>,
+
     // List state
     list_stack: Vec<ListBuilder>,
 
+    // YAML frontmatter of the document, for directives that read it
+    frontmatter: Option<String>,
+
     // Table state
     in_table: bool,
     table_headers: Vec<Vec<Span>>,
     table_rows: Vec<Vec<Vec<Span>>>,
+    table_align: Vec<Alignment>,
+    // Index of the first footer row, after a `|===|` row
+    table_footer: Option<usize>,
+    // Options of a `<!-- table: ... -->` comment, with the number of blocks
+    // when it came, so it only applies to a table right after it
+    table_style: Option<(Vec<(String, String)>, usize)>,
     current_row: Vec<Vec<Span>>,
     in_table_head: bool,
+
+    // Open regions, innermost last
+    regions: Vec<Region>,
+
+    // Past the `---mainmatter---` marker; later ones are ignored
+    in_main_matter: bool,
+
+    // Text of a `<summary>` tag not closed yet
+    summary: Option<String>,
+
+    // HTML of a `<table>` not closed yet
+    html_table: Option<String>,
 }
 
 #[derive(Clone, Copy)]
@@ -73,34 +252,62 @@ enum FormatKind {
 
 struct ListBuilder {
     ordered: bool,
+    start: u64,
     items: Vec<ListItem>,
     current_item_spans: Vec<Span>,
     current_item_checked: Option<bool>,
 }
 
-fn process_event(event: Event, state: &mut ParseState, blocks: &mut Vec<Block>) {
+fn process_event(
+    event: Event,
+    state: &mut ParseState,
+    blocks: &mut Vec<Block>,
+    resolver: &dyn ResourceResolver,
+) {
     match event {
         // Headings
-        Event::Start(Tag::Heading { level, .. }) => {
+        Event::Start(Tag::Heading { level, id, .. }) => {
             state.heading_level = Some(heading_level_to_u8(level));
+            state.heading_id = id.map(|id| id.into_string());
         }
         Event::End(TagEnd::Heading(_)) => {
             if let Some(level) = state.heading_level.take() {
                 let content = std::mem::take(&mut state.spans);
-                blocks.push(Block::Heading { level, content });
+                let id = state.heading_id.take();
+                blocks.push(Block::Heading { level, content, id });
             }
         }
 
         // Paragraphs
-        Event::Start(Tag::Paragraph) => {}
+        Event::Start(Tag::Paragraph) => {
+            state.standalone_image = None;
+        }
         Event::End(TagEnd::Paragraph) => {
             let content = std::mem::take(&mut state.spans);
+            if let Some(mut image) = state.standalone_image.take()
+                && state.list_stack.is_empty()
+                && !state.in_table
+                && let Some(image_label) = image_paragraph(&content, &image)
+            {
+                if let Block::Image { label, .. } = &mut image {
+                    *label = image_label;
+                }
+                blocks.push(image);
+                return;
+            }
             if !content.is_empty() {
-                // Check for manual page break marker
+                // Check for manual page break and main matter markers
                 if content.len() == 1 {
                     if let Span::Text(text) = &content[0] {
-                        if text.trim() == "---pagebreak---" {
-                            blocks.push(Block::PageBreak);
+                        let marker = text.trim();
+                        if marker == "---pagebreak---" || marker == "---mainmatter---" {
+                            // Regions are containers, which a page break can't be in
+                            close_regions(state, blocks);
+                            if marker == "---pagebreak---" {
+                                blocks.push(Block::PageBreak);
+                            } else if !std::mem::replace(&mut state.in_main_matter, true) {
+                                blocks.push(Block::MainMatter);
+                            }
                             return;
                         }
                     }
@@ -110,7 +317,14 @@ fn process_event(event: Event, state: &mut ParseState, blocks: &mut Vec<Block>)
                     list.current_item_spans.extend(content);
                 } else if state.in_table {
                     // Ignore paragraphs in tables, handled by cell
-                } else {
+                } else if let Some(mut block) =
+                    directive_block(&content, state.frontmatter.as_deref(), resolver)
+                {
+                    if let Block::Table { style, .. } = &mut block {
+                        *style = take_table_style(state, blocks);
+                    }
+                    blocks.push(block);
+                } else if let Some(content) = attach_table_caption(content, blocks) {
                     blocks.push(Block::Paragraph { content });
                 }
             }
@@ -121,7 +335,7 @@ fn process_event(event: Event, state: &mut ParseState, blocks: &mut Vec<Block>)
             if state.in_code_block {
                 state.code_content.push_str(&text);
             } else {
-                state.spans.push(Span::Text(text.into_string()));
+                push_text(&mut state.spans, &text);
             }
         }
 
@@ -176,15 +390,37 @@ fn process_event(event: Event, state: &mut ParseState, blocks: &mut Vec<Block>)
             }
         }
 
+        // Images: alone in a paragraph they become blocks, inline they show their alt text
+        Event::Start(Tag::Image {
+            dest_url, title, ..
+        }) => {
+            state.image = Some((dest_url.into_string(), title.into_string()));
+            state.span_stack.push(std::mem::take(&mut state.spans));
+        }
+        Event::End(TagEnd::Image) => {
+            let alt_content = std::mem::take(&mut state.spans);
+            if let Some(mut parent) = state.span_stack.pop() {
+                if let Some((path, title)) = state.image.take() {
+                    let mut alt = String::new();
+                    collect_span_text(&alt_content, &mut alt);
+                    state.standalone_image = parent.is_empty().then(|| Block::Image {
+                        path,
+                        alt: alt.clone(),
+                        title: (!title.is_empty()).then_some(title),
+                        label: None,
+                    });
+                    push_text(&mut parent, &alt);
+                }
+                state.spans = parent;
+            }
+        }
+
         // Code blocks
         Event::Start(Tag::CodeBlock(kind)) => {
             state.in_code_block = true;
-            state.code_language = match kind {
-                pulldown_cmark::CodeBlockKind::Fenced(lang) => {
-                    let lang = lang.into_string();
-                    if lang.is_empty() { None } else { Some(lang) }
-                }
-                pulldown_cmark::CodeBlockKind::Indented => None,
+            (state.code_language, state.code_title) = match kind {
+                pulldown_cmark::CodeBlockKind::Fenced(info) => fence_info(&info),
+                pulldown_cmark::CodeBlockKind::Indented => (None, None),
             };
             state.code_content.clear();
         }
@@ -192,13 +428,19 @@ fn process_event(event: Event, state: &mut ParseState, blocks: &mut Vec<Block>)
             state.in_code_block = false;
             let content = std::mem::take(&mut state.code_content);
             let language = state.code_language.take();
-            blocks.push(Block::CodeBlock { language, content });
+            let title = state.code_title.take();
+            blocks.push(Block::CodeBlock {
+                language,
+                content,
+                title,
+            });
         }
 
         // Lists
         Event::Start(Tag::List(first_item)) => {
             state.list_stack.push(ListBuilder {
                 ordered: first_item.is_some(),
+                start: first_item.unwrap_or(1),
                 items: Vec::new(),
                 current_item_spans: Vec::new(),
                 current_item_checked: None,
@@ -208,6 +450,7 @@ fn process_event(event: Event, state: &mut ParseState, blocks: &mut Vec<Block>)
             if let Some(list_builder) = state.list_stack.pop() {
                 let list = List {
                     ordered: list_builder.ordered,
+                    start: list_builder.start,
                     items: list_builder.items,
                 };
                 // If there's a parent list, this is nested
@@ -251,16 +494,31 @@ fn process_event(event: Event, state: &mut ParseState, blocks: &mut Vec<Block>)
         }
 
         // Tables
-        Event::Start(Tag::Table(_)) => {
+        Event::Start(Tag::Table(alignments)) => {
             state.in_table = true;
             state.table_headers.clear();
             state.table_rows.clear();
+            state.table_align = alignments.into_iter().map(convert_alignment).collect();
         }
         Event::End(TagEnd::Table) => {
             state.in_table = false;
             let headers = std::mem::take(&mut state.table_headers);
             let rows = std::mem::take(&mut state.table_rows);
-            blocks.push(Block::Table { headers, rows });
+            let align = std::mem::take(&mut state.table_align);
+            let footer = state
+                .table_footer
+                .take()
+                .map_or(0, |start| rows.len() - start);
+            let style = take_table_style(state, blocks);
+            blocks.push(Block::Table {
+                headers,
+                rows,
+                footer,
+                align,
+                caption: None,
+                label: None,
+                style,
+            });
         }
 
         Event::Start(Tag::TableHead) => {
@@ -278,7 +536,12 @@ fn process_event(event: Event, state: &mut ParseState, blocks: &mut Vec<Block>)
         Event::End(TagEnd::TableRow) => {
             if !state.in_table_head {
                 let row = std::mem::take(&mut state.current_row);
-                state.table_rows.push(row);
+                // A `|===|` row starts the footer
+                if state.table_footer.is_none() && is_footer_separator(&row) {
+                    state.table_footer = Some(state.table_rows.len());
+                } else {
+                    state.table_rows.push(row);
+                }
             }
         }
 
@@ -290,6 +553,26 @@ fn process_event(event: Event, state: &mut ParseState, blocks: &mut Vec<Block>)
             state.current_row.push(cell_content);
         }
 
+        // Regions; markers that don't match the innermost open region, or
+        // that open a region of a kind already open, are ignored
+        Event::Html(html)
+            if state.list_stack.is_empty() && html_table_line(&html, state, blocks) => {}
+        Event::Html(html) if state.list_stack.is_empty() => match region_marker(&html) {
+            Some(Block::RegionStart(region)) if !state.regions.contains(&region) => {
+                state.regions.push(region);
+                blocks.push(Block::RegionStart(region));
+            }
+            Some(Block::RegionEnd(region)) if state.regions.last() == Some(&region) => {
+                state.regions.pop();
+                blocks.push(Block::RegionEnd(region));
+            }
+            Some(_) => {}
+            None => match table_style(&html) {
+                Some(options) => state.table_style = Some((options, blocks.len())),
+                None => details_html(&html, state, blocks),
+            },
+        },
+
         // Horizontal rule
         Event::Rule => {
             blocks.push(Block::Rule);
@@ -297,7 +580,7 @@ fn process_event(event: Event, state: &mut ParseState, blocks: &mut Vec<Block>)
 
         // Soft/hard breaks
         Event::SoftBreak => {
-            state.spans.push(Span::Text(" ".to_string()));
+            push_text(&mut state.spans, " ");
         }
         Event::HardBreak => {
             state.spans.push(Span::LineBreak);
@@ -308,6 +591,600 @@ fn process_event(event: Event, state: &mut ParseState, blocks: &mut Vec<Block>)
     }
 }
 
+/// The region start or end an HTML comment like `<!-- keep -->` or
+/// `<!-- /keep -->` marks.
+pub(crate) fn region_marker(html: &str) -> Option<Block> {
+    let comment = html
+        .trim()
+        .strip_prefix("<!--")?
+        .strip_suffix("-->")?
+        .trim();
+    let (name, end) = match comment.strip_prefix('/') {
+        Some(name) => (name, true),
+        None => (comment, false),
+    };
+    let region = match name {
+        "keep" => Region::Keep,
+        "wide" => Region::Wide,
+        "changed" => Region::Changed,
+        _ => return None,
+    };
+    Some(if end {
+        Block::RegionEnd(region)
+    } else {
+        Block::RegionStart(region)
+    })
+}
+
+/// The `key=value` options of a `<!-- table: striped=false width=full -->`
+/// comment.
+pub(crate) fn table_style(html: &str) -> Option<Vec<(String, String)>> {
+    let options = html
+        .trim()
+        .strip_prefix("<!--")?
+        .strip_suffix("-->")?
+        .trim()
+        .strip_prefix("table:")?;
+    Some(
+        options
+            .split_whitespace()
+            .map(|option| {
+                let (key, value) = option.split_once('=').unwrap_or((option, ""));
+                (key.to_string(), value.to_string())
+            })
+            .collect(),
+    )
+}
+
+/// The options of a `<!-- table: ... -->` comment for the table about to be
+/// added, unless other blocks came between them.
+fn take_table_style(state: &mut ParseState, blocks: &[Block]) -> Vec<(String, String)> {
+    state
+        .table_style
+        .take()
+        .filter(|(_, at)| *at == blocks.len())
+        .map(|(options, _)| options)
+        .unwrap_or_default()
+}
+
+/// Turn the `<details>` and `<summary>` tags in a line of HTML into a
+/// `Region::Details` starting with the summary as a bold paragraph. Text in
+/// the element written as HTML rather than markdown becomes plain paragraphs;
+/// other tags, and text outside the element, are dropped like any HTML.
+fn details_html(html: &str, state: &mut ParseState, blocks: &mut Vec<Block>) {
+    let mut text = String::new();
+    let mut rest = html;
+    while !rest.is_empty() {
+        let (before, tag, after) = match rest.find('<').and_then(|start| {
+            let end = start + rest[start..].find('>')?;
+            Some((&rest[..start], &rest[start + 1..end], &rest[end + 1..]))
+        }) {
+            Some(parts) => parts,
+            None => (rest, "", ""),
+        };
+        match &mut state.summary {
+            Some(summary) => summary.push_str(before),
+            None => text.push_str(before),
+        }
+        rest = after;
+
+        let name = tag
+            .split_whitespace()
+            .next()
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        match name.as_str() {
+            "details" | "/details" | "summary" => {
+                details_text(&mut text, state, blocks);
+                if name == "summary" {
+                    state.summary = Some(String::new());
+                } else if name == "details" && !state.regions.contains(&Region::Details) {
+                    state.regions.push(Region::Details);
+                    blocks.push(Block::RegionStart(Region::Details));
+                } else if name == "/details" && state.regions.last() == Some(&Region::Details) {
+                    state.regions.pop();
+                    blocks.push(Block::RegionEnd(Region::Details));
+                }
+            }
+            "/summary" => {
+                let summary = state.summary.take().unwrap_or_default();
+                let summary = summary.split_whitespace().collect::<Vec<_>>().join(" ");
+                if !summary.is_empty() && state.regions.last() == Some(&Region::Details) {
+                    blocks.push(Block::Paragraph {
+                        content: vec![Span::Bold(vec![Span::Text(
+                            unicode::nfc(&summary).into_owned(),
+                        )])],
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    details_text(&mut text, state, blocks);
+}
+
+/// Whether a table row is a `|===|` separator: cells of only `=`, or empty.
+fn is_footer_separator(row: &[Vec<Span>]) -> bool {
+    let mut separator = false;
+    for cell in row {
+        match cell.as_slice() {
+            [] => {}
+            [Span::Text(text)]
+                if !text.trim().is_empty() && text.trim().chars().all(|c| c == '=') =>
+            {
+                separator = true
+            }
+            _ => return false,
+        }
+    }
+    separator
+}
+
+/// Collect the lines of an HTML `<table>`, adding the table once it's closed.
+/// `false` if the line isn't part of a table.
+fn html_table_line(html: &str, state: &mut ParseState, blocks: &mut Vec<Block>) -> bool {
+    let table = match &mut state.html_table {
+        Some(table) => table,
+        None if is_table_html(html) => state.html_table.insert(String::new()),
+        None => return false,
+    };
+    table.push_str(html);
+    if table.to_ascii_lowercase().contains("</table") {
+        finish_html_table(state, blocks);
+    }
+    true
+}
+
+fn finish_html_table(state: &mut ParseState, blocks: &mut Vec<Block>) {
+    if let Some(mut block) = state
+        .html_table
+        .take()
+        .and_then(|html| html_table::parse_table(&html))
+    {
+        if let Block::Table { style, .. } = &mut block {
+            *style = take_table_style(state, blocks);
+        }
+        blocks.push(block);
+    }
+}
+
+/// Whether a line of HTML starts a `<table>`, which is rendered.
+pub(crate) fn is_table_html(html: &str) -> bool {
+    html.to_ascii_lowercase().contains("<table")
+}
+
+/// Whether a line of HTML has `<details>` or `<summary>` tags, which are rendered.
+pub(crate) fn is_details_html(html: &str) -> bool {
+    let html = html.to_ascii_lowercase();
+    ["<details", "</details", "<summary"]
+        .iter()
+        .any(|tag| html.contains(tag))
+}
+
+/// Add the text collected from HTML inside a `<details>` element as a paragraph.
+fn details_text(text: &mut String, state: &ParseState, blocks: &mut Vec<Block>) {
+    let paragraph = std::mem::take(text);
+    let paragraph = paragraph.trim();
+    if !paragraph.is_empty() && state.regions.last() == Some(&Region::Details) {
+        blocks.push(Block::Paragraph {
+            content: vec![Span::Text(unicode::nfc(paragraph).into_owned())],
+        });
+    }
+}
+
+fn close_regions(state: &mut ParseState, blocks: &mut Vec<Block>) {
+    while let Some(region) = state.regions.pop() {
+        blocks.push(Block::RegionEnd(region));
+    }
+}
+
+/// A block directive written as a paragraph of its own, e.g. `@qrcode(https://example.com, size=3cm)`.
+pub(crate) struct Directive {
+    pub(crate) name: String,
+    /// Everything before the options, commas included, unless empty
+    pub(crate) arg: Option<String>,
+    /// `key=value` options
+    options: Vec<(String, String)>,
+}
+
+impl Directive {
+    pub(crate) fn option(&self, key: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+pub(crate) fn parse_directive(text: &str) -> Option<Directive> {
+    let text = text.trim().strip_prefix('@')?;
+    let (name, rest) = text.split_once('(')?;
+    let inner = rest.strip_suffix(')')?;
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return None;
+    }
+
+    // Options are the `key=value` parts at the end; the argument before them
+    // keeps its commas and `=`, e.g. in a URL, or is quoted when it ends in
+    // something like an option itself
+    let inner = inner.trim();
+    let (arg, options) = match inner.strip_prefix('"') {
+        Some(quoted) => {
+            let (arg, rest) = quoted.split_once('"')?;
+            let options = match rest.trim() {
+                "" => Vec::new(),
+                rest => rest
+                    .strip_prefix(',')?
+                    .split(',')
+                    .map(directive_option)
+                    .collect::<Option<_>>()?,
+            };
+            (arg.to_string(), options)
+        }
+        None => {
+            let mut parts: Vec<&str> = inner.split(',').collect();
+            let mut options = Vec::new();
+            while parts.len() > 1 {
+                let Some(option) = parts.last().and_then(|part| directive_option(part)) else {
+                    break;
+                };
+                options.insert(0, option);
+                parts.pop();
+            }
+            (parts.join(",").trim().to_string(), options)
+        }
+    };
+
+    Some(Directive {
+        name: name.to_string(),
+        arg: Some(arg).filter(|arg| !arg.is_empty()),
+        options,
+    })
+}
+
+/// A `key=value` directive option, e.g. `size=3cm`.
+fn directive_option(part: &str) -> Option<(String, String)> {
+    let (key, value) = part.split_once('=')?;
+    let key = key.trim();
+    if key.is_empty()
+        || !key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-'))
+    {
+        return None;
+    }
+    Some((key.to_string(), value.trim().to_string()))
+}
+
+/// Whether a paragraph holds nothing but the alt text of the image it started
+/// with, and a `{#fig:id}` label after the image, if one is there.
+fn image_paragraph(content: &[Span], image: &Block) -> Option<Option<String>> {
+    let Block::Image { alt, .. } = image else {
+        return None;
+    };
+    let after = match content {
+        [] if alt.is_empty() => "",
+        [Span::Text(text)] => text.strip_prefix(alt.as_str())?.trim(),
+        _ => return None,
+    };
+    if after.is_empty() {
+        return Some(None);
+    }
+    let label = after.strip_prefix("{#")?.strip_suffix('}')?;
+    Some(Some(label.to_string()))
+}
+
+/// Whether `label` can be a Typst label and be referenced with `[@label]`:
+/// letters, digits and `:`, `_`, `-`, `.`.
+pub(crate) fn is_label(label: &str) -> bool {
+    !label.is_empty() && label.chars().all(is_label_char)
+}
+
+fn is_label_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, ':' | '_' | '-' | '.')
+}
+
+/// Turn a paragraph consisting of a known directive into its block.
+fn directive_block(
+    content: &[Span],
+    frontmatter: Option<&str>,
+    resolver: &dyn ResourceResolver,
+) -> Option<Block> {
+    let [Span::Text(text)] = content else {
+        return None;
+    };
+    let directive = parse_directive(text)?;
+    match directive.name.as_str() {
+        "qrcode" => {
+            let data = directive.arg.clone()?;
+            Some(match directive.option("size") {
+                // Pasted into the Typst markup, so it has to be a length
+                Some(size) if !is_length(size) => Block::Paragraph {
+                    content: vec![Span::Text(format!(
+                        "[QR code size is a length like 3cm, not {:?}]",
+                        size
+                    ))],
+                },
+                size => Block::QrCode {
+                    data,
+                    size: size.map(str::to_string),
+                },
+            })
+        }
+        "restart_numbers" => Some(Block::RestartNumbers {
+            level: match directive.arg.as_deref() {
+                None => 1,
+                Some(level) => level.parse().ok().filter(|level| (1..=6).contains(level))?,
+            },
+        }),
+        "csv" => {
+            let path = directive.arg.as_deref()?;
+            Some(
+                csv_table(&directive, path, resolver).unwrap_or_else(|e| Block::Paragraph {
+                    content: vec![Span::Text(format!("[{}]", e))],
+                }),
+            )
+        }
+        "invoice" => Some(
+            invoice::invoice_table(&directive, frontmatter, resolver).unwrap_or_else(|e| {
+                Block::Paragraph {
+                    content: vec![Span::Text(format!("[{}]", e))],
+                }
+            }),
+        ),
+        _ => None,
+    }
+}
+
+/// Load a CSV file as a table.
+/// Options: `header=false` treats the first row as data, `align=lcr` sets column alignment.
+fn csv_table(
+    directive: &Directive,
+    path: &str,
+    resolver: &dyn ResourceResolver,
+) -> Result<Block, String> {
+    let has_header = directive.option("header") != Some("false");
+    let data = resolver
+        .resolve(path)
+        .map_err(|e| format!("Failed to read CSV {}: {}", path, e))?;
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(data.as_slice());
+
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| format!("Invalid CSV {}: {}", path, e))?;
+        let row: Vec<Vec<Span>> = record
+            .iter()
+            .map(|field| vec![Span::Text(field.to_string())])
+            .collect();
+        rows.push(row);
+    }
+
+    let col_count = rows.iter().map(Vec::len).max().unwrap_or(0);
+    for row in &mut rows {
+        row.resize(col_count, Vec::new());
+    }
+    let headers = if has_header && !rows.is_empty() {
+        rows.remove(0)
+    } else {
+        vec![Vec::new(); col_count]
+    };
+
+    let align = directive
+        .option("align")
+        .unwrap_or("")
+        .chars()
+        .map(|c| match c {
+            'l' => Alignment::Left,
+            'c' => Alignment::Center,
+            'r' => Alignment::Right,
+            _ => Alignment::None,
+        })
+        .collect();
+
+    Ok(Block::Table {
+        headers,
+        rows,
+        footer: 0,
+        align,
+        caption: None,
+        label: None,
+        style: Vec::new(),
+    })
+}
+
+/// Append text, merging with a preceding text span.
+/// pulldown-cmark splits text around characters like `[`, which would hide references.
+/// Text is composed (NFC) here rather than the whole markdown, so code keeps
+/// its exact characters.
+pub(crate) fn push_text(spans: &mut Vec<Span>, text: &str) {
+    if let Some(Span::Text(last)) = spans.last_mut() {
+        last.push_str(text);
+        // A combining mark may come in a piece after the character it's on
+        if !text.is_ascii()
+            && let Cow::Owned(composed) = unicode::nfc(last)
+        {
+            *last = composed;
+        }
+    } else {
+        spans.push(Span::Text(unicode::nfc(text).into_owned()));
+    }
+}
+
+/// Attach a `Table: caption {#tbl:id}` paragraph to the table right before it.
+/// Returns the content back if the paragraph is not a caption.
+fn attach_table_caption(mut content: Vec<Span>, blocks: &mut [Block]) -> Option<Vec<Span>> {
+    let Some(Block::Table { caption, label, .. }) = blocks.last_mut() else {
+        return Some(content);
+    };
+    if caption.is_some() {
+        return Some(content);
+    }
+    let Some(Span::Text(first)) = content.first_mut() else {
+        return Some(content);
+    };
+    let Some(rest) = first.strip_prefix("Table:") else {
+        return Some(content);
+    };
+    *first = rest.trim_start().to_string();
+
+    // Trailing `{#label}` attribute
+    if let Some(Span::Text(last)) = content.last_mut() {
+        let trimmed = last.trim_end();
+        if let Some(start) = trimmed.rfind("{#")
+            && trimmed.ends_with('}')
+        {
+            *label = Some(trimmed[start + 2..trimmed.len() - 1].to_string());
+            *last = trimmed[..start].trim_end().to_string();
+        }
+    }
+
+    *caption = Some(content);
+    None
+}
+
+/// Replace `[@label]` text with reference spans throughout a block.
+fn resolve_references(block: &mut Block) {
+    match block {
+        Block::Heading { content, .. } | Block::Paragraph { content } => {
+            *content = split_references(std::mem::take(content));
+        }
+        Block::List(list) => resolve_list_references(list),
+        Block::Table {
+            headers,
+            rows,
+            caption,
+            ..
+        } => {
+            for cell in headers.iter_mut().chain(rows.iter_mut().flatten()) {
+                *cell = split_references(std::mem::take(cell));
+            }
+            if let Some(caption) = caption {
+                *caption = split_references(std::mem::take(caption));
+            }
+        }
+        Block::CodeBlock { .. }
+        | Block::Image { .. }
+        | Block::Rule
+        | Block::PageBreak
+        | Block::MainMatter
+        | Block::RegionStart(_)
+        | Block::RestartNumbers { .. }
+        | Block::RegionEnd(_)
+        | Block::QrCode { .. }
+        | Block::Diagram { .. } => {}
+    }
+}
+
+fn resolve_list_references(list: &mut List) {
+    for item in &mut list.items {
+        item.content = split_references(std::mem::take(&mut item.content));
+        if let Some(nested) = &mut item.nested {
+            resolve_list_references(nested);
+        }
+    }
+}
+
+fn split_references(spans: Vec<Span>) -> Vec<Span> {
+    let mut out = Vec::new();
+    for span in spans {
+        match span {
+            Span::Text(text) => split_text_sidenotes(&text, &mut out),
+            Span::Bold(inner) => out.push(Span::Bold(split_references(inner))),
+            Span::Italic(inner) => out.push(Span::Italic(split_references(inner))),
+            Span::Link { url, content } => out.push(Span::Link {
+                url,
+                content: split_references(content),
+            }),
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+/// Split `^[margin: text]` sidenotes out of the text, and references out of
+/// the text around them.
+fn split_text_sidenotes(text: &str, out: &mut Vec<Span>) {
+    const START: &str = "^[margin:";
+    let mut rest = text;
+    while let Some(start) = rest.find(START) {
+        let after = &rest[start + START.len()..];
+        let Some(end) = after.find(']') else {
+            break;
+        };
+        split_text_references(&rest[..start], out);
+        out.push(Span::Sidenote(after[..end].trim().to_string()));
+        rest = &after[end + 1..];
+    }
+    split_text_references(rest, out);
+}
+
+fn split_text_references(text: &str, out: &mut Vec<Span>) {
+    let mut rest = text;
+    let mut pending = String::new();
+    while let Some(start) = rest.find("[@") {
+        let after = &rest[start + 2..];
+        let label_len = after
+            .find(|c: char| !is_label_char(c))
+            .unwrap_or(after.len());
+        if label_len > 0 && after[label_len..].starts_with(']') {
+            pending.push_str(&rest[..start]);
+            if !pending.is_empty() {
+                out.push(Span::Text(std::mem::take(&mut pending)));
+            }
+            out.push(Span::Reference(after[..label_len].to_string()));
+            rest = &after[label_len + 1..];
+        } else {
+            pending.push_str(&rest[..start + 2]);
+            rest = after;
+        }
+    }
+    pending.push_str(rest);
+    if !pending.is_empty() {
+        out.push(Span::Text(pending));
+    }
+}
+
+/// The language and title of a code fence's info string, e.g.
+/// `rust title="src/main.rs"`. The title may go without quotes if it has no
+/// spaces; other attributes are ignored.
+fn fence_info(info: &str) -> (Option<String>, Option<String>) {
+    let mut rest = info.trim();
+    let mut language = None;
+    if let Some(word) = rest
+        .split_whitespace()
+        .next()
+        .filter(|word| !word.contains('='))
+    {
+        language = Some(word.to_string());
+        rest = rest[word.len()..].trim_start();
+    }
+    let mut title = None;
+    while !rest.is_empty() {
+        let (key, value) = rest.split_once('=').unwrap_or((rest, ""));
+        let (value, after) = match value.strip_prefix('"') {
+            Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+            None => value.split_once(char::is_whitespace).unwrap_or((value, "")),
+        };
+        if key.trim() == "title" && !value.is_empty() {
+            title = Some(value.to_string());
+        }
+        rest = after.trim_start();
+    }
+    (language, title)
+}
+
+fn convert_alignment(alignment: pulldown_cmark::Alignment) -> Alignment {
+    match alignment {
+        pulldown_cmark::Alignment::None => Alignment::None,
+        pulldown_cmark::Alignment::Left => Alignment::Left,
+        pulldown_cmark::Alignment::Center => Alignment::Center,
+        pulldown_cmark::Alignment::Right => Alignment::Right,
+    }
+}
+
 fn heading_level_to_u8(level: HeadingLevel) -> u8 {
     match level {
         HeadingLevel::H1 => 1,
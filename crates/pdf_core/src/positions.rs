@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+
+use typst_library::layout::{Frame, FrameItem, PagedDocument, Point};
+use typst_syntax::Source;
+
+use crate::typst::SourceMap;
+
+/// Where a block of the markdown source starts in the rendered document.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SourcePosition {
+    /// First markdown line of the block (1-based)
+    pub start_line: usize,
+    /// Last markdown line before the next located block
+    pub end_line: usize,
+    /// 1-based page number
+    pub page: usize,
+    /// Distance from the top of the page to the block's first text, in points
+    pub y: f64,
+}
+
+/// Locate the blocks starting on `lines` in the compiled document by the
+/// markup their text was laid out from.
+///
+/// Text can show up more than once, e.g. a heading in the table of contents or
+/// a running header, so each block takes its last occurrence before the next
+/// block. Blocks without text (images, rules) aren't located; their lines are
+/// covered by the block before them.
+pub fn source_positions(
+    doc: &PagedDocument,
+    source: &Source,
+    source_map: &SourceMap,
+    lines: &[usize],
+    line_count: usize,
+) -> Vec<SourcePosition> {
+    let mut candidates: HashMap<usize, Vec<(usize, f64)>> = HashMap::new();
+    for (i, page) in doc.pages.iter().enumerate() {
+        collect_frame(&page.frame, Point::zero(), &mut |span, y| {
+            let line = source
+                .range(span)
+                .and_then(|range| source_map.line_for_offset(range.start));
+            if let Some(line) = line {
+                candidates.entry(line).or_default().push((i + 1, y));
+            }
+        });
+    }
+    choose_positions(lines, &candidates, line_count)
+}
+
+fn collect_frame(frame: &Frame, offset: Point, found: &mut impl FnMut(typst_syntax::Span, f64)) {
+    for (pos, item) in frame.items() {
+        let pos = offset + *pos;
+        match item {
+            FrameItem::Group(group) => collect_frame(&group.frame, pos, found),
+            FrameItem::Text(text) => {
+                if let Some(glyph) = text.glyphs.first() {
+                    let ascender = text.font.metrics().ascender.at(text.size).to_pt();
+                    found(glyph.span.0, pos.y.to_pt() - ascender);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Pick a (page, y) for each block line, going backwards so that every block
+/// is placed no later than the one after it.
+fn choose_positions(
+    lines: &[usize],
+    candidates: &HashMap<usize, Vec<(usize, f64)>>,
+    line_count: usize,
+) -> Vec<SourcePosition> {
+    let before = |a: &(usize, f64), b: &(usize, f64)| a.0 < b.0 || (a.0 == b.0 && a.1 <= b.1);
+    let mut positions = Vec::new();
+    let mut bound: Option<(usize, f64)> = None;
+    let mut end_line = line_count.max(lines.last().copied().unwrap_or(0));
+    for &line in lines.iter().rev() {
+        let Some(found) = candidates.get(&line) else {
+            continue;
+        };
+        let chosen = found
+            .iter()
+            .filter(|position| bound.is_none_or(|bound| before(position, &bound)))
+            .copied()
+            .reduce(|a, b| if before(&a, &b) { b } else { a });
+        if let Some((page, y)) = chosen {
+            positions.push(SourcePosition {
+                start_line: line,
+                end_line,
+                page,
+                y,
+            });
+            bound = Some((page, y));
+            end_line = line.saturating_sub(1);
+        }
+    }
+    positions.reverse();
+    positions
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{SourcePosition, choose_positions};
+
+    #[test]
+    fn skips_repeated_text_before_the_block() {
+        // The heading on line 1 also appears in a table of contents on page 1
+        let candidates = HashMap::from([
+            (1, vec![(1, 100.0), (2, 72.0)]),
+            (3, vec![(2, 120.0)]),
+            (5, vec![(2, 300.0)]),
+        ]);
+        let positions = choose_positions(&[1, 3, 4, 5], &candidates, 7);
+        assert_eq!(
+            positions,
+            vec![
+                SourcePosition {
+                    start_line: 1,
+                    end_line: 2,
+                    page: 2,
+                    y: 72.0
+                },
+                SourcePosition {
+                    start_line: 3,
+                    end_line: 4,
+                    page: 2,
+                    y: 120.0
+                },
+                SourcePosition {
+                    start_line: 5,
+                    end_line: 7,
+                    page: 2,
+                    y: 300.0
+                },
+            ]
+        );
+    }
+}
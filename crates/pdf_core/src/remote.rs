@@ -0,0 +1,140 @@
+use std::fs;
+use std::path::PathBuf;
+
+use crate::config::ImagesConfig;
+
+/// Whether an image path refers to a remote URL rather than a file.
+pub fn is_remote(path: &str) -> bool {
+    path.starts_with("http://") || path.starts_with("https://")
+}
+
+/// Download a remote image, subject to the `[images]` config, using the on-disk
+/// cache when the URL was fetched before.
+pub fn fetch(url: &str, config: &ImagesConfig) -> Result<Vec<u8>, String> {
+    if !config.allow_remote {
+        return Err("remote images are disabled (set images.allow_remote = true)".to_string());
+    }
+    let host = host_of(url).ok_or_else(|| format!("invalid URL {}", url))?;
+    if !is_allowed(host, &config.allowed_domains) {
+        return Err(format!("{} is not in images.allowed_domains", host));
+    }
+
+    let cache_path = cache_dir(config).map(|dir| dir.join(cache_key(url)));
+    if let Some(path) = &cache_path
+        && let Ok(bytes) = fs::read(path)
+    {
+        return Ok(bytes);
+    }
+
+    let bytes = download(url, config.timeout_secs)?;
+
+    // Caching is best effort: a read-only cache shouldn't fail the render
+    if let Some(path) = &cache_path {
+        if let Some(parent) = path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        let _ = fs::write(path, &bytes);
+    }
+    Ok(bytes)
+}
+
+#[cfg(feature = "remote-images")]
+fn download(url: &str, timeout_secs: u64) -> Result<Vec<u8>, String> {
+    let client = reqwest::blocking::Client::builder()
+        .timeout(std::time::Duration::from_secs(timeout_secs))
+        .build()
+        .map_err(|e| e.to_string())?;
+    let response = client
+        .get(url)
+        .send()
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| e.to_string())?;
+    response
+        .bytes()
+        .map(|bytes| bytes.to_vec())
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(not(feature = "remote-images"))]
+fn download(_url: &str, _timeout_secs: u64) -> Result<Vec<u8>, String> {
+    Err("built without the remote-images feature".to_string())
+}
+
+/// Host name of an http(s) URL, without credentials or port.
+fn host_of(url: &str) -> Option<&str> {
+    let rest = url.split_once("://")?.1;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host = authority.rsplit('@').next()?;
+    let host = host.split(':').next()?;
+    (!host.is_empty()).then_some(host)
+}
+
+fn is_allowed(host: &str, allowed_domains: &[String]) -> bool {
+    allowed_domains.is_empty()
+        || allowed_domains.iter().any(|domain| {
+            host.eq_ignore_ascii_case(domain)
+                || host
+                    .to_ascii_lowercase()
+                    .ends_with(&format!(".{}", domain.to_ascii_lowercase()))
+        })
+}
+
+fn cache_dir(config: &ImagesConfig) -> Option<PathBuf> {
+    if let Some(dir) = &config.cache_dir {
+        return Some(PathBuf::from(dir));
+    }
+    let cache_home = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+    Some(cache_home.join("pdf").join("images"))
+}
+
+/// Stable file name for a URL (64-bit FNV-1a), so cached files survive rebuilds.
+pub fn cache_key(url: &str) -> String {
+    let hash = url.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    });
+    format!("{:016x}", hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fetch, host_of, is_allowed};
+    use crate::config::ImagesConfig;
+
+    #[test]
+    fn host_and_allowlist() {
+        assert_eq!(
+            host_of("https://cdn.example.com/a.png"),
+            Some("cdn.example.com")
+        );
+        assert_eq!(
+            host_of("http://user@example.com:8080?x=1"),
+            Some("example.com")
+        );
+        assert_eq!(host_of("https:///a.png"), None);
+
+        let allowed = vec!["example.com".to_string()];
+        assert!(is_allowed("example.com", &allowed));
+        assert!(is_allowed("CDN.Example.com", &allowed));
+        assert!(!is_allowed("badexample.com", &allowed));
+        assert!(is_allowed("anything.org", &[]));
+    }
+
+    #[test]
+    fn rejected_without_network() {
+        let mut config = ImagesConfig::default();
+        assert!(
+            fetch("https://example.com/a.png", &config)
+                .unwrap_err()
+                .contains("disabled")
+        );
+        config.allow_remote = true;
+        config.allowed_domains = vec!["example.com".to_string()];
+        assert!(
+            fetch("https://evil.test/a.png", &config)
+                .unwrap_err()
+                .contains("allowed_domains")
+        );
+    }
+}
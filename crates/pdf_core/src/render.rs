@@ -0,0 +1,930 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::num::NonZeroUsize;
+use std::ops::{Range, RangeInclusive};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+use rayon::prelude::*;
+use typst_as_lib::TypstAsLibError;
+use typst_library::diag::{Severity as TypstSeverity, SourceDiagnostic};
+use typst_library::foundations::Bytes;
+use typst_library::layout::{PageRanges, PagedDocument};
+use typst_library::text::Font;
+use typst_pdf::PdfOptions;
+use typst_syntax::{FileId, Source, VirtualPath};
+
+use crate::block::Block;
+use crate::cancel::CancellationToken;
+use crate::changes;
+use crate::check;
+use crate::config::{CodeOverflow, Config, LimitExceeded};
+use crate::data_uri;
+use crate::diagnostic::Diagnostic;
+use crate::diagram;
+use crate::engine::{Engine, Unfinished};
+use crate::glyphs;
+use crate::html;
+use crate::outline::{self, OutlineEntry};
+use crate::overflow;
+use crate::parser;
+use crate::positions::{self, SourcePosition};
+use crate::remote;
+use crate::resource::{FsResolver, ResourceResolver};
+use crate::search::{self, SearchMatch};
+use crate::stats::{self, DocStats};
+use crate::svg;
+use crate::text::{self, TextRun};
+use crate::typst::{self, SourceMap};
+use crate::unicode;
+
+// Bundled Open Sans font for sans-serif
+static OPEN_SANS_REGULAR: &[u8] = include_bytes!("../fonts/OpenSans-Regular.ttf");
+static OPEN_SANS_BOLD: &[u8] = include_bytes!("../fonts/OpenSans-Bold.ttf");
+static OPEN_SANS_ITALIC: &[u8] = include_bytes!("../fonts/OpenSans-Italic.ttf");
+static OPEN_SANS_BOLD_ITALIC: &[u8] = include_bytes!("../fonts/OpenSans-BoldItalic.ttf");
+
+/// Path of the generated Typst file. The same file id on every render keeps
+/// Typst's caches valid from one render to the next.
+const MAIN_FILE: &str = "/main.typ";
+
+/// Where [`Renderer::compile_pdf`] puts the PDF it lays out.
+const PDF_FILE: &str = "document.pdf";
+
+/// Bundled Open Sans plus Typst's default fonts, parsed once per process.
+/// Nothing is read from disk, so this also works in the browser (wasm32).
+fn bundled_fonts() -> &'static [Font] {
+    static FONTS: OnceLock<Vec<Font>> = OnceLock::new();
+    FONTS.get_or_init(|| {
+        let fonts: Vec<Font> = [
+            OPEN_SANS_REGULAR,
+            OPEN_SANS_BOLD,
+            OPEN_SANS_ITALIC,
+            OPEN_SANS_BOLD_ITALIC,
+        ]
+        .into_iter()
+        .chain(typst_assets::fonts())
+        .flat_map(|data| Font::iter(Bytes::new(data)))
+        .collect();
+        tracing::info!(faces = fonts.len(), "loaded bundled fonts");
+        fonts
+    })
+}
+
+/// Collect font files below `dir`, recursively.
+fn find_font_files(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_font_files(&path, files)?;
+        } else if path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| {
+                ["ttf", "otf", "ttc", "otc"].contains(&ext.to_ascii_lowercase().as_str())
+            })
+        {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Renders markdown with a fixed config, reusing parsed fonts and Typst's
+/// compilation caches across renders.
+///
+/// Construct once and keep it around (e.g. for live preview or batch conversion)
+/// instead of calling `markdown_to_pdf` repeatedly: after a small edit only the
+/// changed parts of the document are laid out again.
+///
+/// The settings in a document's frontmatter are merged over the config for
+/// that render (see [`Config::merge_frontmatter`]).
+#[derive(Clone)]
+pub struct Renderer {
+    config: Config,
+    engine: Arc<Engine>,
+    resolver: Arc<dyn ResourceResolver>,
+    /// Earlier version of the document to mark changes against
+    previous: Option<Arc<[Block]>>,
+    /// The config already has the frontmatter's settings
+    frontmatter_merged: bool,
+}
+
+impl Renderer {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            engine: Arc::new(Engine::new(bundled_fonts().to_vec(), false)),
+            resolver: Arc::new(FsResolver::default()),
+            previous: None,
+            frontmatter_merged: false,
+        }
+    }
+
+    /// A renderer for a different config that shares this one's fonts, Typst
+    /// setup and resolver, e.g. to vary a setting per document without loading
+    /// fonts again.
+    pub fn with_config(&self, config: Config) -> Self {
+        Self {
+            config,
+            engine: Arc::clone(&self.engine),
+            resolver: Arc::clone(&self.resolver),
+            previous: self.previous.clone(),
+            frontmatter_merged: self.frontmatter_merged,
+        }
+    }
+
+    /// Leave the frontmatter alone because the configs this renderer gets
+    /// already have its settings, with overrides on top that it mustn't undo
+    /// (e.g. `--set` on the command line).
+    pub fn with_frontmatter_merged(mut self) -> Self {
+        self.frontmatter_merged = true;
+        self
+    }
+
+    /// This renderer with `markdown`'s frontmatter settings merged over its config.
+    fn for_document(&self, markdown: &str) -> Result<Cow<'_, Self>, String> {
+        if self.frontmatter_merged || parser::frontmatter(markdown).is_none() {
+            return Ok(Cow::Borrowed(self));
+        }
+        let mut config = self.config.clone();
+        config.merge_frontmatter(markdown)?;
+        Ok(Cow::Owned(
+            self.with_config(config).with_frontmatter_merged(),
+        ))
+    }
+
+    /// Read images, includes and the background image through `resolver`
+    /// instead of from the working directory.
+    pub fn with_resolver(mut self, resolver: impl ResourceResolver + 'static) -> Self {
+        self.resolver = Arc::new(resolver);
+        self
+    }
+
+    /// Draw change bars in the margin next to the blocks added or edited since
+    /// `previous`, an earlier version of the markdown, e.g. for reviewing a
+    /// controlled document.
+    pub fn with_changes_from(mut self, previous: &str) -> Self {
+        let previous = unicode::clean(previous, &self.config);
+        let (blocks, _) = parser::parse_with_resolver(&previous, &*self.resolver);
+        self.previous = Some(blocks.into());
+        self
+    }
+
+    /// Let documents use the fonts installed on the system as well, e.g. one
+    /// picked from a list of them. They're searched for on the first render.
+    #[cfg(feature = "system-fonts")]
+    pub fn with_system_fonts(mut self) -> Self {
+        self.engine = Arc::new(Engine::new(self.engine.fonts().to_vec(), true));
+        self
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Make an extra font (TTF/OTF/TTC bytes) available to documents, e.g. one
+    /// fetched by a browser app that has no filesystem to load fonts from.
+    pub fn add_font(&mut self, data: Vec<u8>) -> Result<(), String> {
+        let faces: Vec<Font> = Font::iter(Bytes::new(data)).collect();
+        if faces.is_empty() {
+            return Err("Font data contains no usable font faces".to_string());
+        }
+        tracing::info!(faces = faces.len(), "added font");
+        self.extend_fonts(faces);
+        Ok(())
+    }
+
+    /// Make every font file (TTF/OTF/TTC/OTC) in `dir` and its subdirectories
+    /// available to documents. Returns the number of font faces added.
+    pub fn add_fonts_dir(&mut self, dir: &Path) -> Result<usize, String> {
+        let mut files = Vec::new();
+        find_font_files(dir, &mut files)
+            .map_err(|e| format!("Reading fonts from {}: {}", dir.display(), e))?;
+        files.sort();
+
+        let mut faces = Vec::new();
+        for path in files {
+            let data =
+                fs::read(&path).map_err(|e| format!("Reading font {}: {}", path.display(), e))?;
+            faces.extend(Font::iter(Bytes::new(data)));
+        }
+        let added = faces.len();
+        if added == 0 {
+            return Err(format!("No usable fonts found in {}", dir.display()));
+        }
+        tracing::info!(faces = added, dir = %dir.display(), "added fonts");
+        self.extend_fonts(faces);
+        Ok(added)
+    }
+
+    /// Set Typst up again with `faces` added, leaving renderers that shared the
+    /// old setup as they were.
+    fn extend_fonts(&mut self, faces: Vec<Font>) {
+        let mut fonts = self.engine.fonts().to_vec();
+        fonts.extend(faces);
+        self.engine = Arc::new(Engine::new(fonts, self.engine.system_fonts()));
+    }
+
+    /// Render markdown to PDF bytes.
+    pub fn render_pdf(&self, markdown: &str) -> Result<Vec<u8>, String> {
+        export_pdf(&self.compile(markdown)?)
+    }
+
+    /// Render markdown to PDF bytes containing only the given pages (1-based, inclusive).
+    pub fn render_pdf_pages(
+        &self,
+        markdown: &str,
+        pages: RangeInclusive<usize>,
+    ) -> Result<Vec<u8>, String> {
+        self.document_to_pdf(&self.compile(markdown)?, Some(pages))
+    }
+
+    /// Render markdown to one PNG image per page at the given resolution.
+    pub fn render_png(&self, markdown: &str, dpi: f32) -> Result<Vec<Vec<u8>>, String> {
+        let doc = self.compile(markdown)?;
+        export_png_range(&doc, 0..doc.pages.len(), dpi)
+    }
+
+    /// Render only the given pages (1-based, inclusive) to PNG images.
+    pub fn render_png_pages(
+        &self,
+        markdown: &str,
+        dpi: f32,
+        pages: RangeInclusive<usize>,
+    ) -> Result<Vec<Vec<u8>>, String> {
+        self.document_to_png(&self.compile(markdown)?, dpi, Some(pages))
+    }
+
+    /// Compile markdown and list its headings with the pages they start on.
+    pub fn outline(&self, markdown: &str) -> Result<Vec<OutlineEntry>, String> {
+        let renderer = self.for_document(markdown)?;
+        let (blocks, lines) = renderer.parse(markdown);
+        let doc = renderer.compile_blocks(&blocks, &lines)?;
+        Ok(outline::document_outline(&blocks, &doc))
+    }
+
+    /// Compile markdown and locate each block on the rendered pages, e.g. to keep
+    /// an editor and a preview scrolled to the same place.
+    pub fn source_positions(&self, markdown: &str) -> Result<Vec<SourcePosition>, String> {
+        let renderer = self.for_document(markdown)?;
+        let (blocks, lines) = renderer.parse(markdown);
+        let compiled = renderer
+            .compile_diagnosed(&blocks, &lines, None)
+            .map_err(|e| e.to_string())?;
+        Ok(positions::source_positions(
+            &compiled.doc,
+            &compiled.source,
+            &compiled.source_map,
+            &lines,
+            markdown.lines().count(),
+        ))
+    }
+
+    /// Compile markdown and find all occurrences of `query` on the rendered pages.
+    pub fn search(&self, markdown: &str, query: &str) -> Result<Vec<SearchMatch>, String> {
+        Ok(search::search(&self.compile(markdown)?, query))
+    }
+
+    /// Document statistics with the real page count from a full layout.
+    pub fn stats(&self, markdown: &str) -> Result<DocStats, String> {
+        let renderer = self.for_document(markdown)?;
+        let (blocks, lines) = renderer.parse(markdown);
+        let doc = renderer.compile_blocks(&blocks, &lines)?;
+        Ok(DocStats {
+            estimated_pages: doc.pages.len(),
+            ..stats::stats(&blocks)
+        })
+    }
+
+    /// [`Self::stats`] and [`Self::outline`] from a single compile, e.g. for an
+    /// editor's status bar and sidebar.
+    pub fn stats_and_outline(
+        &self,
+        markdown: &str,
+    ) -> Result<(DocStats, Vec<OutlineEntry>), String> {
+        let renderer = self.for_document(markdown)?;
+        let (blocks, lines) = renderer.parse(markdown);
+        let doc = renderer.compile_blocks(&blocks, &lines)?;
+        let stats = DocStats {
+            estimated_pages: doc.pages.len(),
+            ..stats::stats(&blocks)
+        };
+        Ok((stats, outline::document_outline(&blocks, &doc)))
+    }
+
+    /// Render markdown to a standalone HTML document.
+    pub fn render_html(&self, markdown: &str) -> String {
+        // Invalid frontmatter is left for `check` to report
+        let renderer = self.for_document(markdown).unwrap_or(Cow::Borrowed(self));
+        html::blocks_to_html(&renderer.parse(markdown).0, &renderer.config)
+    }
+
+    /// Render markdown to PDF bytes, giving up early once `cancel` is triggered.
+    pub fn render_pdf_cancellable(
+        &self,
+        markdown: &str,
+        cancel: &CancellationToken,
+    ) -> Result<Vec<u8>, String> {
+        let doc = self.compile_cancellable(markdown, cancel)?;
+        cancel.check()?;
+        export_pdf(&doc)
+    }
+
+    /// Render markdown to SVG pages.
+    pub fn render_svg(&self, markdown: &str) -> Result<SvgDocument, String> {
+        export_svg(&self.compile(markdown)?)
+    }
+
+    /// Render markdown to SVG pages, giving up early once `cancel` is triggered.
+    pub fn render_svg_cancellable(
+        &self,
+        markdown: &str,
+        cancel: &CancellationToken,
+    ) -> Result<SvgDocument, String> {
+        let doc = self.compile_cancellable(markdown, cancel)?;
+        export_svg_range(&doc, 0..doc.pages.len(), Some(cancel))
+    }
+
+    /// Render only the given pages (1-based, inclusive) to SVG.
+    /// The whole document is still laid out, but only these pages are serialized.
+    pub fn render_svg_pages(
+        &self,
+        markdown: &str,
+        pages: RangeInclusive<usize>,
+    ) -> Result<SvgDocument, String> {
+        self.document_to_svg(&self.compile(markdown)?, Some(pages))
+    }
+
+    /// Render markdown to the Typst markup that is compiled for the PDF,
+    /// e.g. to debug layout issues.
+    pub fn render_typst(&self, markdown: &str) -> String {
+        let renderer = self.for_document(markdown).unwrap_or(Cow::Borrowed(self));
+        typst::blocks_to_typst(&renderer.parse(markdown).0, &renderer.config)
+    }
+
+    /// Export a document from [`Renderer::compile`] to PDF, optionally only the
+    /// given pages (1-based, inclusive). Compiling once and exporting several
+    /// times avoids laying out the document again for each format.
+    pub fn document_to_pdf(
+        &self,
+        doc: &PagedDocument,
+        pages: Option<RangeInclusive<usize>>,
+    ) -> Result<Vec<u8>, String> {
+        let Some(pages) = pages else {
+            return export_pdf(doc);
+        };
+        let selected = select_pages(doc.pages.len(), &pages)?;
+        let options = PdfOptions {
+            page_ranges: Some(PageRanges::new(vec![
+                NonZeroUsize::new(selected.start + 1)..=NonZeroUsize::new(selected.end),
+            ])),
+            ..pdf_options()
+        };
+        let _span = tracing::info_span!("export", format = "pdf").entered();
+        typst_pdf::pdf(doc, &options).map_err(|e| format!("PDF generation failed: {:?}", e))
+    }
+
+    /// Export a compiled document to one PNG image per page.
+    pub fn document_to_png(
+        &self,
+        doc: &PagedDocument,
+        dpi: f32,
+        pages: Option<RangeInclusive<usize>>,
+    ) -> Result<Vec<Vec<u8>>, String> {
+        export_png_range(doc, page_indices(doc, pages)?, dpi)
+    }
+
+    /// Export a compiled document to SVG pages.
+    pub fn document_to_svg(
+        &self,
+        doc: &PagedDocument,
+        pages: Option<RangeInclusive<usize>>,
+    ) -> Result<SvgDocument, String> {
+        export_svg_range(doc, page_indices(doc, pages)?, None)
+    }
+
+    /// Compile markdown to a laid-out Typst document, e.g. for [`crate::document_text`].
+    pub fn compile(&self, markdown: &str) -> Result<PagedDocument, String> {
+        self.config
+            .limits
+            .check_input(markdown)
+            .map_err(|e| e.to_string())?;
+        let renderer = self.for_document(markdown)?;
+        let (blocks, lines) = renderer.parse(markdown);
+        renderer.compile_blocks(&blocks, &lines)
+    }
+
+    /// Lay out the first `pages` pages of an existing PDF as they are, e.g. to
+    /// compare a new version with it in [`crate::diff_documents`].
+    pub fn compile_pdf(&self, pdf: Vec<u8>, pages: usize) -> Result<PagedDocument, String> {
+        // Each page comes in as an image of its own size
+        let markup = format!(
+            "#set page(width: auto, height: auto, margin: 0)\n\
+             #for n in range(1, {}) {{ pagebreak(weak: true); image(\"{}\", page: n) }}\n",
+            pages + 1,
+            PDF_FILE,
+        );
+        let main = Source::new(FileId::new(None, VirtualPath::new(MAIN_FILE)), markup);
+        let limit = self.config.limits.max_compile_ms;
+        let compiled = self
+            .engine
+            .compile(
+                main,
+                vec![(PDF_FILE.to_string(), pdf)],
+                None,
+                limit.map(Duration::from_millis),
+            )
+            .map_err(|unfinished| match unfinished {
+                Unfinished::TimedOut => format!(
+                    "Reading the PDF took more than {} ms",
+                    limit.unwrap_or_default()
+                ),
+                Unfinished::Runaway(running) => LimitExceeded::Runaway { running }.to_string(),
+            })?;
+        compiled
+            .output
+            .map_err(|e| format!("Reading the PDF failed: {:?}", e))
+    }
+
+    fn compile_cancellable(
+        &self,
+        markdown: &str,
+        cancel: &CancellationToken,
+    ) -> Result<PagedDocument, String> {
+        cancel.check()?;
+        self.config
+            .limits
+            .check_input(markdown)
+            .map_err(|e| e.to_string())?;
+        let renderer = self.for_document(markdown)?;
+        let (blocks, lines) = renderer.parse(markdown);
+        cancel.check()?;
+        renderer.compile_checked(&blocks, &lines, Some(cancel))
+    }
+
+    fn parse(&self, markdown: &str) -> (Vec<Block>, Vec<usize>) {
+        let _span = tracing::info_span!("parse", bytes = markdown.len()).entered();
+        let markdown = unicode::clean(markdown, &self.config);
+        let (blocks, lines) = parser::parse_with_resolver(&markdown, &*self.resolver);
+        let (blocks, lines) = match &self.previous {
+            Some(previous) => changes::mark_changes(previous, blocks, lines),
+            None => (blocks, lines),
+        };
+        // Graphviz runs here rather than while generating the markup, and only
+        // for diagrams it hasn't rendered before
+        let blocks = tracing::info_span!("diagrams")
+            .in_scope(|| diagram::render_diagrams(blocks, &self.config.diagrams));
+        (blocks, lines)
+    }
+
+    /// Compile blocks to a Typst document.
+    /// `lines` holds each block's markdown source line, used to locate errors (may be empty).
+    pub(crate) fn compile_blocks(
+        &self,
+        blocks: &[Block],
+        lines: &[usize],
+    ) -> Result<PagedDocument, String> {
+        self.compile_checked(blocks, lines, None)
+    }
+
+    /// Compile markdown, collecting errors and warnings instead of failing on the
+    /// first one, e.g. for editors or CI checks that report every problem.
+    pub fn compile_report(&self, markdown: &str) -> CompileReport {
+        let result = self
+            .config
+            .limits
+            .check_input(markdown)
+            .map_err(CompileError::from)
+            .and_then(|()| self.for_document(markdown).map_err(CompileError::Other))
+            .and_then(|renderer| {
+                let (blocks, lines) = renderer.parse(markdown);
+                renderer.compile_diagnosed(&blocks, &lines, None)
+            });
+        match result {
+            Ok(compiled) => CompileReport {
+                document: Some(compiled.doc),
+                diagnostics: compiled.warnings,
+            },
+            Err(error) => CompileReport {
+                document: None,
+                diagnostics: error.into_diagnostics(),
+            },
+        }
+    }
+
+    /// Check markdown without exporting anything: broken internal links and
+    /// references, unreadable images and unsupported markdown, then the Typst
+    /// compile itself unless those already found errors.
+    pub fn check(&self, markdown: &str) -> Vec<Diagnostic> {
+        if let Err(e) = self.config.limits.check_input(markdown) {
+            return vec![Diagnostic::error(e.to_string())];
+        }
+        let renderer = match self.for_document(markdown) {
+            Ok(renderer) => renderer,
+            Err(e) => return vec![Diagnostic::error(e)],
+        };
+        let (blocks, lines) = renderer.parse(markdown);
+        let mut diagnostics = check::check_blocks(
+            markdown,
+            &blocks,
+            &lines,
+            &renderer.config,
+            &*renderer.resolver,
+        );
+        if !diagnostics.iter().any(Diagnostic::is_error) {
+            match renderer.compile_diagnosed(&blocks, &lines, None) {
+                Ok(compiled) => diagnostics.extend(compiled.warnings),
+                Err(error) => diagnostics.extend(error.into_diagnostics()),
+            }
+        }
+        diagnostics
+    }
+
+    fn compile_checked(
+        &self,
+        blocks: &[Block],
+        lines: &[usize],
+        cancel: Option<&CancellationToken>,
+    ) -> Result<PagedDocument, String> {
+        self.compile_diagnosed(blocks, lines, cancel)
+            .map(|compiled| compiled.doc)
+            .map_err(|e| e.to_string())
+    }
+
+    /// Compile blocks, returning the document with Typst's warnings.
+    fn compile_diagnosed(
+        &self,
+        blocks: &[Block],
+        lines: &[usize],
+        cancel: Option<&CancellationToken>,
+    ) -> Result<Compiled, CompileError> {
+        let limits = &self.config.limits;
+        // Only read the clock when needed: `Instant` is unavailable on wasm32
+        let started = limits.max_compile_ms.map(|_| Instant::now());
+        let (typst_content, source_map) = tracing::info_span!("typst-gen", blocks = blocks.len())
+            .in_scope(|| typst::blocks_to_typst_mapped(blocks, lines, &self.config));
+        let files = tracing::info_span!("resources")
+            .in_scope(|| load_files(&self.config, blocks, &*self.resolver))?;
+        if let Some(cancel) = cancel {
+            cancel.check()?;
+        }
+
+        // Spans in diagnostics and the laid-out pages only resolve against the
+        // very source Typst compiled, so it's made here and kept
+        let source = Source::new(
+            FileId::new(None, VirtualPath::new(MAIN_FILE)),
+            typst_content,
+        );
+
+        // Whatever is left of the time limit after generating and loading
+        let timeout = limits
+            .max_compile_ms
+            .zip(started)
+            .map(|(limit, started)| Duration::from_millis(limit).saturating_sub(started.elapsed()));
+        let compiled = tracing::info_span!("compile")
+            .in_scope(|| self.engine.compile(source.clone(), files, cancel, timeout))
+            .map_err(|unfinished| match unfinished {
+                Unfinished::TimedOut => LimitExceeded::CompileMs {
+                    actual: started.map_or(0, |started| started.elapsed().as_millis() as u64),
+                    limit: limits.max_compile_ms.unwrap_or_default(),
+                },
+                Unfinished::Runaway(running) => LimitExceeded::Runaway { running },
+            })?;
+        // Typst reports the files it couldn't read then, not the cancellation
+        if let Some(cancel) = cancel {
+            cancel.check()?;
+        }
+        let mut warnings = locate_diagnostics(&compiled.warnings, &source, &source_map);
+        let doc = compiled.output.map_err(|e| match e {
+            TypstAsLibError::TypstSource(errors) => {
+                CompileError::Typst(locate_diagnostics(&errors, &source, &source_map))
+            }
+            other => CompileError::Other(format!("Typst compilation failed: {:?}", other)),
+        })?;
+        tracing::info!(pages = doc.pages.len(), "compiled document");
+        warnings.extend(glyphs::missing_glyphs(&doc, &source, &source_map));
+        if self.config.code.overflow == Some(CodeOverflow::ScrollNote) {
+            warnings.extend(overflow::overflowing_code(&doc, &source, &source_map));
+        }
+
+        if let Some(started) = started {
+            limits.check_compile_ms(started.elapsed().as_millis() as u64)?;
+        }
+        limits.check_pages(doc.pages.len())?;
+        Ok(Compiled {
+            doc,
+            warnings,
+            source,
+            source_map,
+        })
+    }
+}
+
+/// A compiled document with the Typst source it was laid out from, which
+/// spans in it resolve against.
+struct Compiled {
+    doc: PagedDocument,
+    warnings: Vec<Diagnostic>,
+    source: Source,
+    source_map: SourceMap,
+}
+
+/// Outcome of [`Renderer::compile_report`].
+pub struct CompileReport {
+    /// The laid-out document, or `None` if compilation failed
+    pub document: Option<PagedDocument>,
+    /// Errors, or the warnings of a successful compilation
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+enum CompileError {
+    /// Errors reported by Typst, located in the markdown source
+    Typst(Vec<Diagnostic>),
+    /// A limit from the `[limits]` config
+    Limit(LimitExceeded),
+    /// Missing resources and other failures before or after compiling
+    Other(String),
+}
+
+impl CompileError {
+    fn into_diagnostics(self) -> Vec<Diagnostic> {
+        match self {
+            CompileError::Typst(diagnostics) => diagnostics,
+            CompileError::Limit(limit) => vec![Diagnostic::error(limit.to_string())],
+            CompileError::Other(message) => vec![Diagnostic::error(message)],
+        }
+    }
+}
+
+impl From<LimitExceeded> for CompileError {
+    fn from(limit: LimitExceeded) -> Self {
+        CompileError::Limit(limit)
+    }
+}
+
+impl From<String> for CompileError {
+    fn from(message: String) -> Self {
+        CompileError::Other(message)
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompileError::Typst(diagnostics) => {
+                let messages: Vec<String> = diagnostics.iter().map(ToString::to_string).collect();
+                write!(f, "Typst compilation failed: {}", messages.join("; "))
+            }
+            CompileError::Limit(limit) => write!(f, "{}", limit),
+            CompileError::Other(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+/// Convert Typst diagnostics to markdown source lines where possible.
+fn locate_diagnostics(
+    diagnostics: &[SourceDiagnostic],
+    source: &Source,
+    source_map: &SourceMap,
+) -> Vec<Diagnostic> {
+    diagnostics
+        .iter()
+        .map(|diagnostic| {
+            let line = source
+                .range(diagnostic.span)
+                .and_then(|range| source_map.line_for_offset(range.start));
+            let located = match diagnostic.severity {
+                TypstSeverity::Error => Diagnostic::error(diagnostic.message.as_str()),
+                TypstSeverity::Warning => Diagnostic::warning(diagnostic.message.as_str()),
+            };
+            located.at_line(line)
+        })
+        .collect()
+}
+
+/// Resolve files referenced by the config and the document so Typst can access them by path.
+fn load_files(
+    config: &Config,
+    blocks: &[Block],
+    resolver: &dyn ResourceResolver,
+) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let mut files: Vec<(String, Vec<u8>)> = Vec::new();
+    if let Some(path) = &config.page.background_image {
+        let bytes = resolver
+            .resolve(path)
+            .map_err(|e| format!("Failed to read background image {}: {}", path, e))?;
+        files.push((path.clone(), bytes));
+    }
+    for block in blocks {
+        let Block::Image { path, .. } = block else {
+            continue;
+        };
+        let name = if data_uri::is_data_uri(path) {
+            data_uri::file_name(path)
+        } else {
+            path.clone()
+        };
+        if !files.iter().any(|(existing, _)| *existing == name) {
+            let bytes = if data_uri::is_data_uri(path) {
+                data_uri::decode(path)
+            } else if remote::is_remote(path) {
+                remote::fetch(path, &config.images)
+            } else {
+                resolver.resolve(path)
+            }
+            .map_err(|e| format!("Failed to read image {}: {}", name, e))?;
+            // SVGs stay vector; only their font lists need a usable fallback
+            let bytes = if svg::is_svg(&bytes) {
+                svg::with_font_fallback(&String::from_utf8_lossy(&bytes)).into_bytes()
+            } else {
+                bytes
+            };
+            files.push((name, bytes));
+        }
+    }
+    Ok(files)
+}
+
+pub(crate) fn export_pdf(doc: &PagedDocument) -> Result<Vec<u8>, String> {
+    let _span = tracing::info_span!("export", format = "pdf").entered();
+    typst_pdf::pdf(doc, &pdf_options()).map_err(|e| format!("PDF generation failed: {:?}", e))
+}
+
+/// Always write a structure tree, so headings, tables and image alt text are
+/// available to screen readers and accessibility checkers.
+fn pdf_options() -> PdfOptions<'static> {
+    PdfOptions {
+        tagged: true,
+        ..PdfOptions::default()
+    }
+}
+
+/// Result of rendering markdown to SVG pages.
+pub struct SvgDocument {
+    pub pages: Vec<String>,
+    /// Text runs of each page, in the same order as `pages`. The SVGs draw
+    /// glyphs as shapes, so this is what a viewer can make selectable.
+    pub text: Vec<Vec<TextRun>>,
+    pub width_pt: f64,
+    pub height_pt: f64,
+}
+
+pub(crate) fn export_svg(doc: &PagedDocument) -> Result<SvgDocument, String> {
+    export_svg_range(doc, 0..doc.pages.len(), None)
+}
+
+fn export_svg_range(
+    doc: &PagedDocument,
+    range: Range<usize>,
+    cancel: Option<&CancellationToken>,
+) -> Result<SvgDocument, String> {
+    let _span = tracing::info_span!("export", format = "svg", pages = range.len()).entered();
+    // Pages serialize independently; collect keeps them in document order
+    let (pages, text) = doc.pages[range]
+        .par_iter()
+        .map(|page| {
+            if let Some(cancel) = cancel {
+                cancel.check()?;
+            }
+            Ok((typst_svg::svg(page), text::text_runs(&page.frame)))
+        })
+        .collect::<Result<(Vec<String>, Vec<Vec<TextRun>>), String>>()?;
+
+    // Get dimensions from first page (assuming all pages same size)
+    let (width_pt, height_pt) = if let Some(first_page) = doc.pages.first() {
+        let size = first_page.frame.size();
+        (size.x.to_pt(), size.y.to_pt())
+    } else {
+        (595.0, 842.0) // A4 default
+    };
+
+    Ok(SvgDocument {
+        pages,
+        text,
+        width_pt,
+        height_pt,
+    })
+}
+
+fn export_png_range(
+    doc: &PagedDocument,
+    range: Range<usize>,
+    dpi: f32,
+) -> Result<Vec<Vec<u8>>, String> {
+    let _span = tracing::info_span!("export", format = "png", pages = range.len()).entered();
+    let pixel_per_pt = dpi / 72.0;
+    doc.pages[range]
+        .par_iter()
+        .map(|page| {
+            typst_render::render(page, pixel_per_pt)
+                .encode_png()
+                .map_err(|e| format!("PNG encoding failed: {}", e))
+        })
+        .collect()
+}
+
+/// Indices of the requested pages, or of all pages.
+fn page_indices(
+    doc: &PagedDocument,
+    pages: Option<RangeInclusive<usize>>,
+) -> Result<Range<usize>, String> {
+    match pages {
+        Some(pages) => select_pages(doc.pages.len(), &pages),
+        None => Ok(0..doc.pages.len()),
+    }
+}
+
+/// Turn a 1-based inclusive page range into indices into the page list.
+/// The end is clamped to the last page; a range starting past it is an error.
+fn select_pages(page_count: usize, pages: &RangeInclusive<usize>) -> Result<Range<usize>, String> {
+    let (start, end) = (*pages.start(), *pages.end());
+    if start == 0 || start > end {
+        return Err(format!("Invalid page range {}-{}", start, end));
+    }
+    if start > page_count {
+        return Err(format!(
+            "Page {} is out of range (document has {} pages)",
+            start, page_count
+        ));
+    }
+    Ok(start - 1..end.min(page_count))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CompileError, Renderer, select_pages};
+    use crate::config::Config;
+    use crate::diagnostic::Diagnostic;
+    use std::ops::RangeInclusive;
+
+    #[test]
+    fn typst_errors_have_markdown_lines() {
+        // The size reaches Typst as is, where `big` is an unknown variable
+        let renderer = Renderer::new(Config::compiled_default());
+        let diagnostics = renderer.check("Text\n\n@qrcode(https://example.com, size=big)\n");
+        assert!(
+            diagnostics
+                .iter()
+                .any(|diagnostic| diagnostic.is_error() && diagnostic.line == Some(3)),
+            "{:?}",
+            diagnostics
+        );
+    }
+
+    #[test]
+    fn outline_with_toc() {
+        // The table of contents' title is a heading too, but not one of ours
+        let mut config = Config::compiled_default();
+        config.document.toc = true;
+        let outline = Renderer::new(config)
+            .outline("# One\n\nText\n\n## Two\n")
+            .unwrap();
+        let titles: Vec<_> = outline
+            .iter()
+            .map(|entry| (entry.level, entry.title.as_str()))
+            .collect();
+        assert_eq!(titles, [(1, "One"), (2, "Two")]);
+        assert_eq!(outline[0].page, 2);
+    }
+
+    #[test]
+    fn frontmatter_settings() {
+        let markdown =
+            "---\npdf:\n  links:\n    color: \"#123456\"\n---\n\n[a](https://example.com)\n";
+        let renderer = Renderer::new(Config::compiled_default());
+        assert!(renderer.render_typst(markdown).contains("#123456"));
+        // Unless the config has them already, with overrides on top
+        let merged = renderer.with_frontmatter_merged();
+        assert!(!merged.render_typst(markdown).contains("#123456"));
+    }
+
+    #[test]
+    fn compile_error_message() {
+        let error = CompileError::Typst(vec![
+            Diagnostic::error("unknown variable").at_line(Some(3)),
+            Diagnostic::error("expected expression"),
+        ]);
+        assert_eq!(
+            error.to_string(),
+            "Typst compilation failed: line 3: unknown variable; expected expression"
+        );
+        assert_eq!(
+            CompileError::Other("too big".to_string()).into_diagnostics(),
+            vec![Diagnostic::error("too big")]
+        );
+    }
+
+    #[test]
+    fn page_range_selection() {
+        assert_eq!(select_pages(10, &(3..=7)), Ok(2..7));
+        assert_eq!(select_pages(10, &(8..=20)), Ok(7..10));
+        assert_eq!(select_pages(1, &(1..=1)), Ok(0..1));
+        assert!(select_pages(5, &(6..=7)).is_err());
+        assert!(select_pages(5, &(0..=2)).is_err());
+        assert!(select_pages(5, &RangeInclusive::new(4, 2)).is_err());
+    }
+}
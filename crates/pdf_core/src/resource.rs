@@ -0,0 +1,117 @@
+use std::path::{Component, Path, PathBuf};
+
+/// Supplies the bytes of files a document refers to: images, CSV includes and
+/// the configured background image.
+///
+/// Implement this to serve resources from memory, a database or a virtual
+/// filesystem instead of the local disk, and pass it to
+/// [`crate::Renderer::with_resolver`].
+pub trait ResourceResolver: Send + Sync {
+    /// Return the contents of `path` exactly as written in the document or config.
+    fn resolve(&self, path: &str) -> Result<Vec<u8>, String>;
+}
+
+/// Reads resources from the local filesystem, relative to a root directory.
+///
+/// Only files below the root are read: absolute paths, `..` and symlinks
+/// leading out of it are refused, so a document can't read just any file.
+#[derive(Debug, Clone)]
+pub struct FsResolver {
+    root: PathBuf,
+    /// Also read files outside the root
+    trusted: bool,
+}
+
+impl FsResolver {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            trusted: false,
+        }
+    }
+
+    /// Also read absolute paths and files outside the root, for documents from
+    /// a trusted source, e.g. the user's own files on the command line.
+    pub fn trusted(mut self) -> Self {
+        self.trusted = true;
+        self
+    }
+
+    /// `path` below the root, or why it isn't.
+    fn contained(&self, path: &str) -> Result<PathBuf, String> {
+        let outside = || format!("{} is outside {}", path, self.root.display());
+        if Path::new(path)
+            .components()
+            .any(|component| !matches!(component, Component::Normal(_) | Component::CurDir))
+        {
+            return Err(outside());
+        }
+        let full = self.root.join(path);
+        // Symlinks may still lead elsewhere
+        let root = match self.root.as_os_str().is_empty() {
+            true => Path::new("."),
+            false => &self.root,
+        };
+        let root = root.canonicalize().map_err(|e| e.to_string())?;
+        if !full
+            .canonicalize()
+            .map_err(|e| e.to_string())?
+            .starts_with(root)
+        {
+            return Err(outside());
+        }
+        Ok(full)
+    }
+}
+
+impl Default for FsResolver {
+    /// Resolve relative paths against the working directory.
+    fn default() -> Self {
+        Self::new(".")
+    }
+}
+
+impl ResourceResolver for FsResolver {
+    fn resolve(&self, path: &str) -> Result<Vec<u8>, String> {
+        let full = if self.trusted {
+            self.root.join(path)
+        } else {
+            self.contained(path)?
+        };
+        std::fs::read(full).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FsResolver, ResourceResolver};
+
+    #[test]
+    fn stays_below_the_root() {
+        let dir = std::env::temp_dir().join("pdf_core_fs_resolver");
+        let _ = std::fs::remove_dir_all(&dir);
+        let root = dir.join("docs");
+        std::fs::create_dir_all(root.join("images")).unwrap();
+        std::fs::write(root.join("images").join("logo.png"), "logo").unwrap();
+        std::fs::write(dir.join("secret.txt"), "secret").unwrap();
+
+        let resolver = FsResolver::new(&root);
+        assert_eq!(resolver.resolve("images/logo.png").unwrap(), b"logo");
+        assert_eq!(resolver.resolve("./images/logo.png").unwrap(), b"logo");
+        assert!(resolver.resolve("../secret.txt").is_err());
+        assert!(resolver.resolve("images/../../secret.txt").is_err());
+        let absolute = dir.join("secret.txt");
+        assert!(resolver.resolve(absolute.to_str().unwrap()).is_err());
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(dir.join("secret.txt"), root.join("link.txt")).unwrap();
+            assert!(resolver.resolve("link.txt").is_err());
+        }
+
+        let trusted = FsResolver::new(&root).trusted();
+        assert_eq!(trusted.resolve("../secret.txt").unwrap(), b"secret");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,118 @@
+use std::ops::Range;
+
+use typst_library::layout::{Frame, FrameItem, PagedDocument, Point};
+use typst_library::text::TextItem;
+
+/// Page-relative rectangle in points, measured from the top-left corner.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// One occurrence of a search query in the rendered document.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SearchMatch {
+    /// 1-based page number
+    pub page: usize,
+    pub bbox: BoundingBox,
+}
+
+/// Find all occurrences of `query` (ASCII case-insensitive) in the laid-out text.
+/// Matches are found within a single run of text, so a query spanning a line
+/// break or a change of style is not found.
+pub fn search(doc: &PagedDocument, query: &str) -> Vec<SearchMatch> {
+    let mut matches = Vec::new();
+    if query.is_empty() {
+        return matches;
+    }
+    let query = query.to_ascii_lowercase();
+    for (i, page) in doc.pages.iter().enumerate() {
+        search_frame(&page.frame, Point::zero(), &query, i + 1, &mut matches);
+    }
+    matches
+}
+
+fn search_frame(
+    frame: &Frame,
+    offset: Point,
+    query: &str,
+    page: usize,
+    matches: &mut Vec<SearchMatch>,
+) {
+    for (pos, item) in frame.items() {
+        let pos = offset + *pos;
+        match item {
+            FrameItem::Group(group) => search_frame(&group.frame, pos, query, page, matches),
+            FrameItem::Text(text) => search_text(text, pos, query, page, matches),
+            _ => {}
+        }
+    }
+}
+
+fn search_text(
+    text: &TextItem,
+    pos: Point,
+    query: &str,
+    page: usize,
+    matches: &mut Vec<SearchMatch>,
+) {
+    // ASCII lowercasing keeps byte offsets in line with the glyph ranges
+    let haystack = text.text.to_ascii_lowercase();
+    let metrics = text.font.metrics();
+    let ascender = metrics.ascender.at(text.size).to_pt();
+    let descender = metrics.descender.at(text.size).to_pt();
+    let glyphs: Vec<(Range<usize>, f64)> = text
+        .glyphs
+        .iter()
+        .map(|glyph| (glyph.range(), glyph.x_advance.at(text.size).to_pt()))
+        .collect();
+
+    for (start, found) in haystack.match_indices(query) {
+        let Some((x, width)) = glyph_extent(&glyphs, &(start..start + found.len())) else {
+            continue;
+        };
+        matches.push(SearchMatch {
+            page,
+            bbox: BoundingBox {
+                x: pos.x.to_pt() + x,
+                y: pos.y.to_pt() - ascender,
+                width,
+                height: ascender - descender,
+            },
+        });
+    }
+}
+
+/// Horizontal offset and width covered by the glyphs for a byte range of the text.
+/// `glyphs` holds each glyph's text range and advance, in visual order.
+fn glyph_extent(glyphs: &[(Range<usize>, f64)], range: &Range<usize>) -> Option<(f64, f64)> {
+    let mut x = 0.0;
+    let mut extent: Option<(f64, f64)> = None;
+    for (glyph_range, advance) in glyphs {
+        if glyph_range.start < range.end && range.start < glyph_range.end {
+            extent = Some(match extent {
+                Some((start, _)) => (start, x + advance),
+                None => (x, x + advance),
+            });
+        }
+        x += advance;
+    }
+    extent.map(|(start, end)| (start, end - start))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::glyph_extent;
+
+    #[test]
+    fn glyph_extent_covers_matched_bytes() {
+        // "abcd" with one glyph per byte, then a ligature spanning two bytes
+        let glyphs = [(0..1, 5.0), (1..2, 6.0), (2..3, 7.0), (3..5, 8.0)];
+        assert_eq!(glyph_extent(&glyphs, &(1..3)), Some((5.0, 13.0)));
+        assert_eq!(glyph_extent(&glyphs, &(4..5)), Some((18.0, 8.0)));
+        assert_eq!(glyph_extent(&glyphs, &(5..6)), None);
+    }
+}
@@ -0,0 +1,116 @@
+use crate::block::{Block, List};
+use crate::typst::collect_span_text;
+
+/// Average silent reading speed used for reading-time estimates
+const WORDS_PER_MINUTE: usize = 200;
+
+/// Approximate lines of body text that fit on an A4 page
+const LINES_PER_PAGE: usize = 45;
+
+/// Counts for status bars and editorial workflows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DocStats {
+    pub words: usize,
+    /// Characters of text content, including spaces but not line breaks
+    pub characters: usize,
+    pub headings: usize,
+    pub code_blocks: usize,
+    /// Estimated from the text length, or the real page count when
+    /// computed with [`crate::Renderer::stats`]
+    pub estimated_pages: usize,
+}
+
+impl DocStats {
+    /// Estimated reading time, rounded up to whole minutes.
+    pub fn reading_minutes(&self) -> usize {
+        self.words.div_ceil(WORDS_PER_MINUTE)
+    }
+}
+
+/// Compute document statistics from the block AST without laying it out.
+pub fn stats(blocks: &[Block]) -> DocStats {
+    let mut stats = DocStats::default();
+    let mut lines = 0;
+    let mut full_pages = 0;
+
+    for block in blocks {
+        let mut text = String::new();
+        match block {
+            Block::Heading { content, .. } => {
+                stats.headings += 1;
+                collect_span_text(content, &mut text);
+                lines += 2;
+            }
+            Block::Paragraph { content } => {
+                collect_span_text(content, &mut text);
+                lines += text.len() / 80 + 1;
+            }
+            Block::CodeBlock { content, .. } => {
+                stats.code_blocks += 1;
+                text.push_str(content);
+                lines += content.lines().count() + 1;
+            }
+            Block::List(list) => lines += list_text(list, &mut text) + 1,
+            Block::Table {
+                headers,
+                rows,
+                caption,
+                ..
+            } => {
+                for cell in headers.iter().chain(rows.iter().flatten()) {
+                    collect_span_text(cell, &mut text);
+                    text.push(' ');
+                }
+                if let Some(caption) = caption {
+                    collect_span_text(caption, &mut text);
+                }
+                lines += rows.len() + 2;
+            }
+            Block::Image { .. } => lines += 15,
+            Block::Rule => lines += 1,
+            Block::PageBreak | Block::MainMatter => {
+                full_pages += lines.div_ceil(LINES_PER_PAGE).max(1);
+                lines = 0;
+            }
+            Block::QrCode { .. } => lines += 5,
+            Block::Diagram { .. } => lines += 15,
+            Block::RegionStart(_) | Block::RegionEnd(_) | Block::RestartNumbers { .. } => {}
+        }
+        stats.words += text.split_whitespace().count();
+        stats.characters += text.lines().map(|line| line.chars().count()).sum::<usize>();
+    }
+
+    stats.estimated_pages = full_pages + lines.div_ceil(LINES_PER_PAGE).max(1);
+    stats
+}
+
+/// Collect list item text (one item per line) and return the number of items.
+fn list_text(list: &List, out: &mut String) -> usize {
+    let mut count = 0;
+    for item in &list.items {
+        collect_span_text(&item.content, out);
+        out.push('\n');
+        count += 1;
+        if let Some(ref nested) = item.nested {
+            count += list_text(nested, out);
+        }
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stats;
+
+    #[test]
+    fn counts_from_ast() {
+        let markdown = "# Title\n\nTwo words.\n\n- one\n- two three\n\n```rust\nlet x = 1;\n```\n\n---pagebreak---\n\n## Next";
+        let result = stats(markdown);
+        assert_eq!(result.words, 11);
+        assert_eq!(result.characters, 5 + 10 + 3 + 9 + 10 + 4);
+        assert_eq!(result.headings, 2);
+        assert_eq!(result.code_blocks, 1);
+        assert_eq!(result.estimated_pages, 2);
+        assert_eq!(result.reading_minutes(), 1);
+    }
+}
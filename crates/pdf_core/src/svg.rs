@@ -0,0 +1,99 @@
+/// Font appended to every SVG font list; bundled, so it is always available.
+const FALLBACK_FONT: &str = "Open Sans";
+
+/// Whether image bytes are an SVG document (possibly after an XML prolog or comments).
+pub fn is_svg(data: &[u8]) -> bool {
+    let head = &data[..data.len().min(1024)];
+    String::from_utf8_lossy(head).contains("<svg")
+}
+
+/// Append the bundled font to each `font-family` in an SVG, so text set in fonts
+/// that aren't available (e.g. Excalidraw's Virgil, or webfonts loaded through
+/// `@font-face`) still renders instead of disappearing.
+pub fn with_font_fallback(svg: &str) -> String {
+    const PROPERTY: &str = "font-family";
+    let mut out = String::with_capacity(svg.len());
+    let mut rest = svg;
+
+    while let Some(pos) = rest.find(PROPERTY) {
+        let value_start = pos + PROPERTY.len();
+        out.push_str(&rest[..value_start]);
+        rest = &rest[value_start..];
+
+        let Some((prefix_len, value_len)) = font_list_span(rest) else {
+            continue;
+        };
+        let value = &rest[prefix_len..prefix_len + value_len];
+        out.push_str(&rest[..prefix_len + value_len]);
+        if !value.trim().is_empty() && !value.contains(FALLBACK_FONT) {
+            out.push_str(", ");
+            out.push_str(FALLBACK_FONT);
+        }
+        rest = &rest[prefix_len + value_len..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// For text following `font-family`, the length of the `="` / `:` prefix and of the
+/// font list itself, for both the attribute and the CSS property form.
+fn font_list_span(text: &str) -> Option<(usize, usize)> {
+    let trimmed = text.trim_start();
+    let skipped = text.len() - trimmed.len();
+
+    if let Some(after_eq) = trimmed.strip_prefix('=') {
+        // Attribute: font-family="Virgil, Segoe UI Emoji"
+        let quote = after_eq
+            .chars()
+            .next()
+            .filter(|c| *c == '"' || *c == '\'')?;
+        let prefix_len = skipped + 2;
+        let value_len = text[prefix_len..].find(quote)?;
+        return Some((prefix_len, value_len));
+    }
+
+    // CSS property: font-family: 'Virgil', sans-serif;
+    let after_colon = trimmed.strip_prefix(':')?;
+    let prefix_len = skipped + 1;
+    let mut in_single_quote = false;
+    for (i, c) in after_colon.char_indices() {
+        match c {
+            '\'' => in_single_quote = !in_single_quote,
+            ';' | '}' | '"' | '<' | '\n' if !in_single_quote => return Some((prefix_len, i)),
+            _ => {}
+        }
+    }
+    Some((prefix_len, after_colon.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_svg, with_font_fallback};
+
+    #[test]
+    fn font_fallback() {
+        assert_eq!(
+            with_font_fallback(r#"<text font-family="Virgil, Segoe UI Emoji">Hi</text>"#),
+            r#"<text font-family="Virgil, Segoe UI Emoji, Open Sans">Hi</text>"#
+        );
+        assert_eq!(
+            with_font_fallback("<text font-family='Helvetica'/>"),
+            "<text font-family='Helvetica, Open Sans'/>"
+        );
+        assert_eq!(
+            with_font_fallback(r#"<g style="font-family: 'Comic Sans'; fill: red">"#),
+            r#"<g style="font-family: 'Comic Sans', Open Sans; fill: red">"#
+        );
+        // @font-face declarations with double-quoted names are left alone
+        let font_face = r#"<style>@font-face { font-family: "Virgil"; }</style>"#;
+        assert_eq!(with_font_fallback(font_face), font_face);
+    }
+
+    #[test]
+    fn detects_svg() {
+        assert!(is_svg(
+            b"<?xml version=\"1.0\"?>\n<!-- x -->\n<svg xmlns=\"...\">"
+        ));
+        assert!(!is_svg(b"\x89PNG\r\n\x1a\n"));
+    }
+}
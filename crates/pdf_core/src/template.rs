@@ -0,0 +1,171 @@
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+/// Load a JSON or YAML data file (chosen by extension) as template context.
+pub fn load_data(path: &Path) -> Result<Value, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read data file {}: {}", path.display(), e))?;
+    let is_yaml = matches!(
+        path.extension().and_then(|ext| ext.to_str()),
+        Some("yaml" | "yml")
+    );
+    if is_yaml {
+        serde_yaml::from_str(&content)
+            .map_err(|e| format!("Invalid YAML in {}: {}", path.display(), e))
+    } else {
+        serde_json::from_str(&content)
+            .map_err(|e| format!("Invalid JSON in {}: {}", path.display(), e))
+    }
+}
+
+/// Expand `{{path.to.value}}` substitutions and `{{#each list}}...{{/each}}` loops.
+///
+/// Inside a loop, `{{this}}` is the current item, `{{@index}}` its position, and
+/// other names are looked up on the item first, then on the enclosing context.
+pub fn render(template: &str, data: &Value) -> Result<String, String> {
+    let mut out = String::new();
+    let scope = Scope {
+        value: data,
+        index: None,
+    };
+    render_scoped(template, &[scope], &mut out)?;
+    Ok(out)
+}
+
+#[derive(Clone, Copy)]
+struct Scope<'a> {
+    value: &'a Value,
+    index: Option<usize>,
+}
+
+fn render_scoped(template: &str, scopes: &[Scope], out: &mut String) -> Result<(), String> {
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after
+            .find("}}")
+            .ok_or_else(|| "Unclosed {{ in template".to_string())?;
+        let tag = after[..end].trim();
+        rest = &after[end + 2..];
+
+        if let Some(path) = tag.strip_prefix("#each ") {
+            let (body, remainder) = split_each_body(rest)?;
+            let list = lookup(scopes, path.trim())?;
+            let items = list
+                .as_array()
+                .ok_or_else(|| format!("Template value '{}' is not a list", path.trim()))?;
+            for (index, item) in items.iter().enumerate() {
+                let mut inner = scopes.to_vec();
+                inner.push(Scope {
+                    value: item,
+                    index: Some(index),
+                });
+                render_scoped(body, &inner, out)?;
+            }
+            rest = remainder;
+        } else if tag == "/each" {
+            return Err("Unexpected {{/each}} in template".to_string());
+        } else if tag == "@index" {
+            let index = scopes
+                .last()
+                .and_then(|scope| scope.index)
+                .ok_or_else(|| "{{@index}} used outside of {{#each}}".to_string())?;
+            out.push_str(&index.to_string());
+        } else {
+            out.push_str(&value_to_string(lookup(scopes, tag)?));
+        }
+    }
+    out.push_str(rest);
+    Ok(())
+}
+
+/// Split the text after `{{#each ...}}` into the loop body and what follows `{{/each}}`.
+/// Tags on their own line don't leave blank lines behind.
+fn split_each_body(text: &str) -> Result<(&str, &str), String> {
+    let text = text.strip_prefix('\n').unwrap_or(text);
+    let mut depth = 0;
+    let mut pos = 0;
+    while let Some(start) = text[pos..].find("{{") {
+        let tag_start = pos + start;
+        let end = text[tag_start..]
+            .find("}}")
+            .ok_or_else(|| "Unclosed {{ in template".to_string())?;
+        let tag = text[tag_start + 2..tag_start + end].trim();
+        let tag_end = tag_start + end + 2;
+        if tag.starts_with("#each ") {
+            depth += 1;
+        } else if tag == "/each" {
+            if depth == 0 {
+                let remainder = &text[tag_end..];
+                return Ok((
+                    &text[..tag_start],
+                    remainder.strip_prefix('\n').unwrap_or(remainder),
+                ));
+            }
+            depth -= 1;
+        }
+        pos = tag_end;
+    }
+    Err("Missing {{/each}} in template".to_string())
+}
+
+fn lookup<'a>(scopes: &[Scope<'a>], path: &str) -> Result<&'a Value, String> {
+    let innermost = scopes.last().expect("template scope stack is never empty");
+    let found = match path.strip_prefix("this") {
+        Some("") => Some(innermost.value),
+        Some(rest) if rest.starts_with('.') => walk(innermost.value, &rest[1..]),
+        _ => scopes
+            .iter()
+            .rev()
+            .find_map(|scope| walk(scope.value, path)),
+    };
+    found.ok_or_else(|| format!("Unknown template variable '{}'", path))
+}
+
+fn walk<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.')
+        .try_fold(value, |current, segment| match current {
+            Value::Object(map) => map.get(segment),
+            Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+            _ => None,
+        })
+}
+
+fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render;
+    use serde_json::json;
+
+    #[test]
+    fn substitutes_paths() {
+        let data = json!({ "client": { "name": "ACME" }, "total": 42.5, "tags": ["a", "b"] });
+        assert_eq!(
+            render("Bill {{ client.name }}: {{total}} ({{tags.1}})", &data).unwrap(),
+            "Bill ACME: 42.5 (b)"
+        );
+        assert!(render("{{missing}}", &data).is_err());
+    }
+
+    #[test]
+    fn each_loops() {
+        let data = json!({
+            "currency": "EUR",
+            "items": [{ "name": "Design", "price": 100 }, { "name": "Build", "price": 250 }]
+        });
+        let template = "| # | Item |\n|---|---|\n{{#each items}}\n| {{@index}} | {{name}}: {{this.price}} {{currency}} |\n{{/each}}\nDone";
+        assert_eq!(
+            render(template, &data).unwrap(),
+            "| # | Item |\n|---|---|\n| 0 | Design: 100 EUR |\n| 1 | Build: 250 EUR |\nDone"
+        );
+    }
+}
@@ -0,0 +1,182 @@
+use serde::Serialize;
+use typst_library::layout::{Abs, Frame, FrameItem, PagedDocument, Point};
+
+use crate::block::{Block, List};
+use crate::typst::collect_span_text;
+
+/// Flatten blocks to readable plain text, one blank line between blocks.
+pub fn blocks_to_text(blocks: &[Block]) -> String {
+    let mut out = String::new();
+    for block in blocks {
+        let start = out.len();
+        block_to_text(block, &mut out);
+        if out.len() > start {
+            out.push('\n');
+        }
+    }
+    out.truncate(out.trim_end().len());
+    out
+}
+
+fn block_to_text(block: &Block, out: &mut String) {
+    match block {
+        Block::Heading { content, .. } | Block::Paragraph { content } => {
+            collect_span_text(content, out);
+            out.push('\n');
+        }
+        Block::CodeBlock { content, .. } => {
+            out.push_str(content);
+            if !content.ends_with('\n') {
+                out.push('\n');
+            }
+        }
+        Block::List(list) => list_to_text(list, 0, out),
+        Block::Table {
+            headers,
+            rows,
+            caption,
+            ..
+        } => {
+            // Cells are tab-separated so rows stay on one line
+            for row in std::iter::once(headers).chain(rows) {
+                let cells: Vec<String> = row
+                    .iter()
+                    .map(|cell| {
+                        let mut text = String::new();
+                        collect_span_text(cell, &mut text);
+                        text
+                    })
+                    .collect();
+                if cells.iter().any(|cell| !cell.trim().is_empty()) {
+                    out.push_str(&cells.join("\t"));
+                    out.push('\n');
+                }
+            }
+            if let Some(caption) = caption {
+                collect_span_text(caption, out);
+                out.push('\n');
+            }
+        }
+        Block::Diagram { code, .. } => block_to_text(code, out),
+        Block::Image { alt, .. } => {
+            if !alt.is_empty() {
+                out.push_str(alt);
+                out.push('\n');
+            }
+        }
+        Block::Rule
+        | Block::PageBreak
+        | Block::MainMatter
+        | Block::RegionStart(_)
+        | Block::RestartNumbers { .. }
+        | Block::RegionEnd(_)
+        | Block::QrCode { .. } => {}
+    }
+}
+
+fn list_to_text(list: &List, indent: usize, out: &mut String) {
+    for (i, item) in list.items.iter().enumerate() {
+        out.push_str(&"  ".repeat(indent));
+        match item.checked {
+            Some(true) => out.push_str("[x] "),
+            Some(false) => out.push_str("[ ] "),
+            None if list.ordered => out.push_str(&format!("{}. ", list.start + i as u64)),
+            None => out.push_str("- "),
+        }
+        collect_span_text(&item.content, out);
+        out.push('\n');
+        if let Some(ref nested) = item.nested {
+            list_to_text(nested, indent + 1, out);
+        }
+    }
+}
+
+/// Text of a laid-out document in reading order, with pages separated by form feeds.
+/// Text runs on the same baseline are joined by spaces, new baselines start new lines.
+pub fn document_text(doc: &PagedDocument) -> String {
+    doc.pages
+        .iter()
+        .map(|page| {
+            let mut out = String::new();
+            let mut baseline = None;
+            frame_text(&page.frame, Point::zero(), &mut baseline, &mut out);
+            out
+        })
+        .collect::<Vec<_>>()
+        .join("\n\u{c}\n")
+}
+
+fn frame_text(frame: &Frame, offset: Point, baseline: &mut Option<Abs>, out: &mut String) {
+    for (pos, item) in frame.items() {
+        let pos = offset + *pos;
+        match item {
+            FrameItem::Group(group) => frame_text(&group.frame, pos, baseline, out),
+            FrameItem::Text(text) => {
+                match *baseline {
+                    Some(y) if y == pos.y => out.push(' '),
+                    Some(_) => out.push('\n'),
+                    None => {}
+                }
+                *baseline = Some(pos.y);
+                out.push_str(&text.text);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// A run of laid-out text in one font and size, e.g. for a selectable text
+/// layer over an image of the page. Coordinates are in points from the
+/// page's top-left corner.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TextRun {
+    pub text: String,
+    pub x: f64,
+    pub baseline: f64,
+    /// Advance width of the run's glyphs
+    pub width: f64,
+    /// Font size
+    pub size: f64,
+}
+
+/// The text runs of a page in the order they were laid out.
+pub(crate) fn text_runs(frame: &Frame) -> Vec<TextRun> {
+    let mut runs = Vec::new();
+    collect_runs(frame, Point::zero(), &mut runs);
+    runs
+}
+
+fn collect_runs(frame: &Frame, offset: Point, runs: &mut Vec<TextRun>) {
+    for (pos, item) in frame.items() {
+        let pos = offset + *pos;
+        match item {
+            FrameItem::Group(group) => collect_runs(&group.frame, pos, runs),
+            FrameItem::Text(text) => runs.push(TextRun {
+                text: text.text.to_string(),
+                x: pos.x.to_pt(),
+                baseline: pos.y.to_pt(),
+                width: text
+                    .glyphs
+                    .iter()
+                    .map(|glyph| glyph.x_advance.at(text.size).to_pt())
+                    .sum(),
+                size: text.size.to_pt(),
+            }),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::extract_text;
+
+    #[test]
+    fn flattens_blocks() {
+        let markdown = "# Title\n\nSome **bold** [link](https://example.com).\n\n1. one\n2. [two](#title)\n\n- [x] done\n\n| A | B |\n|---|---|\n| 1 | 2 |\n\n---\n\n```\ncode\n```";
+        assert_eq!(
+            extract_text(markdown),
+            "Title\n\nSome bold link.\n\n1. one\n2. two\n\n[x] done\n\nA\tB\n1\t2\n\ncode"
+        );
+    }
+}
@@ -1,59 +1,479 @@
-use crate::block::{Block, List, Span};
-use crate::config::Config;
+use std::borrow::Cow;
+
+use crate::acronyms;
+use crate::block::{Alignment, Block, List, Region, Span};
+use crate::build_info;
+use crate::config::{
+    CodeOverflow, Config, FootnoteNumbering, Mode, PrintUrls, TableWidth, TablesConfig, Theme,
+};
+use crate::data_uri;
+use crate::diagram;
+use crate::lists;
+use crate::parser;
+use crate::svg;
+
+/// Maps byte offsets in generated Typst markup back to markdown source lines.
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    /// (Typst offset where a block's markup starts, markdown line), sorted by offset
+    entries: Vec<(usize, usize)>,
+}
+
+impl SourceMap {
+    fn push(&mut self, offset: usize, line: usize) {
+        self.entries.push((offset, line));
+    }
+
+    /// Markdown line of the block whose markup contains the given Typst offset.
+    pub fn line_for_offset(&self, offset: usize) -> Option<usize> {
+        let index = self.entries.partition_point(|&(start, _)| start <= offset);
+        index.checked_sub(1).map(|i| self.entries[i].1)
+    }
+}
 
 /// Convert blocks to Typst markup
 pub fn blocks_to_typst(blocks: &[Block], config: &Config) -> String {
+    blocks_to_typst_mapped(blocks, &[], config).0
+}
+
+/// Convert blocks to Typst markup, recording where each block's markup starts.
+/// `lines` holds the markdown source line of each block (may be empty).
+pub fn blocks_to_typst_mapped(
+    blocks: &[Block],
+    lines: &[usize],
+    config: &Config,
+) -> (String, SourceMap) {
+    let blocks = &*acronyms::expand(blocks, &config.acronyms.terms);
+    let blocks = &*lists::continue_numbering(blocks, config.lists.continue_numbering);
     let mut out = String::new();
+    let mut source_map = SourceMap::default();
 
     // Set up paragraph settings to prevent widows/orphans
     out.push_str("#set par(linebreaks: \"optimized\")\n");
 
+    // Line numbers in the margin
+    if config.typography.line_numbers {
+        match config.typography.line_number_step.filter(|&step| step > 1) {
+            Some(step) => out.push_str(&format!(
+                "#set par.line(numbering: n => if calc.rem(n, {}) == 0 [#n] else [])\n",
+                step
+            )),
+            None => out.push_str("#set par.line(numbering: \"1\")\n"),
+        }
+    }
+
+    // PDF metadata
+    let document = &config.document;
+    if document.title.is_some() || document.author.is_some() {
+        let mut fields = Vec::new();
+        if let Some(title) = &document.title {
+            fields.push(format!("title: \"{}\"", escape_string(title)));
+        }
+        if let Some(author) = &document.author {
+            fields.push(format!("author: \"{}\"", escape_string(author)));
+        }
+        out.push_str(&format!("#set document({})\n", fields.join(", ")));
+    }
+
+    // Compact resume layout, first so that margins and text settings from the
+    // config, e.g. for sidenotes or two-sided pages, are set on top of it
+    if document.theme == Theme::Resume {
+        out.push_str("#set page(margin: 1.8cm)\n#set text(size: 10pt)\n#set par(spacing: 0.8em)\n#set list(spacing: 0.5em)\n");
+        out.push_str("#show heading: set block(above: 1.2em, below: 0.6em)\n");
+        out.push_str("#show heading.where(level: 2): it => block(above: 1.4em, below: 0.6em, width: 100%, stroke: (bottom: 0.5pt), inset: (bottom: 0.3em), upper(text(size: 0.9em, tracking: 0.05em, it.body)))\n");
+    }
+
     // Font family
-    if config.font.sans {
+    if let Some(family) = &config.font.family {
+        out.push_str(&format!("#set text(font: \"{}\")\n", escape_string(family)));
+    } else if config.font.sans {
         out.push_str("#set text(font: \"Open Sans\")\n");
     }
 
-    // Page numbers
-    if config.page.numbers {
+    // Letter-spacing
+    let typography = &config.typography;
+    if let Some(tracking) = &typography.tracking {
+        out.push_str(&format!("#set text(tracking: {})\n", tracking));
+    }
+    for level in 1..=6 {
+        if let Some(tracking) = typography.tracking_for_heading(level) {
+            out.push_str(&format!(
+                "#show heading.where(level: {}): set text(tracking: {})\n",
+                level, tracking
+            ));
+        }
+    }
+
+    // OpenType features. Each rule replaces the features set before it, so
+    // every one of them repeats whether ligatures are off.
+    let features = &typography.features;
+    let body_ligatures = Some(false).filter(|_| !features.ligatures);
+    if !features.body.is_empty() || body_ligatures.is_some() {
+        out.push_str(&format!(
+            "#set text(features: {})\n",
+            feature_list(&features.body, body_ligatures)
+        ));
+    }
+    // Code turns them back on if only the body has them off
+    let code_ligatures =
+        Some(features.code_ligatures).filter(|&on| !on || body_ligatures.is_some());
+    for (selector, list) in features.show_rules() {
+        let ligatures = if selector == "raw" {
+            code_ligatures
+        } else {
+            body_ligatures
+        };
+        out.push_str(&format!(
+            "#show {}: set text(features: {})\n",
+            selector,
+            feature_list(list, ligatures)
+        ));
+    }
+    if features.code.is_empty() && code_ligatures != body_ligatures {
+        out.push_str(&format!(
+            "#show raw: set text(features: {})\n",
+            feature_list(&[], code_ligatures)
+        ));
+    }
+
+    // Bullets per nesting level
+    let bullets = &config.lists.bullets;
+    if !bullets.is_empty() {
+        let mut markers = String::new();
+        for bullet in bullets {
+            markers.push('[');
+            escape_text(bullet, &mut markers);
+            markers.push_str("], ");
+        }
+        out.push_str(&format!("#set list(marker: ({}))\n", markers.trim_end()));
+    }
+
+    // Footnotes
+    let footnotes = &config.footnotes;
+    if footnotes.numbering == FootnoteNumbering::Symbols {
+        out.push_str("#set footnote(numbering: \"*\")\n");
+    }
+    if let Some(size) = &footnotes.font_size {
+        out.push_str(&format!("#show footnote.entry: set text(size: {})\n", size));
+    }
+    if let Some(length) = &footnotes.separator_length {
+        out.push_str(&format!(
+            "#set footnote.entry(separator: line(length: {}, stroke: 0.5pt))\n",
+            length
+        ));
+    }
+
+    // Paper size
+    if let Some(size) = &config.page.size {
+        out.push_str(&format!("#set page(paper: \"{}\")\n", escape_string(size)));
+    }
+
+    // Slides replace the paper size
+    let slides = document.mode == Mode::Slides;
+    if slides {
+        out.push_str("#set page(paper: \"presentation-16-9\")\n#set text(size: 22pt)\n");
+    }
+
+    if let Some(columns) = config.page.columns.filter(|_| has_columns(config)) {
+        out.push_str(&format!("#set page(columns: {})\n", columns));
+    }
+
+    // Mirrored margins, leaving room for the binding
+    let two_sided = config.page.two_sided;
+    if two_sided {
+        out.push_str(&format!(
+            "#set page(margin: (inside: {INSIDE_MARGIN}, outside: {OUTSIDE_MARGIN}))\n"
+        ));
+    }
+
+    // Sidenotes go in a right (or outside) margin widened to fit them
+    if blocks
+        .iter()
+        .any(|block| block_has_span(block, is_sidenote))
+    {
+        if two_sided {
+            out.push_str(&format!(
+                "#set page(margin: (outside: {SIDENOTE_WIDTH} + 2 * {SIDENOTE_GAP}))\n"
+            ));
+            out.push_str(&format!(
+                "#let sidenote(body) = context {{ let recto = calc.odd(here().page()); place(if recto {{ right }} else {{ left }}, dx: if recto {{ {SIDENOTE_WIDTH} + {SIDENOTE_GAP} }} else {{ -({SIDENOTE_WIDTH} + {SIDENOTE_GAP}) }}, block(width: {SIDENOTE_WIDTH}, text(size: 0.8em, body))) }}\n"
+            ));
+        } else {
+            out.push_str(&format!(
+                "#set page(margin: (right: {SIDENOTE_WIDTH} + 2 * {SIDENOTE_GAP}))\n"
+            ));
+            out.push_str(&format!(
+                "#let sidenote(body) = place(right, dx: {SIDENOTE_WIDTH} + {SIDENOTE_GAP}, block(width: {SIDENOTE_WIDTH}, text(size: 0.8em, body)))\n"
+            ));
+        }
+    }
+
+    // Page numbers; front matter before a `---mainmatter---` marker gets roman ones
+    if blocks
+        .iter()
+        .any(|block| matches!(block, Block::MainMatter))
+    {
+        out.push_str("#set page(numbering: \"i\")\n");
+    } else if config.page.numbers {
         out.push_str("#set page(numbering: \"1\")\n");
     }
 
+    // Letterhead / background underlay
+    if let Some(path) = &config.page.background_image {
+        let image = format!(
+            "image(\"{}\", width: 100%, height: 100%)",
+            escape_string(path)
+        );
+        if config.page.background_first_page_only {
+            out.push_str(&format!(
+                "#set page(background: context if here().page() == 1 {{ {} }})\n",
+                image
+            ));
+        } else {
+            out.push_str(&format!("#set page(background: {})\n", image));
+        }
+    }
+
+    // Watermark, in the foreground so images don't cover it
+    if let Some(text) = &config.page.watermark {
+        out.push_str(&format!(
+            "#set page(foreground: place(center + horizon, rotate(-45deg, text(size: 72pt, fill: gray.transparentize(70%), \"{}\"))))\n",
+            escape_string(text)
+        ));
+    }
+
+    // Running header, which the first page may replace or skip
+    let header = &config.header;
+    let running = [
+        &header.text,
+        &header.footer,
+        &header.first_page_header,
+        &header.first_page_footer,
+    ];
+    if running
+        .iter()
+        .any(|line| line.as_deref().is_some_and(|line| line.contains(SECTION)))
+    {
+        // The heading starting on this page, else the last one before it
+        out.push_str(&format!(
+            "#let section-title() = {{ let headings = query(heading.where(level: {})); let page = here().page(); let on-page = headings.filter(it => it.location().page() == page); let before = headings.filter(it => it.location().page() < page); if on-page.len() > 0 {{ on-page.first().body }} else if before.len() > 0 {{ before.last().body }} }}\n",
+            header.section_level.unwrap_or(1)
+        ));
+    }
+    let align = if two_sided { OUTSIDE_EDGE } else { "center" };
+    if let Some(line) = running_line(
+        header.text.as_deref(),
+        header.first_page_header.as_deref(),
+        header.skip_first,
+    ) {
+        out.push_str(&format!(
+            "#set page(header: context align({}, stack(..{})))\n",
+            align, line
+        ));
+    }
+
+    // Running footer and build info, with the page number above them if pages
+    // are numbered. Two-sided pages always need this to put the number outside.
+    let footer_line = running_line(
+        header.footer.as_deref(),
+        header.first_page_footer.as_deref(),
+        header.skip_first,
+    );
+    if footer_line.is_some() || config.build_info.footer || two_sided {
+        let mut items =
+            vec!["..if numbering != none { (counter(page).display(numbering),) }".to_string()];
+        if let Some(line) = footer_line {
+            items.push(format!("..{}", line));
+        }
+        if config.build_info.footer {
+            items.push(format!(
+                "text(size: 7pt, fill: gray, \"{}\")",
+                escape_string(&build_info::footer_line(&config.build_info))
+            ));
+        }
+        out.push_str(&format!(
+            "#set page(footer: context {{ let numbering = here().page-numbering(); align({}, stack(spacing: 0.8em, {})) }})\n",
+            align,
+            items.join(", ")
+        ));
+    }
+
+    // Footnote numbers restart in each page's header, which comes before
+    // the page's text; the header itself is kept
+    if config.footnotes.per_page {
+        out.push_str("#show: body => context { let header = page.header; set page(header: { counter(footnote).update(0); if header != auto { header } }); body }\n");
+    }
+
+    // Bleed and crop marks: a larger page around the trimmed one, with the
+    // margins still measured from the trim edge
+    if config.page.bleed.is_some() || config.page.crop_marks {
+        let bleed = config.page.bleed.as_deref().unwrap_or("0pt");
+        let mut extent = bleed.to_string();
+        let mut marks = String::new();
+        if config.page.crop_marks {
+            out.push_str(&format!(
+                "#let crop-marks(width, height, extent) = {{ let length = {CROP_MARK_LENGTH}; for y in (extent, height - extent) {{ for x in (0pt, width - length) {{ place(dx: x, dy: y, line(length: length, stroke: 0.25pt)) }} }}; for x in (extent, width - extent) {{ for y in (0pt, height - length) {{ place(dx: x, dy: y, line(length: length, angle: 90deg, stroke: 0.25pt)) }} }} }}\n"
+            ));
+            extent = format!("{} + {}", bleed, CROP_MARK_LENGTH);
+            marks =
+                ", foreground: { page.foreground; crop-marks(width, height, extent) }".to_string();
+        }
+        out.push_str(&format!(
+            "#show: body => context {{ let extent = {extent}; let margin = page.margin; let trim-margin = 2.5 / 21 * calc.min(page.width, page.height); let side(key) = {{ let value = if type(margin) == dictionary {{ margin.at(key, default: auto) }} else {{ margin }}; (if value == auto {{ trim-margin }} else {{ value }}) + extent }}; let (width, height) = (page.width + 2 * extent, page.height + 2 * extent); set page(width: width, height: height, margin: if type(margin) == dictionary and \"inside\" in margin {{ (top: side(\"top\"), bottom: side(\"bottom\"), inside: side(\"inside\"), outside: side(\"outside\")) }} else {{ (top: side(\"top\"), bottom: side(\"bottom\"), left: side(\"left\"), right: side(\"right\")) }}{marks}); body }}\n"
+        ));
+    }
+
+    // Sets the first letter of a paragraph beside as many of its words as fit
+    // the lines next to it, and the rest of the paragraph below
+    if config.typography.drop_caps {
+        out.push_str(&format!(
+            "#let drop-cap(letter, words) = layout(size => {{ let edges = (top-edge: \"cap-height\", bottom-edge: \"baseline\"); let height = measure(block(width: size.width, range({DROP_CAP_LINES}).map(_ => [X]).join(linebreak()))).height; let cap = text(..edges, size: 10pt * height / measure(text(..edges, size: 10pt, letter)).height, letter); let gap = 0.2em; let width = size.width - measure(cap).width - gap; let fits(n) = measure(block(width: width, words.slice(0, n).join(\" \"))).height <= height + 1pt; let n = calc.min(1, words.len()); while n < words.len() and fits(n + 1) {{ n += 1 }}; grid(columns: 2, column-gutter: gap, cap, words.slice(0, n).join(\" \") + if n < words.len() {{ linebreak(justify: true) }}); if n < words.len() {{ block(above: par.leading, {{ set par(first-line-indent: 0pt); words.slice(n).join(\" \") }}) }} }})\n"
+        ));
+    }
+
+    // Code blocks that fit long lines to the page, given the language and code
+    match config.code.overflow {
+        Some(CodeOverflow::Wrap) => out.push_str(&format!(
+            "#let code-block(lang, code) = layout(size => {{ let columns = calc.max(8, calc.floor(size.width / measure(raw(\"0\")).width)) - {WRAP_MARKER_WIDTH}; let lines = code.split(\"\\n\").map(line => {{ let chars = line.clusters(); if chars.len() <= columns {{ line }} else {{ range(0, chars.len(), step: columns).map(i => chars.slice(i, calc.min(i + columns, chars.len())).join()).join(\"\\n{WRAP_MARKER}\") }} }}); raw(block: true, lang: lang, lines.join(\"\\n\")) }})\n"
+        )),
+        Some(CodeOverflow::Shrink) => out.push_str(
+            "#let code-block(lang, code) = layout(size => { let body = raw(block: true, lang: lang, code); let width = measure(body).width; if width > size.width { set text(size: size.width / width * 1em); body } else { body } })\n",
+        ),
+        // Also given the metadata to place if the lines don't fit, which the
+        // renderer looks for to warn about the block
+        Some(CodeOverflow::ScrollNote) => out.push_str(
+            "#let code-block(lang, code, overflow) = layout(size => { let body = raw(block: true, lang: lang, code); body; if measure(body).width > size.width { overflow } })\n",
+        ),
+        None => {}
+    }
+
+    // Code in black; there's no gray highlighting theme
+    if config.print.grayscale {
+        out.push_str("#set raw(theme: none)\n");
+    }
+
     // Style links
     if config.links.underline {
         out.push_str(&format!(
-            "#show link: it => underline(text(fill: rgb(\"{}\"), it))\n",
-            config.links.color
+            "#show link: it => underline(text(fill: {}, it))\n",
+            color(config, &format!("rgb(\"{}\")", config.links.color))
         ));
     } else {
         out.push_str(&format!(
-            "#show link: it => text(fill: rgb(\"{}\"), it)\n",
-            config.links.color
+            "#show link: it => text(fill: {}, it)\n",
+            color(config, &format!("rgb(\"{}\")", config.links.color))
+        ));
+    }
+
+    // References to unnumbered headings show the heading text instead of failing
+    if blocks
+        .iter()
+        .any(|block| block_has_span(block, is_reference))
+    {
+        out.push_str(
+            "#show ref: it => if it.element != none and it.element.func() == heading and it.element.numbering == none { link(it.target, it.element.body) } else { it }\n",
+        );
+    }
+
+    // User show-rule overrides come last so they take precedence
+    for (selector, body) in config.overrides.show_rules() {
+        out.push_str(&format!("#show {}: {}\n", selector, body));
+    }
+
+    // The ink saver follows them, so it also beats fills they set with set rules;
+    // fills passed as explicit arguments (ours below, or an override's) are not
+    // reachable this way, so emission leaves ours out in ink saver mode
+    if config.print.ink_saver {
+        out.push_str("#show block: set block(fill: none)\n#show box: set box(fill: none)\n#show rect: set rect(fill: none)\n");
+        out.push_str(&format!(
+            "#show table: set table(fill: none, stroke: {THIN_RULE})\n#show line: set line(stroke: {THIN_RULE})\n"
         ));
     }
 
     out.push('\n');
 
+    if let Some(number) = config.page.first_number {
+        out.push_str(&format!("#counter(page).update({})\n", number));
+    }
+
+    // Without a title block, the abstract goes below the document's own title:
+    // its first heading, if that's at the top level
+    let mut abstract_heading = None;
+    if document.theme == Theme::Letter {
+        emit_letter_head(config, &mut out);
+    } else if document.title_page {
+        emit_title_page(config, &mut out);
+    } else {
+        if document.title_block {
+            emit_title_block(config, &mut out);
+        } else if document.r#abstract.is_some() && document.theme != Theme::Resume {
+            abstract_heading = blocks
+                .iter()
+                .position(|block| matches!(block, Block::Heading { .. }))
+                .filter(|&i| matches!(blocks[i], Block::Heading { level: 1, .. }));
+        }
+        if let Some(text) = document
+            .r#abstract
+            .as_ref()
+            .filter(|_| abstract_heading.is_none())
+        {
+            emit_abstract(text, &mut out);
+        }
+    }
+    if document.toc {
+        out.push_str("#outline()\n#pagebreak(weak: true)\n\n");
+    }
+
     // Track if previous long section needs a break after it, and at what level
     let mut pending_end_break_level: Option<u8> = None;
+    // Regions are containers, which page breaks can't be in
+    let mut open_regions = 0usize;
+    // Headings at this level or higher restart the page numbers
+    let mut restart_level: Option<u8> = None;
+    // Inside a `<details>` element that's left out
+    let mut omitting = false;
 
     let mut i = 0;
+    if document.theme == Theme::Resume {
+        i = emit_resume_header(blocks, lines, config, &mut out, &mut source_map);
+    }
     while i < blocks.len() {
         let block = &blocks[i];
+        if let Some(&line) = lines.get(i) {
+            source_map.push(out.len(), line);
+        }
 
         match block {
+            Block::RegionStart(Region::Details) if config.document.omit_details => omitting = true,
+            Block::RegionEnd(Region::Details) if omitting => omitting = false,
+            _ if omitting => {}
             Block::Heading { level, .. } => {
+                if open_regions == 0 && restart_level.is_some_and(|restart| *level <= restart) {
+                    strip_trailing_rule(&mut out);
+                    out.push_str("#pagebreak(weak: true)\n#counter(page).update(1)\n");
+                }
+
+                // Each slide starts at a heading, so the breaks below don't apply
+                if slides && open_regions == 0 && *level <= 2 {
+                    strip_trailing_rule(&mut out);
+                    out.push_str("#pagebreak(weak: true)\n");
+                }
+                let paginate = open_regions == 0 && !slides;
+
                 // Check if this section is long enough to warrant a page break
                 let section_lines = count_section_lines(blocks, i);
-                let force_break = config
-                    .layout
-                    .break_if_lines_for_heading(*level)
-                    .map(|threshold| section_lines >= threshold)
-                    .unwrap_or(false);
+                let force_break = paginate
+                    && config
+                        .layout
+                        .break_if_lines_for_heading(*level)
+                        .map(|threshold| section_lines >= threshold)
+                        .unwrap_or(false);
 
                 // Only process end breaks for headings at the same level or higher
-                let should_check_end_break = pending_end_break_level
-                    .map(|pending_level| *level <= pending_level)
-                    .unwrap_or(false);
+                let should_check_end_break = paginate
+                    && pending_end_break_level
+                        .map(|pending_level| *level <= pending_level)
+                        .unwrap_or(false);
 
                 if force_break {
                     // This section wants a break before it, which satisfies any pending end break
@@ -65,7 +485,11 @@ pub fn blocks_to_typst(blocks: &[Block], config: &Config) -> String {
                     strip_trailing_rule(&mut out);
                     out.push_str("#pagebreak(weak: true)\n");
                     pending_end_break_level = None;
-                } else if let Some(min_space) = config.layout.min_space_for_heading(*level) {
+                } else if let Some(min_space) = config
+                    .layout
+                    .min_space_for_heading(*level)
+                    .filter(|_| paginate)
+                {
                     // If min_space is configured, insert a non-breaking block to reserve space
                     // This causes Typst to move the heading to the next page if not enough room
                     out.push_str(&format!(
@@ -82,25 +506,350 @@ pub fn blocks_to_typst(blocks: &[Block], config: &Config) -> String {
 
                 // Keep heading with following content using a block that prevents breaks
                 out.push_str("#block(breakable: false)[\n");
-                emit_heading(block, &mut out);
+                emit_heading(block, config, &mut out);
 
                 // Include the next block if it exists (to keep heading with first content)
-                // But don't include pagebreaks - they can't be inside containers
-                if i + 1 < blocks.len() && !matches!(&blocks[i + 1], Block::PageBreak) {
+                // But don't include pagebreaks - they can't be inside containers -
+                // or region markers, whose brackets would cross this block's -
+                // or a numbering restart, which this loop has to see.
+                // The title is kept with the abstract instead.
+                if abstract_heading == Some(i) {
+                    if let Some(text) = &document.r#abstract {
+                        emit_abstract(text, &mut out);
+                    }
+                } else if i + 1 < blocks.len()
+                    && !matches!(
+                        &blocks[i + 1],
+                        Block::PageBreak
+                            | Block::MainMatter
+                            | Block::RegionStart(_)
+                            | Block::RegionEnd(_)
+                            | Block::RestartNumbers { .. }
+                    )
+                {
                     i += 1;
-                    emit_block(&blocks[i], &mut out);
+                    if let Some(&line) = lines.get(i) {
+                        source_map.push(out.len(), line);
+                    }
+                    let drop_cap = match &blocks[i] {
+                        Block::Paragraph { content }
+                            if *level == 1 && config.typography.drop_caps =>
+                        {
+                            emit_drop_cap(content, config, &mut out)
+                        }
+                        _ => false,
+                    };
+                    if !drop_cap {
+                        emit_block(&blocks[i], config, &mut out);
+                    }
                 }
                 out.push_str("]\n\n");
             }
+            Block::RestartNumbers { level } => restart_level = Some(*level),
+            Block::RegionStart(_) => {
+                open_regions += 1;
+                emit_block(block, config, &mut out);
+            }
+            Block::RegionEnd(_) => {
+                open_regions = open_regions.saturating_sub(1);
+                emit_block(block, config, &mut out);
+            }
             _ => {
-                emit_block(block, &mut out);
+                emit_block(block, config, &mut out);
             }
         }
 
         i += 1;
     }
 
-    out
+    // Space to sign the letter above the name
+    if let Some(name) = config
+        .letter
+        .signature
+        .as_ref()
+        .filter(|_| document.theme == Theme::Letter)
+    {
+        out.push_str("#v(2cm)\n");
+        escape_text(name, &mut out);
+        out.push('\n');
+    }
+
+    (out, source_map)
+}
+
+/// Width of the sidenote column in the right margin
+const SIDENOTE_WIDTH: &str = "4.5cm";
+/// Space between the text and the sidenotes, and between them and the page edge
+const SIDENOTE_GAP: &str = "0.8cm";
+
+/// Margins of two-sided pages; the inside one leaves room for the binding
+const INSIDE_MARGIN: &str = "3cm";
+const OUTSIDE_MARGIN: &str = "2cm";
+/// Alignment of headers and footers on two-sided pages: right on odd pages,
+/// left on even ones
+const OUTSIDE_EDGE: &str = "if calc.odd(here().page()) { right } else { left }";
+
+fn has_columns(config: &Config) -> bool {
+    config.page.columns.is_some_and(|columns| columns > 1)
+}
+
+/// Stroke of table lines and horizontal rules in ink saver mode
+const THIN_RULE: &str = "0.5pt";
+
+/// Value of the `metadata` a code block with lines too long for the page
+/// places, for `code.overflow = "scroll-note"`
+pub(crate) const CODE_OVERFLOW_MARKER: &str = "code-overflow";
+
+/// Start of the continuation of a wrapped code line, and its width in characters
+const WRAP_MARKER: &str = "↪ ";
+const WRAP_MARKER_WIDTH: usize = 2;
+
+/// Number of lines a drop cap spans
+const DROP_CAP_LINES: usize = 3;
+
+/// Length of the crop marks, which run from the page edge up to the bleed
+const CROP_MARK_LENGTH: &str = "8mm";
+
+/// Color of the change bars next to `Region::Changed` blocks
+const CHANGE_BAR_COLOR: &str = "rgb(\"#d33\")";
+
+/// A Typst array of OpenType feature tags to turn on, or, to turn ligatures
+/// on or off too, a dictionary of the tags and their values.
+fn feature_list(features: &[String], ligatures: Option<bool>) -> String {
+    let Some(ligatures) = ligatures else {
+        let tags: Vec<String> = features
+            .iter()
+            .map(|tag| format!("\"{}\", ", escape_string(tag)))
+            .collect();
+        return format!("({})", tags.concat().trim_end());
+    };
+    let value = u8::from(ligatures);
+    let mut tags = vec![format!("\"liga\": {value}"), format!("\"calt\": {value}")];
+    tags.extend(
+        features
+            .iter()
+            .map(|tag| format!("\"{}\": 1", escape_string(tag))),
+    );
+    format!("({})", tags.join(", "))
+}
+
+/// A Typst color, converted to a gray of the same lightness for
+/// `print.grayscale`.
+fn color(config: &Config, color: &str) -> String {
+    if config.print.grayscale {
+        format!("luma({})", color)
+    } else {
+        color.to_string()
+    }
+}
+
+/// Placeholder in header and footer lines for the current section's heading
+const SECTION: &str = "{section}";
+
+/// Typst array holding the running header or footer line of the current page:
+/// `first` on the first page, `every` on the others and on the first unless
+/// `skip_first`. `None` if no page gets a line.
+fn running_line(every: Option<&str>, first: Option<&str>, skip_first: bool) -> Option<String> {
+    let first = first.or(every.filter(|_| !skip_first));
+    if every.is_none() && first.is_none() {
+        return None;
+    }
+    let line = |text: Option<&str>| match text {
+        None => "()".to_string(),
+        Some(text) if text.contains(SECTION) => {
+            let mut markup = String::new();
+            for (i, part) in text.split(SECTION).enumerate() {
+                if i > 0 {
+                    // The semicolon ends the call, whatever text follows
+                    markup.push_str("#section-title();");
+                }
+                escape_text(part, &mut markup);
+            }
+            format!("(text(size: 9pt)[{}],)", markup)
+        }
+        Some(text) => format!("(text(size: 9pt, \"{}\"),)", escape_string(text)),
+    };
+    Some(format!(
+        "if here().page() == 1 {{ {} }} else {{ {} }}",
+        line(first),
+        line(every)
+    ))
+}
+
+/// A page with the title and author centered, without a page number.
+fn emit_title_page(config: &Config, out: &mut String) {
+    out.push_str("#page(numbering: none, align(center + horizon)[\n");
+    if let Some(title) = &config.document.title {
+        out.push_str("#text(size: 2em, weight: \"bold\")[");
+        escape_text(title, out);
+        out.push_str("]\n\n");
+    }
+    if let Some(author) = &config.document.author {
+        escape_text(author, out);
+        out.push('\n');
+    }
+    if let Some(date) = &config.document.date {
+        out.push('\n');
+        escape_text(date, out);
+        out.push('\n');
+    }
+    if let Some(text) = &config.document.r#abstract {
+        out.push_str("#v(2em)\n");
+        emit_abstract(text, out);
+    }
+    out.push_str("])\n\n");
+}
+
+/// Sender, recipient, date and subject of a letter. The recipient's address
+/// gets a fixed height, so the text starts at the same place whatever its length.
+fn emit_letter_head(config: &Config, out: &mut String) {
+    let letter = &config.letter;
+    if let Some(sender) = &letter.sender {
+        out.push_str("#align(right, text(size: 0.9em)[");
+        emit_lines(sender, out);
+        out.push_str("])\n\n");
+    }
+    if let Some(recipient) = &letter.recipient {
+        out.push_str("#block(height: 4cm)[");
+        emit_lines(recipient, out);
+        out.push_str("]\n\n");
+    }
+    if let Some(date) = &config.document.date {
+        out.push_str("#align(right)[");
+        escape_text(date, out);
+        out.push_str("]\n\n");
+    }
+    if let Some(subject) = &letter.subject {
+        out.push_str("#strong[");
+        escape_text(subject, out);
+        out.push_str("]\n\n");
+    }
+}
+
+/// The leading `# Name` of a resume on the left and the contact line below it
+/// in the markdown on the right, one item per line. Returns how many blocks
+/// that took.
+fn emit_resume_header(
+    blocks: &[Block],
+    lines: &[usize],
+    config: &Config,
+    out: &mut String,
+    source_map: &mut SourceMap,
+) -> usize {
+    let Some(name @ Block::Heading { level: 1, .. }) = blocks.first() else {
+        return 0;
+    };
+    if let Some(&line) = lines.first() {
+        source_map.push(out.len(), line);
+    }
+    out.push_str("#grid(columns: (1fr, auto), align: (left + bottom, right + bottom),\n[\n");
+    emit_heading(name, config, out);
+    out.push_str("],\n[");
+    let contact = match blocks.get(1) {
+        Some(Block::Paragraph { content }) => {
+            if let Some(&line) = lines.get(1) {
+                source_map.push(out.len(), line);
+            }
+            for (i, item) in contact_items(content).iter().enumerate() {
+                if i > 0 {
+                    out.push_str(" \\\n");
+                }
+                spans_to_typst(item, config, out);
+            }
+            1
+        }
+        _ => 0,
+    };
+    out.push_str("])\n#line(length: 100%, stroke: 0.5pt)\n\n");
+    1 + contact
+}
+
+/// A contact line split at `·`, `|` and `•` separators.
+fn contact_items(spans: &[Span]) -> Vec<Vec<Span>> {
+    let mut items = Vec::new();
+    let mut item = Vec::new();
+    for span in spans {
+        let Span::Text(text) = span else {
+            item.push(span.clone());
+            continue;
+        };
+        let parts: Vec<&str> = text.split(['·', '|', '•']).collect();
+        for (i, part) in parts.iter().enumerate() {
+            // Spacing around a separator goes with it
+            let mut part = *part;
+            if i > 0 {
+                items.push(std::mem::take(&mut item));
+                part = part.trim_start();
+            }
+            if i + 1 < parts.len() {
+                part = part.trim_end();
+            }
+            if !part.is_empty() {
+                item.push(Span::Text(part.to_string()));
+            }
+        }
+    }
+    items.push(item);
+    items.retain(|item| !item.is_empty());
+    items
+}
+
+/// Text with a line break for each of its lines.
+fn emit_lines(text: &str, out: &mut String) {
+    let lines: Vec<&str> = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect();
+    for (i, line) in lines.iter().enumerate() {
+        if i > 0 {
+            out.push_str(" \\\n");
+        }
+        escape_text(line, out);
+    }
+}
+
+/// The title, author and date centered above the content.
+fn emit_title_block(config: &Config, out: &mut String) {
+    let document = &config.document;
+    let lines: Vec<&String> = [&document.author, &document.date]
+        .into_iter()
+        .flatten()
+        .collect();
+    if document.title.is_none() && lines.is_empty() {
+        return;
+    }
+    out.push_str("#align(center)[\n");
+    if let Some(title) = &document.title {
+        out.push_str("#text(size: 1.7em, weight: \"bold\")[");
+        escape_text(title, out);
+        out.push_str("]\n\n");
+    }
+    for line in lines {
+        escape_text(line, out);
+        out.push_str("\n\n");
+    }
+    out.push_str("]\n#v(1em)\n\n");
+}
+
+/// The abstract in a centered block, narrower and smaller than the body text.
+fn emit_abstract(text: &str, out: &mut String) {
+    out.push_str("#align(center, block(width: 80%)[\n");
+    out.push_str("#set align(start)\n#set par(justify: true)\n#set text(size: 0.9em)\n");
+    out.push_str("#align(center, text(weight: \"bold\")[Abstract])\n\n");
+    for paragraph in abstract_paragraphs(text) {
+        escape_text(&paragraph, out);
+        out.push_str("\n\n");
+    }
+    out.push_str("])\n\n");
+}
+
+/// Paragraphs of an abstract, separated by blank lines, each on one line.
+pub(crate) fn abstract_paragraphs(text: &str) -> Vec<String> {
+    text.split("\n\n")
+        .map(|paragraph| paragraph.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|paragraph| !paragraph.is_empty())
+        .collect()
 }
 
 /// Remove trailing horizontal rule if present (redundant before page breaks)
@@ -134,16 +883,29 @@ fn count_section_lines(blocks: &[Block], start: usize) -> usize {
             Block::List(list) => {
                 lines += count_list_lines(list);
             }
-            Block::Table { headers, rows } => {
+            Block::Table { headers, rows, .. } => {
                 lines += 1 + headers.len() + rows.len();
             }
+            Block::Image { .. } => {
+                lines += 15;
+            }
             Block::Rule => {
                 lines += 1;
             }
             Block::Heading { .. } => {
                 lines += 2; // Heading + spacing
             }
-            Block::PageBreak => {}
+            Block::QrCode { .. } => {
+                lines += 5;
+            }
+            Block::Diagram { .. } => {
+                lines += 15;
+            }
+            Block::PageBreak
+            | Block::MainMatter
+            | Block::RegionStart(_)
+            | Block::RegionEnd(_)
+            | Block::RestartNumbers { .. } => {}
         }
     }
 
@@ -156,10 +918,74 @@ fn span_char_count(span: &Span) -> usize {
         Span::Bold(inner) | Span::Italic(inner) => inner.iter().map(span_char_count).sum(),
         Span::Code(t) => t.len(),
         Span::Link { content, .. } => content.iter().map(span_char_count).sum(),
+        Span::Reference(label) => label.len(),
         Span::LineBreak => 1,
+        // In the margin, not in the lines of the body
+        Span::Sidenote(_) => 0,
     }
 }
 
+fn is_reference(span: &Span) -> bool {
+    matches!(span, Span::Reference(_))
+}
+
+fn is_sidenote(span: &Span) -> bool {
+    matches!(span, Span::Sidenote(_))
+}
+
+/// Whether any inline content of the block, at any depth, satisfies `matches`.
+fn block_has_span(block: &Block, matches: fn(&Span) -> bool) -> bool {
+    match block {
+        Block::Heading { content, .. } | Block::Paragraph { content } => {
+            spans_have(content, matches)
+        }
+        Block::List(list) => list_has_span(list, matches),
+        Block::Table {
+            headers,
+            rows,
+            caption,
+            ..
+        } => {
+            headers.iter().any(|cell| spans_have(cell, matches))
+                || rows.iter().flatten().any(|cell| spans_have(cell, matches))
+                || caption
+                    .as_deref()
+                    .is_some_and(|caption| spans_have(caption, matches))
+        }
+        Block::CodeBlock { .. }
+        | Block::Image { .. }
+        | Block::Rule
+        | Block::PageBreak
+        | Block::MainMatter
+        | Block::RegionStart(_)
+        | Block::RegionEnd(_)
+        | Block::RestartNumbers { .. }
+        | Block::QrCode { .. }
+        | Block::Diagram { .. } => false,
+    }
+}
+
+fn list_has_span(list: &List, matches: fn(&Span) -> bool) -> bool {
+    list.items.iter().any(|item| {
+        spans_have(&item.content, matches)
+            || item
+                .nested
+                .as_deref()
+                .is_some_and(|nested| list_has_span(nested, matches))
+    })
+}
+
+fn spans_have(spans: &[Span], matches: fn(&Span) -> bool) -> bool {
+    spans.iter().any(|span| {
+        matches(span)
+            || match span {
+                Span::Bold(inner) | Span::Italic(inner) => spans_have(inner, matches),
+                Span::Link { content, .. } => spans_have(content, matches),
+                _ => false,
+            }
+    })
+}
+
 fn count_list_lines(list: &List) -> usize {
     let mut lines = 0;
     for item in &list.items {
@@ -171,15 +997,79 @@ fn count_list_lines(list: &List) -> usize {
     lines
 }
 
-fn emit_heading(block: &Block, out: &mut String) {
-    if let Block::Heading { level, content } = block {
+/// Emit a paragraph starting with a drop cap, handing `drop-cap` its words one
+/// by one so it can fit the first lines beside the letter. Opening quotes go
+/// with the letter. `false` if the paragraph doesn't start with plain text
+/// beginning with a letter or digit, which then needs emitting as usual.
+fn emit_drop_cap(content: &[Span], config: &Config, out: &mut String) -> bool {
+    let Some((Span::Text(text), rest)) = content.split_first() else {
+        return false;
+    };
+    let Some((start, letter)) = text
+        .char_indices()
+        .find(|(_, ch)| !matches!(ch, '"' | '\'' | '“' | '‘' | '„' | '«' | '('))
+        .filter(|(_, ch)| ch.is_alphanumeric())
+    else {
+        return false;
+    };
+    let (initial, text) = text.split_at(start + letter.len_utf8());
+
+    let mut words = Vec::new();
+    let mut word = String::new();
+    push_words(text, &mut word, &mut words);
+    for span in rest {
+        match span {
+            Span::Text(text) => push_words(text, &mut word, &mut words),
+            Span::LineBreak => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+                words.push("#linebreak()".to_string());
+            }
+            // Formatting, links and notes stay attached to the word they're in
+            _ => span_to_typst(span, config, &mut word),
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+
+    out.push_str(&format!("#drop-cap(\"{}\", (", escape_string(initial)));
+    for word in words {
+        out.push_str(&format!("[{}], ", word));
+    }
+    out.push_str("))\n\n");
+    true
+}
+
+/// Add the words of `text` to `words`; the first continues `word`, the last is
+/// left in it, as the next span may continue it. Non-breaking spaces don't
+/// separate words.
+fn push_words(text: &str, word: &mut String, words: &mut Vec<String>) {
+    for (i, piece) in text
+        .split(|ch: char| ch.is_whitespace() && !matches!(ch, '\u{a0}' | '\u{202f}'))
+        .enumerate()
+    {
+        if i > 0 && !word.is_empty() {
+            words.push(std::mem::take(word));
+        }
+        escape_text(piece, word);
+    }
+}
+
+fn emit_heading(block: &Block, config: &Config, out: &mut String) {
+    if let Block::Heading { level, content, id } = block {
         for _ in 0..*level {
             out.push('=');
         }
         out.push(' ');
-        spans_to_typst(content, out);
-        // Add a label for internal linking based on heading text
-        let label = heading_to_label(content);
+        spans_to_typst(content, config, out);
+        // Add a label for internal linking: explicit `{#id}` or derived from heading
+        // text. An id Typst can't take is left out (`check` reports it).
+        let label = id
+            .clone()
+            .filter(|id| parser::is_label(id))
+            .unwrap_or_else(|| heading_to_label(content));
         if !label.is_empty() {
             out.push(' ');
             out.push('<');
@@ -192,7 +1082,7 @@ fn emit_heading(block: &Block, out: &mut String) {
 }
 
 /// Convert heading content to a URL-style label (lowercase, hyphens for spaces)
-fn heading_to_label(spans: &[Span]) -> String {
+pub(crate) fn heading_to_label(spans: &[Span]) -> String {
     let mut text = String::new();
     collect_span_text(spans, &mut text);
 
@@ -209,45 +1099,116 @@ fn heading_to_label(spans: &[Span]) -> String {
         .collect()
 }
 
+/// Replace the tabs in code with spaces up to the next multiple of `width`
+/// columns, if set.
+pub(crate) fn expand_tabs(code: &str, width: Option<usize>) -> Cow<'_, str> {
+    let Some(width) = width.filter(|_| code.contains('\t')) else {
+        return Cow::Borrowed(code);
+    };
+    let width = width.max(1);
+    let mut out = String::with_capacity(code.len());
+    let mut column = 0;
+    for ch in code.chars() {
+        match ch {
+            '\t' => {
+                let spaces = width - column % width;
+                out.extend(std::iter::repeat_n(' ', spaces));
+                column += spaces;
+            }
+            '\n' => {
+                out.push(ch);
+                column = 0;
+            }
+            _ => {
+                out.push(ch);
+                column += 1;
+            }
+        }
+    }
+    Cow::Owned(out)
+}
+
 /// Recursively collect plain text from spans
-fn collect_span_text(spans: &[Span], out: &mut String) {
+pub(crate) fn collect_span_text(spans: &[Span], out: &mut String) {
     for span in spans {
         match span {
             Span::Text(t) => out.push_str(t),
             Span::Bold(inner) | Span::Italic(inner) => collect_span_text(inner, out),
             Span::Code(t) => out.push_str(t),
             Span::Link { content, .. } => collect_span_text(content, out),
+            Span::Reference(label) => out.push_str(label),
             Span::LineBreak => out.push(' '),
+            // Not part of the running text
+            Span::Sidenote(_) => {}
         }
     }
 }
 
-fn emit_block(block: &Block, out: &mut String) {
+fn emit_block(block: &Block, config: &Config, out: &mut String) {
     match block {
         Block::Heading { .. } => {
-            emit_heading(block, out);
+            emit_heading(block, config, out);
         }
         Block::Paragraph { content } => {
-            spans_to_typst(content, out);
+            spans_to_typst(content, config, out);
             out.push('\n');
             out.push('\n');
         }
-        Block::CodeBlock { language, content } => {
+        Block::CodeBlock {
+            language,
+            content,
+            title,
+        } => {
             // Keep small code blocks together, allow large ones to break
+            let content = &*expand_tabs(content, config.code.tab_width);
             let line_count = content.lines().count();
             if line_count <= 20 {
                 out.push_str("#block(breakable: false)[\n");
             }
-            out.push_str("```");
-            if let Some(lang) = language {
-                out.push_str(lang);
+            let listing = title.as_ref().filter(|_| config.code.number_listings);
+            if let Some(title) = listing {
+                out.push_str("#figure(kind: raw, caption: figure.caption(position: top)[");
+                escape_text(title, out);
+                out.push_str("])[\n");
+            } else if let Some(title) = title {
+                let bar = if config.print.ink_saver {
+                    format!("stroke: (bottom: {THIN_RULE})")
+                } else {
+                    "fill: luma(235)".to_string()
+                };
+                out.push_str(&format!(
+                    "#block(width: 100%, {}, inset: (x: 0.6em, y: 0.4em), below: 0.4em, text(size: 0.8em, raw(\"{}\")))\n",
+                    bar,
+                    escape_string(title)
+                ));
             }
-            out.push('\n');
-            out.push_str(content);
-            if !content.ends_with('\n') {
+            if let Some(overflow) = config.code.overflow {
+                let lang = match language {
+                    Some(lang) => format!("\"{}\"", escape_string(lang)),
+                    None => "none".to_string(),
+                };
+                let code = escape_string(content.strip_suffix('\n').unwrap_or(content))
+                    .replace('\n', "\\n");
+                out.push_str(&format!("#code-block({}, \"{}\"", lang, code));
+                if overflow == CodeOverflow::ScrollNote {
+                    out.push_str(&format!(", metadata(\"{}\")", CODE_OVERFLOW_MARKER));
+                }
+                out.push_str(")\n");
+            } else {
+                out.push_str("```");
+                if let Some(lang) = language {
+                    out.push_str(lang);
+                }
                 out.push('\n');
+                out.push_str(content);
+                if !content.ends_with('\n') {
+                    out.push('\n');
+                }
+                out.push_str("```\n");
+            }
+            if listing.is_some() {
+                out.push_str("]\n");
             }
-            out.push_str("```\n");
             if line_count <= 20 {
                 out.push_str("]\n");
             }
@@ -258,19 +1219,91 @@ fn emit_block(block: &Block, out: &mut String) {
             let item_count = count_list_items(list);
             if item_count <= 5 {
                 out.push_str("#block(breakable: false)[\n");
-                list_to_typst(list, 0, out);
+                list_to_typst(list, 0, config, out);
                 out.push_str("]\n\n");
             } else {
-                list_to_typst(list, 0, out);
+                list_to_typst(list, 0, config, out);
                 out.push('\n');
             }
         }
-        Block::Table { headers, rows } => {
-            // Keep tables together when possible
-            out.push_str("#block(breakable: false)[\n");
-            table_to_typst(headers, rows, out);
+        Block::Table {
+            headers,
+            rows,
+            footer,
+            align,
+            caption,
+            label,
+            style,
+        } => {
+            let tables = config.tables.with_options(style);
+            // Keep tables together when possible; one with a footer may break
+            // across pages, repeating the footer on each
+            if *footer == 0 {
+                out.push_str("#block(breakable: false)[\n");
+            } else {
+                out.push_str("#block[\n");
+            }
+            if let Some(size) = &tables.font_size {
+                out.push_str(&format!("#show table: set text(size: {})\n", size));
+            }
+            if let Some(caption) = caption {
+                // Captioned tables become numbered figures so they can be referenced
+                let mut table = String::new();
+                table_to_typst(headers, rows, *footer, align, &tables, config, &mut table);
+                out.push_str("#figure(\n");
+                out.push_str(table.trim_start_matches('#'));
+                out.push_str(", caption: [");
+                spans_to_typst(caption, config, out);
+                out.push_str("])");
+                if let Some(label) = label.as_ref().filter(|label| parser::is_label(label)) {
+                    out.push_str(&format!(" <{}>", label));
+                }
+                out.push('\n');
+            } else {
+                table_to_typst(headers, rows, *footer, align, &tables, config, out);
+            }
             out.push_str("]\n\n");
         }
+        Block::Image {
+            path,
+            alt,
+            title,
+            label,
+        } => {
+            // The file itself is supplied to Typst by the renderer's resource resolver
+            let path = if data_uri::is_data_uri(path) {
+                data_uri::file_name(path)
+            } else {
+                path.clone()
+            };
+            let mut image = format!("image(\"{}\"", escape_string(&path));
+            // Alt text ends up in the tagged PDF; the caption is the best substitute
+            if let Some(alt) = Some(alt.as_str())
+                .filter(|alt| !alt.is_empty())
+                .or(title.as_deref())
+            {
+                image.push_str(&format!(", alt: \"{}\"", escape_string(alt)));
+            }
+            image.push(')');
+            // Captioned or labeled images become numbered figures
+            let label = label.as_ref().filter(|label| parser::is_label(label));
+            match (title, label) {
+                (None, None) => out.push_str(&format!("#align(center, {})\n\n", image)),
+                _ => {
+                    out.push_str(&format!("#figure({}", image));
+                    if let Some(title) = title {
+                        out.push_str(", caption: [");
+                        escape_text(title, out);
+                        out.push(']');
+                    }
+                    out.push(')');
+                    if let Some(label) = label {
+                        out.push_str(&format!(" <{}>", label));
+                    }
+                    out.push_str("\n\n");
+                }
+            }
+        }
         Block::Rule => {
             out.push_str("#line(length: 100%)\n\n");
         }
@@ -278,6 +1311,63 @@ fn emit_block(block: &Block, out: &mut String) {
             strip_trailing_rule(out);
             out.push_str("#pagebreak()\n\n");
         }
+        // Changing the page settings starts a new page
+        Block::MainMatter => {
+            strip_trailing_rule(out);
+            out.push_str("#set page(numbering: \"1\")\n#counter(page).update(1)\n\n");
+        }
+        // Takes effect at the headings that follow
+        Block::RestartNumbers { .. } => {}
+        Block::RegionStart(Region::Keep) => out.push_str("#block(breakable: false)[\n"),
+        // A floating placement is the only way out of the page's columns; in a
+        // single column the region is laid out like the blocks around it
+        Block::RegionStart(Region::Wide) => {
+            if has_columns(config) {
+                out.push_str("#place(auto, scope: \"parent\", float: true)[\n");
+            }
+        }
+        // The stroke sits in the outset, left of the text
+        Block::RegionStart(Region::Changed) => out.push_str(&format!(
+            "#block(width: 100%, stroke: (left: 2pt + {}), outset: (left: 0.8em))[\n",
+            color(config, CHANGE_BAR_COLOR)
+        )),
+        Block::RegionStart(Region::Details) => out.push_str(
+            "#block(width: 100%, stroke: (left: 0.5pt + gray), inset: (left: 1em, y: 0.3em))[\n",
+        ),
+        Block::RegionEnd(Region::Keep | Region::Changed | Region::Details) => out.push_str("]\n\n"),
+        Block::RegionEnd(Region::Wide) => {
+            if has_columns(config) {
+                out.push_str("]\n\n");
+            }
+        }
+        Block::QrCode { data, size } => match diagram::render_qr(data) {
+            Ok(svg) => {
+                out.push_str(&format!(
+                    "#image(bytes(\"{}\"), format: \"svg\", width: {}, alt: \"QR code: {}\")\n\n",
+                    escape_string(&svg),
+                    size.as_deref().unwrap_or("3cm"),
+                    escape_string(data)
+                ));
+            }
+            Err(e) => {
+                out.push_str(&format!("#text(fill: {})[", color(config, "red")));
+                escape_text(&e, out);
+                out.push_str("]\n\n");
+            }
+        },
+        Block::Diagram { code, svg } => match svg {
+            Ok(svg) => out.push_str(&format!(
+                "#align(center, image(bytes(\"{}\"), format: \"svg\", alt: \"Diagram\"))\n\n",
+                escape_string(&svg::with_font_fallback(svg))
+            )),
+            Err(e) => {
+                // Show the error above the source instead of dropping the diagram
+                out.push_str(&format!("#text(fill: {})[", color(config, "red")));
+                escape_text(e, out);
+                out.push_str("]\n\n");
+                emit_block(code, config, out);
+            }
+        },
     }
 }
 
@@ -291,34 +1381,25 @@ fn count_list_items(list: &List) -> usize {
     count
 }
 
-fn spans_to_typst(spans: &[Span], out: &mut String) {
+fn spans_to_typst(spans: &[Span], config: &Config, out: &mut String) {
     for span in spans {
-        span_to_typst(span, out);
+        span_to_typst(span, config, out);
     }
 }
 
-fn span_to_typst(span: &Span, out: &mut String) {
+fn span_to_typst(span: &Span, config: &Config, out: &mut String) {
     match span {
         Span::Text(text) => {
-            // Escape special Typst characters
-            for ch in text.chars() {
-                match ch {
-                    '#' | '*' | '_' | '@' | '$' | '\\' | '`' | '<' | '>' | '[' | ']' => {
-                        out.push('\\');
-                        out.push(ch);
-                    }
-                    _ => out.push(ch),
-                }
-            }
+            escape_text(text, out);
         }
         Span::Bold(inner) => {
             out.push('*');
-            spans_to_typst(inner, out);
+            spans_to_typst(inner, config, out);
             out.push('*');
         }
         Span::Italic(inner) => {
             out.push('_');
-            spans_to_typst(inner, out);
+            spans_to_typst(inner, config, out);
             out.push('_');
         }
         Span::Code(text) => {
@@ -333,28 +1414,74 @@ fn span_to_typst(span: &Span, out: &mut String) {
                 out.push_str("#link(<");
                 out.push_str(anchor);
                 out.push_str(">)[");
-                spans_to_typst(content, out);
+                spans_to_typst(content, config, out);
                 out.push(']');
             } else {
                 // External link
+                let escaped_url = escape_string(url);
                 out.push_str("#link(\"");
-                out.push_str(&url.replace('\\', "\\\\").replace('"', "\\\""));
+                out.push_str(&escaped_url);
                 out.push_str("\")[");
-                spans_to_typst(content, out);
+                spans_to_typst(content, config, out);
                 out.push(']');
+
+                // Make the URL visible on paper, unless the link text already is the URL
+                let mut text = String::new();
+                collect_span_text(content, &mut text);
+                if text != *url {
+                    match config.links.print_urls {
+                        PrintUrls::None => {}
+                        PrintUrls::Inline => {
+                            out.push_str(" (#link(\"");
+                            out.push_str(&escaped_url);
+                            out.push_str("\"))");
+                        }
+                        PrintUrls::Footnote => {
+                            out.push_str("#footnote[#link(\"");
+                            out.push_str(&escaped_url);
+                            out.push_str("\")]");
+                        }
+                    }
+                }
             }
         }
+        Span::Reference(label) => {
+            out.push_str(&format!("#ref(<{}>)", label));
+        }
         Span::LineBreak => {
             out.push_str(" \\\n");
         }
+        Span::Sidenote(text) => {
+            out.push_str("#sidenote[");
+            escape_text(text, out);
+            out.push(']');
+        }
+    }
+}
+
+/// Escape special Typst characters in markup text
+fn escape_text(text: &str, out: &mut String) {
+    for ch in text.chars() {
+        match ch {
+            '#' | '*' | '_' | '@' | '$' | '\\' | '`' | '<' | '>' | '[' | ']' => {
+                out.push('\\');
+                out.push(ch);
+            }
+            _ => out.push(ch),
+        }
     }
 }
 
-fn list_to_typst(list: &List, indent: usize, out: &mut String) {
+/// Escape text for use inside a Typst string literal
+fn escape_string(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn list_to_typst(list: &List, indent: usize, config: &Config, out: &mut String) {
     let prefix = if list.ordered { "+" } else { "-" };
     let indent_str: String = "  ".repeat(indent);
 
-    for item in &list.items {
+    for (i, item) in list.items.iter().enumerate() {
         out.push_str(&indent_str);
         // Task list items: use checkbox instead of bullet
         if let Some(checked) = item.checked {
@@ -363,17 +1490,22 @@ fn list_to_typst(list: &List, indent: usize, out: &mut String) {
             } else {
                 out.push_str("#box(inset: (x: 2pt))[#text(1.2em)[#sym.ballot]] ");
             }
-            spans_to_typst(&item.content, out);
+            spans_to_typst(&item.content, config, out);
             out.push_str("\\\n");
         } else {
-            out.push_str(prefix);
+            // An explicit number on the first item, which the others count on from
+            if i == 0 && list.ordered && list.start != 1 {
+                out.push_str(&format!("{}.", list.start));
+            } else {
+                out.push_str(prefix);
+            }
             out.push(' ');
-            spans_to_typst(&item.content, out);
+            spans_to_typst(&item.content, config, out);
             out.push('\n');
         }
 
         if let Some(ref nested) = item.nested {
-            list_to_typst(nested, indent + 1, out);
+            list_to_typst(nested, indent + 1, config, out);
         }
     }
 }
@@ -389,7 +1521,15 @@ fn is_row_empty(row: &[Vec<Span>]) -> bool {
     })
 }
 
-fn table_to_typst(headers: &[Vec<Span>], rows: &[Vec<Vec<Span>>], out: &mut String) {
+fn table_to_typst(
+    headers: &[Vec<Span>],
+    rows: &[Vec<Vec<Span>>],
+    footer: usize,
+    align: &[Alignment],
+    tables: &TablesConfig,
+    config: &Config,
+    out: &mut String,
+) {
     let col_count = headers.len();
     if col_count == 0 {
         return;
@@ -399,35 +1539,80 @@ fn table_to_typst(headers: &[Vec<Span>], rows: &[Vec<Vec<Span>>], out: &mut Stri
     let has_headers = !is_row_empty(headers);
 
     out.push_str("#table(\n");
-    out.push_str(&format!("  columns: {},\n", col_count));
+    match tables.width {
+        TableWidth::Auto => out.push_str(&format!("  columns: {},\n", col_count)),
+        TableWidth::Full => out.push_str(&format!("  columns: (1fr,) * {},\n", col_count)),
+    }
+    if tables.striped && !config.print.ink_saver {
+        // Shade the first body row and every other one after it
+        let first = if has_headers { "odd" } else { "even" };
+        out.push_str(&format!(
+            "  fill: (_, y) => if calc.{}(y) {{ luma(242) }},\n",
+            first
+        ));
+    }
+    if align.iter().any(|a| *a != Alignment::None) {
+        let aligns: Vec<&str> = (0..col_count)
+            .map(|i| match align.get(i) {
+                Some(Alignment::Left) => "left",
+                Some(Alignment::Center) => "center",
+                Some(Alignment::Right) => "right",
+                Some(Alignment::None) | None => "auto",
+            })
+            .collect();
+        out.push_str(&format!("  align: ({}),\n", aligns.join(", ")));
+    }
 
     // Header cells (bold) - only if not empty
     if has_headers {
         for cell in headers {
             out.push_str("  [*");
-            spans_to_typst(cell, out);
+            spans_to_typst(cell, config, out);
             out.push_str("*],\n");
         }
     }
 
     // Data rows - skip empty rows
-    for row in rows {
+    let (body, foot) = rows.split_at(rows.len().saturating_sub(footer));
+    for row in body {
         if is_row_empty(row) {
             continue;
         }
         for cell in row {
             out.push_str("  [");
-            spans_to_typst(cell, out);
+            spans_to_typst(cell, config, out);
             out.push_str("],\n");
         }
     }
 
+    // Footer rows (bold, below a heavier rule), repeated on every page
+    if !foot.is_empty() {
+        let rule = if config.print.ink_saver {
+            THIN_RULE
+        } else {
+            "1.5pt"
+        };
+        out.push_str(&format!(
+            "  table.footer(\n    table.hline(stroke: {}),\n",
+            rule
+        ));
+        for cell in foot.iter().flatten() {
+            out.push_str("    [#strong[");
+            spans_to_typst(cell, config, out);
+            out.push_str("]],\n");
+        }
+        out.push_str("  ),\n");
+    }
+
     out.push_str(")\n");
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::markdown_to_typst;
+    use super::blocks_to_typst_mapped;
+    use crate::config::{CodeOverflow, Config, FootnoteNumbering, Mode, PrintUrls, Theme};
+    use crate::resource::ResourceResolver;
+    use crate::{markdown_to_typst, markdown_to_typst_with_config};
 
     const PREAMBLE: &str = "#set par(linebreaks: \"optimized\")\n#show link: it => underline(text(fill: rgb(\"#1a4f8b\"), it))\n\n";
 
@@ -486,6 +1671,52 @@ mod tests {
         );
     }
 
+    #[test]
+    fn code_block_title() {
+        let markdown = "```rust title=\"src/main.rs\"\nfn main() {}\n```";
+        assert_eq!(
+            markdown_to_typst(markdown),
+            format!(
+                "{PREAMBLE}#block(breakable: false)[\n#block(width: 100%, fill: luma(235), inset: (x: 0.6em, y: 0.4em), below: 0.4em, text(size: 0.8em, raw(\"src/main.rs\")))\n```rust\nfn main() {{}}\n```\n]\n\n"
+            )
+        );
+
+        let mut config = Config::compiled_default();
+        config.code.number_listings = true;
+        let result = markdown_to_typst_with_config(markdown, &config);
+        assert!(result.contains("#figure(kind: raw, caption: figure.caption(position: top)[src/main.rs])[\n```rust\nfn main() {}\n```\n]\n"));
+        // Untitled code stays as it is
+        assert!(
+            !markdown_to_typst_with_config("```rust\nfn main() {}\n```", &config)
+                .contains("figure")
+        );
+    }
+
+    #[test]
+    fn code_overflow() {
+        let markdown = "```rust\nlet s = \"a\\tb\";\n```";
+        let mut config = Config::compiled_default();
+        config.code.overflow = Some(CodeOverflow::Wrap);
+        let result = markdown_to_typst_with_config(markdown, &config);
+        assert!(result.contains("#let code-block(lang, code) = layout(size => { let columns"));
+        assert!(result.contains("join(\"\\n↪ \")"));
+        assert!(result.contains("#code-block(\"rust\", \"let s = \\\"a\\\\tb\\\";\")\n"));
+
+        config.code.overflow = Some(CodeOverflow::ScrollNote);
+        let result = markdown_to_typst_with_config("```\none\ntwo\n```", &config);
+        assert!(result.contains("#code-block(none, \"one\\ntwo\", metadata(\"code-overflow\"))\n"));
+        assert!(!markdown_to_typst("```\ncode\n```").contains("code-block"));
+    }
+
+    #[test]
+    fn tab_width() {
+        let mut config = Config::compiled_default();
+        config.code.tab_width = Some(4);
+        let result = markdown_to_typst_with_config("```make\nall:\n\tgo build\nx\ty\n```", &config);
+        assert!(result.contains("```make\nall:\n    go build\nx   y\n```"));
+        assert!(markdown_to_typst("```\n\tx\n```").contains("\tx"));
+    }
+
     #[test]
     fn long_code_block() {
         // Long code blocks (>20 lines) are allowed to break across pages
@@ -516,6 +1747,15 @@ mod tests {
         );
     }
 
+    #[test]
+    fn ordered_list_start() {
+        assert_eq!(
+            markdown_to_typst("5. five\n6. six"),
+            format!("{PREAMBLE}#block(breakable: false)[\n5. five\n+ six\n]\n\n")
+        );
+        assert!(markdown_to_typst("0. zero").contains("0. zero\n"));
+    }
+
     #[test]
     fn hard_break() {
         assert_eq!(
@@ -540,6 +1780,763 @@ mod tests {
         assert_eq!(markdown_to_typst(md), expected);
     }
 
+    #[test]
+    fn link_urls_for_print() {
+        let md = "[docs](https://example.com)";
+        let mut config = Config::compiled_default();
+
+        config.links.print_urls = PrintUrls::Footnote;
+        assert!(markdown_to_typst_with_config(md, &config).contains(
+            "#link(\"https://example.com\")[docs]#footnote[#link(\"https://example.com\")]"
+        ));
+
+        config.links.print_urls = PrintUrls::Inline;
+        assert!(
+            markdown_to_typst_with_config(md, &config)
+                .contains("#link(\"https://example.com\")[docs] (#link(\"https://example.com\"))")
+        );
+
+        // Autolinks already show their URL
+        let result = markdown_to_typst_with_config("<https://example.com>", &config);
+        assert!(!result.contains(" (#link"));
+    }
+
+    #[test]
+    fn show_rule_overrides() {
+        let mut config = Config::compiled_default();
+        config.overrides.heading = Some("set text(fill: red)".to_string());
+        config.overrides.list = Some("it => pad(left: 1em, it)".to_string());
+        let result = markdown_to_typst_with_config("text", &config);
+        assert!(result.contains("#show heading: set text(fill: red)\n"));
+        assert!(result.contains("#show list: it => pad(left: 1em, it)\n"));
+        assert!(result.contains("#show enum: it => pad(left: 1em, it)\n"));
+        assert!(result.ends_with("\n\ntext\n\n"));
+    }
+
+    #[test]
+    fn document_metadata() {
+        let mut config = Config::compiled_default();
+        config.document.title = Some("Q3 \"Report\"".to_string());
+        config.document.author = Some("Ann #1".to_string());
+        let result = markdown_to_typst_with_config("Text.", &config);
+        assert!(
+            result.contains("#set document(title: \"Q3 \\\"Report\\\"\", author: \"Ann #1\")\n")
+        );
+        assert!(!result.contains("#page("));
+
+        config.document.title_page = true;
+        let result = markdown_to_typst_with_config("Text.", &config);
+        assert!(result.contains(
+            "#page(numbering: none, align(center + horizon)[\n#text(size: 2em, weight: \"bold\")[Q3 \"Report\"]\n\nAnn \\#1\n])\n\nText."
+        ));
+    }
+
+    #[test]
+    fn title_block() {
+        let mut config = Config::compiled_default();
+        config.document.title = Some("Report".to_string());
+        config.document.date = Some("March 2025".to_string());
+        assert!(!markdown_to_typst_with_config("Text.", &config).contains("#align(center)["));
+
+        config.document.title_block = true;
+        let result = markdown_to_typst_with_config("Text.", &config);
+        assert!(result.contains(
+            "#align(center)[\n#text(size: 1.7em, weight: \"bold\")[Report]\n\nMarch 2025\n\n]\n#v(1em)\n\nText."
+        ));
+
+        // The title page takes its place
+        config.document.title_page = true;
+        let result = markdown_to_typst_with_config("Text.", &config);
+        assert!(!result.contains("#align(center)["));
+        assert!(result.contains("[Report]\n\n\nMarch 2025\n])"));
+    }
+
+    #[test]
+    fn letter() {
+        let mut config = Config::compiled_default();
+        config.document.theme = Theme::Letter;
+        config.document.title_page = true;
+        config.document.date = Some("1 March 2026".to_string());
+        config.letter.sender = Some("Ann\nStreet 1\n".to_string());
+        config.letter.recipient = Some("Acme Corp\nStreet 2".to_string());
+        config.letter.subject = Some("Offer #2".to_string());
+        config.letter.signature = Some("Ann".to_string());
+        let result = markdown_to_typst_with_config("Dear Bo,\n\nKind regards,", &config);
+        assert!(result.contains(
+            "#align(right, text(size: 0.9em)[Ann \\\nStreet 1])\n\n#block(height: 4cm)[Acme Corp \\\nStreet 2]\n\n#align(right)[1 March 2026]\n\n#strong[Offer \\#2]\n\nDear Bo,"
+        ));
+        assert!(result.ends_with("Kind regards,\n\n#v(2cm)\nAnn\n"));
+        assert!(!result.contains("#page("));
+    }
+
+    #[test]
+    fn slides() {
+        let mut config = Config::compiled_default();
+        config.document.mode = Mode::Slides;
+        let md = "# Talk\n\nIntro\n\n## Point\n\n- One\n\n### Detail\n\nText";
+        let result = markdown_to_typst_with_config(md, &config);
+        assert!(result.contains("#set page(paper: \"presentation-16-9\")\n"));
+        assert_eq!(
+            result
+                .matches("#pagebreak(weak: true)\n#block(breakable: false)[")
+                .count(),
+            2
+        );
+        assert!(!result.contains("height: 30%"));
+        assert!(!result.contains("#pagebreak(weak: true)\n#block(breakable: false)[\n=== Detail"));
+    }
+
+    #[test]
+    fn resume() {
+        let mut config = Config::compiled_default();
+        config.document.theme = Theme::Resume;
+        let md = "# Ann Smith\n\nann@example.com · City · [example.com](https://example.com)\n\n## Experience\n\n- Shipped it";
+        let result = markdown_to_typst_with_config(md, &config);
+        assert!(result.contains("#set list(spacing: 0.5em)\n"));
+        assert!(result.contains(
+            "#grid(columns: (1fr, auto), align: (left + bottom, right + bottom),\n[\n= Ann Smith <ann-smith>\n\n],\n[ann\\@example.com \\\nCity \\\n#link(\"https://example.com\")[example.com]])\n#line(length: 100%, stroke: 0.5pt)\n\n"
+        ));
+        // The rest is laid out as usual
+        assert!(result.contains("= Experience <experience>"));
+
+        // Margins the config needs are set after the theme's
+        config.page.two_sided = true;
+        let result = markdown_to_typst_with_config("Claim.^[margin: Note.]", &config);
+        let theme = result.find("#set page(margin: 1.8cm)").unwrap();
+        assert!(theme < result.find("#set page(margin: (inside: 3cm").unwrap());
+        assert!(theme < result.find("#set page(margin: (outside: 4.5cm").unwrap());
+    }
+
+    #[test]
+    fn abstract_block() {
+        let mut config = Config::compiled_default();
+        config.document.r#abstract = Some("We measure #3\nthings.\n\nThen report.\n".to_string());
+        let result = markdown_to_typst_with_config("Text.", &config);
+        assert!(result.contains(
+            "#align(center, text(weight: \"bold\")[Abstract])\n\nWe measure \\#3 things.\n\nThen report.\n\n])\n\nText."
+        ));
+
+        // Below the document's title, a first heading at the top level
+        let result = markdown_to_typst_with_config("## Section\n\nText.", &config);
+        assert!(result.find("[Abstract]") < result.find("= Section"));
+        let result = markdown_to_typst_with_config("# Title\n\nText.", &config);
+        assert!(result.contains(
+            "#block(breakable: false)[\n= Title <title>\n\n#align(center, block(width: 80%)[\n"
+        ));
+        assert!(result.find("[Abstract]") < result.find("Text."));
+
+        // Below the author on the title page
+        config.document.author = Some("Ann".to_string());
+        config.document.title_page = true;
+        let result = markdown_to_typst_with_config("Text.", &config);
+        assert!(result.contains("Ann\n#v(2em)\n#align(center, block(width: 80%)[\n"));
+        assert_eq!(result.matches("[Abstract]").count(), 1);
+    }
+
+    #[test]
+    fn line_numbers() {
+        let mut config = Config::compiled_default();
+        assert!(!markdown_to_typst_with_config("text", &config).contains("par.line"));
+        config.typography.line_numbers = true;
+        assert!(
+            markdown_to_typst_with_config("text", &config)
+                .contains("#set par.line(numbering: \"1\")\n")
+        );
+        config.typography.line_number_step = Some(5);
+        assert!(
+            markdown_to_typst_with_config("text", &config)
+                .contains("#set par.line(numbering: n => if calc.rem(n, 5) == 0 [#n] else [])\n")
+        );
+    }
+
+    #[test]
+    fn font_family() {
+        let mut config = Config::compiled_default();
+        config.font.sans = true;
+        config.font.family = Some("Inter".to_string());
+        let result = markdown_to_typst_with_config("text", &config);
+        assert!(result.contains("#set text(font: \"Inter\")\n"));
+        assert!(!result.contains("Open Sans"));
+    }
+
+    #[test]
+    fn page_size_and_toc() {
+        let mut config = Config::compiled_default();
+        config.page.size = Some("us-letter".to_string());
+        config.document.toc = true;
+        let result = markdown_to_typst_with_config("# Intro", &config);
+        assert!(result.contains("#set page(paper: \"us-letter\")\n"));
+        assert!(!result.contains("#counter(page)"));
+        assert!(result.contains("\n\n#outline()\n#pagebreak(weak: true)\n\n"));
+
+        config.page.first_number = Some(12);
+        let result = markdown_to_typst_with_config("# Intro", &config);
+        assert!(result.contains("\n\n#counter(page).update(12)\n#outline()"));
+    }
+
+    #[test]
+    fn background_image() {
+        let mut config = Config::compiled_default();
+        config.page.background_image = Some("letterhead.svg".to_string());
+        assert!(markdown_to_typst_with_config("text", &config).contains(
+            "#set page(background: image(\"letterhead.svg\", width: 100%, height: 100%))\n"
+        ));
+
+        config.page.background_first_page_only = true;
+        assert!(markdown_to_typst_with_config("text", &config).contains(
+            "#set page(background: context if here().page() == 1 { image(\"letterhead.svg\", width: 100%, height: 100%) })\n"
+        ));
+    }
+
+    #[test]
+    fn watermark() {
+        let mut config = Config::compiled_default();
+        config.page.watermark = Some("DRAFT \"v2\"".to_string());
+        assert!(markdown_to_typst_with_config("text", &config).contains(
+            "#set page(foreground: place(center + horizon, rotate(-45deg, text(size: 72pt, fill: gray.transparentize(70%), \"DRAFT \\\"v2\\\"\"))))\n"
+        ));
+    }
+
+    #[test]
+    fn running_header_and_footer() {
+        let mut config = Config::compiled_default();
+        config.header.text = Some("Report".to_string());
+        config.header.footer = Some("Confidential".to_string());
+        let result = markdown_to_typst_with_config("text", &config);
+        assert!(result.contains(
+            "#set page(header: context align(center, stack(..if here().page() == 1 { (text(size: 9pt, \"Report\"),) } else { (text(size: 9pt, \"Report\"),) })))\n"
+        ));
+        assert!(result.contains(
+            "stack(spacing: 0.8em, ..if numbering != none { (counter(page).display(numbering),) }, ..if here().page() == 1 { (text(size: 9pt, \"Confidential\"),) } else {"
+        ));
+
+        config.header.skip_first = true;
+        config.header.first_page_footer = Some("Draft".to_string());
+        let result = markdown_to_typst_with_config("text", &config);
+        assert!(result.contains("stack(..if here().page() == 1 { () } else {"));
+        assert!(
+            result.contains("..if here().page() == 1 { (text(size: 9pt, \"Draft\"),) } else {")
+        );
+
+        // Only a first-page line
+        config.header.text = None;
+        config.header.first_page_header = Some("Acme".to_string());
+        assert!(markdown_to_typst_with_config("text", &config).contains(
+            "stack(..if here().page() == 1 { (text(size: 9pt, \"Acme\"),) } else { () })"
+        ));
+        assert!(!markdown_to_typst("text").contains("#set page(header"));
+
+        config.header.text = Some("Chapter — {section}.".to_string());
+        config.header.section_level = Some(2);
+        let result = markdown_to_typst_with_config("text", &config);
+        assert!(
+            result.contains(
+                "#let section-title() = { let headings = query(heading.where(level: 2));"
+            )
+        );
+        assert!(result.contains("else { (text(size: 9pt)[Chapter — #section-title();.],) }"));
+    }
+
+    #[test]
+    fn two_sided() {
+        let mut config = Config::compiled_default();
+        config.page.two_sided = true;
+        let result = markdown_to_typst_with_config("Claim.^[margin: Note.]", &config);
+        assert!(result.contains("#set page(margin: (inside: 3cm, outside: 2cm))\n"));
+        assert!(result.contains("#set page(margin: (outside: 4.5cm + 2 * 0.8cm))\n"));
+        assert!(result.contains("place(if recto { right } else { left }"));
+        // The page number moves to the outside edge
+        assert!(result.contains(
+            "align(if calc.odd(here().page()) { right } else { left }, stack(spacing: 0.8em, ..if numbering != none"
+        ));
+    }
+
+    #[test]
+    fn bleed_and_crop_marks() {
+        let mut config = Config::compiled_default();
+        config.page.bleed = Some("3mm".to_string());
+        let result = markdown_to_typst_with_config("Text", &config);
+        assert!(result.contains("#show: body => context { let extent = 3mm;"));
+        assert!(!result.contains("crop-marks"));
+
+        config.page.crop_marks = true;
+        let result = markdown_to_typst_with_config("Text", &config);
+        assert!(result.contains("let extent = 3mm + 8mm;"));
+        assert!(result.contains(
+            "foreground: { page.foreground; crop-marks(width, height, extent) }); body }\n"
+        ));
+        assert!(result.contains("#let crop-marks(width, height, extent) = "));
+    }
+
+    #[test]
+    fn grayscale() {
+        let mut config = Config::compiled_default();
+        config.print.grayscale = true;
+        config.diagrams.graphviz = true;
+        config.diagrams.dot_path = "nonexistent-dot-binary".to_string();
+        let md = "<!-- changed -->\n\nSee [docs](https://example.com).\n\n<!-- /changed -->\n\n```dot\ndigraph { a -> b }\n```";
+        let result = markdown_to_typst_with_config(md, &config);
+        assert!(result.contains("#set raw(theme: none)\n"));
+        assert!(result.contains("text(fill: luma(rgb(\"#1a4f8b\")), it)"));
+        assert!(result.contains("stroke: (left: 2pt + luma(rgb(\"#d33\")))"));
+        assert!(result.contains("#text(fill: luma(red))[Failed to run"));
+    }
+
+    #[test]
+    fn ink_saver() {
+        let mut config = Config::compiled_default();
+        config.overrides.raw = Some("it => block(fill: luma(240), inset: 6pt, it)".to_string());
+        config.print.ink_saver = true;
+        let result = markdown_to_typst_with_config("Text\n\n---\n", &config);
+        // After the overrides, so it beats fills they set with set rules
+        assert!(result.contains("#show raw: it => block(fill: luma(240), inset: 6pt, it)\n#show block: set block(fill: none)\n"));
+        assert!(result.contains("#show table: set table(fill: none, stroke: 0.5pt)\n#show line: set line(stroke: 0.5pt)\n"));
+
+        // Explicit fills and heavy rules are left out at emission
+        config.tables.striped = true;
+        let result = markdown_to_typst_with_config(
+            "```rust title=\"main.rs\"\nfn main() {}\n```\n\n| A |\n|---|\n| 1 |\n| 2 |\n|===|\n| 3 |\n",
+            &config,
+        );
+        assert!(result.contains("#block(width: 100%, stroke: (bottom: 0.5pt), inset:"));
+        assert!(!result.contains("luma(235)"));
+        assert!(!result.contains("luma(242)"));
+        assert!(result.contains("table.hline(stroke: 0.5pt)"));
+    }
+
+    #[test]
+    fn drop_caps() {
+        let mut config = Config::compiled_default();
+        config.typography.drop_caps = true;
+        let result = markdown_to_typst_with_config(
+            "# Chapter\n\n“Once upon **a time**, there\n\n# Next\n\n**Bold** start\n\n## Section\n\nNo cap",
+            &config,
+        );
+        assert!(result.contains("#let drop-cap(letter, words)"));
+        assert!(result.contains("#drop-cap(\"“O\", ([nce], [upon], [*a time*,], [there], ))"));
+        // Only plain text after level 1 headings gets one
+        assert!(result.contains("*Bold* start"));
+        assert!(result.contains("No cap"));
+        assert_eq!(result.matches("#drop-cap(").count(), 1);
+
+        let result =
+            markdown_to_typst_with_config("# Chapter\n\nOnce", &Config::compiled_default());
+        assert!(!result.contains("drop-cap"));
+    }
+
+    #[test]
+    fn tracking() {
+        let mut config = Config::compiled_default();
+        config.typography.tracking = Some("0.01em".to_string());
+        config.typography.h2_tracking = Some("0.05em".to_string());
+        let result = markdown_to_typst_with_config("## Section", &config);
+        assert!(result.contains("#set text(tracking: 0.01em)\n"));
+        assert!(result.contains("#show heading.where(level: 2): set text(tracking: 0.05em)\n"));
+        assert!(!result.contains("level: 1): set text(tracking"));
+    }
+
+    #[test]
+    fn font_features() {
+        let mut config = Config::compiled_default();
+        let features = &mut config.typography.features;
+        features.body = vec!["onum".to_string()];
+        features.h1 = vec!["smcp".to_string(), "c2sc".to_string()];
+        features.tables = vec!["tnum".to_string()];
+        let result = markdown_to_typst_with_config("# Title", &config);
+        assert!(result.contains("#set text(features: (\"onum\",))\n"));
+        assert!(result.contains(
+            "#show heading.where(level: 1): set text(features: (\"smcp\", \"c2sc\",))\n"
+        ));
+        assert!(result.contains("#show table: set text(features: (\"tnum\",))\n"));
+        assert!(!result.contains("#show raw: set text(features"));
+    }
+
+    #[test]
+    fn ligatures() {
+        let mut config = Config::compiled_default();
+        config.typography.features.ligatures = false;
+        config.typography.features.h1 = vec!["smcp".to_string()];
+        let result = markdown_to_typst_with_config("# Title", &config);
+        assert!(result.contains("#set text(features: (\"liga\": 0, \"calt\": 0))\n"));
+        assert!(result.contains(
+            "#show heading.where(level: 1): set text(features: (\"liga\": 0, \"calt\": 0, \"smcp\": 1))\n"
+        ));
+        // Code keeps them unless turned off too
+        assert!(result.contains("#show raw: set text(features: (\"liga\": 1, \"calt\": 1))\n"));
+
+        let mut config = Config::compiled_default();
+        config.typography.features.code_ligatures = false;
+        let result = markdown_to_typst_with_config("# Title", &config);
+        assert!(!result.contains("#set text(features"));
+        assert!(result.contains("#show raw: set text(features: (\"liga\": 0, \"calt\": 0))\n"));
+
+        let result = markdown_to_typst_with_config("# Title", &Config::compiled_default());
+        assert!(!result.contains("features"));
+    }
+
+    #[test]
+    fn footnotes() {
+        let mut config = Config::compiled_default();
+        config.links.print_urls = PrintUrls::Footnote;
+        config.footnotes.numbering = FootnoteNumbering::Symbols;
+        config.footnotes.font_size = Some("8pt".to_string());
+        config.footnotes.separator_length = Some("5cm".to_string());
+        config.footnotes.per_page = true;
+        let result = markdown_to_typst_with_config("[docs](https://example.com)", &config);
+        assert!(result.contains(
+            "#set footnote(numbering: \"*\")\n#show footnote.entry: set text(size: 8pt)\n#set footnote.entry(separator: line(length: 5cm, stroke: 0.5pt))\n"
+        ));
+        assert!(result.contains(
+            "set page(header: { counter(footnote).update(0); if header != auto { header } })"
+        ));
+
+        let result = markdown_to_typst_with_config("Text", &Config::compiled_default());
+        assert!(!result.contains("footnote"));
+    }
+
+    #[test]
+    fn bullets() {
+        let mut config = Config::compiled_default();
+        config.lists.bullets = vec!["•".to_string(), "◦".to_string(), "*".to_string()];
+        let result = markdown_to_typst_with_config("- One\n- Two", &config);
+        assert!(result.contains("#set list(marker: ([•], [◦], [\\*],))\n"));
+
+        let result = markdown_to_typst_with_config("- One", &Config::compiled_default());
+        assert!(!result.contains("marker"));
+    }
+
+    #[test]
+    fn restart_numbers() {
+        let md =
+            "# Report\n\nText\n\n@restart_numbers(1)\n\n# Appendix A\n\n## Data\n\n# Appendix B\n";
+        let result = markdown_to_typst(md);
+        assert_eq!(result.matches("#counter(page).update(1)").count(), 2);
+        assert!(result.contains("#pagebreak(weak: true)\n#counter(page).update(1)\n#block(breakable: false, height: 30%)\n#v(-30%, weak: true)\n#block(breakable: false)[\n= Appendix A"));
+        // Not a heading level: left as text
+        assert!(markdown_to_typst("@restart_numbers(7)").contains("\\@restart\\_numbers(7)"));
+    }
+
+    #[test]
+    fn changed_regions() {
+        let result = markdown_to_typst("<!-- changed -->\n\nNew text.\n\n<!-- /changed -->\n");
+        assert!(result.contains(
+            "#block(width: 100%, stroke: (left: 2pt + rgb(\"#d33\")), outset: (left: 0.8em))[\nNew text.\n\n]\n\n"
+        ));
+    }
+
+    #[test]
+    fn cross_references() {
+        let md = "# Introduction {#sec:intro}\n\nSee [@sec:intro] and [@tbl:results].\n\n| A |\n|---|\n| 1 |\n\nTable: Results {#tbl:results}";
+        let result = markdown_to_typst(md);
+        assert!(result.contains("= Introduction <sec:intro>\n"));
+        assert!(result.contains("See #ref(<sec:intro>) and #ref(<tbl:results>)."));
+        assert!(result.contains("#show ref: it =>"));
+        assert!(result.contains(
+            "#figure(\ntable(\n  columns: 1,\n  [*A*],\n  [1],\n)\n, caption: [Results]) <tbl:results>\n"
+        ));
+        // Brackets that aren't references stay escaped text
+        assert!(markdown_to_typst("[@ not a ref]").contains("\\[\\@ not a ref\\]"));
+
+        let result = markdown_to_typst(
+            "![Flow](flow.svg \"Data flow\"){#fig:flow}\n\n![](arch.svg) {#fig:arch}\n\nSee [@fig:flow].",
+        );
+        assert!(result.contains(
+            "#figure(image(\"flow.svg\", alt: \"Flow\"), caption: [Data flow]) <fig:flow>\n"
+        ));
+        assert!(result.contains("#figure(image(\"arch.svg\")) <fig:arch>\n"));
+        assert!(result.contains("See #ref(<fig:flow>)."));
+
+        // Labels Typst can't take are left out rather than breaking the markup
+        let result = markdown_to_typst(
+            "# Intro {#über}\n\n| A |\n|---|\n| 1 |\n\nTable: Results {#tbl results}\n\n![](x.svg){#fig/x}",
+        );
+        assert!(result.contains("= Intro <intro>\n"));
+        assert!(result.contains(", caption: [Results])\n"));
+        assert!(result.contains("#align(center, image(\"x.svg\"))\n"));
+    }
+
+    #[test]
+    fn graphviz_fallback() {
+        let md = "```dot\ndigraph { a -> b }\n```";
+        // Disabled by default: plain code block
+        assert!(!markdown_to_typst(md).contains("image("));
+
+        // A missing Graphviz install reports the error and keeps the source
+        let mut config = Config::compiled_default();
+        config.diagrams.graphviz = true;
+        config.diagrams.dot_path = "nonexistent-dot-binary".to_string();
+        let result = markdown_to_typst_with_config(md, &config);
+        assert!(result.contains("#text(fill: red)[Failed to run nonexistent-dot-binary"));
+        assert!(result.contains("```dot\ndigraph { a -> b }\n```"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn graphviz_cache() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // A stand-in for `dot` that counts its runs
+        let dir = std::env::temp_dir().join("pdf_core_graphviz_cache");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let runs = dir.join("runs");
+        let dot = dir.join("dot");
+        std::fs::write(
+            &dot,
+            format!(
+                "#!/bin/sh\ncat > /dev/null\necho run >> '{}'\necho '<svg xmlns=\"http://www.w3.org/2000/svg\"/>'\n",
+                runs.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&dot, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut config = Config::compiled_default();
+        config.diagrams.graphviz = true;
+        config.diagrams.dot_path = dot.display().to_string();
+        let md = "```dot\ndigraph { cached -> once }\n```";
+        for _ in 0..2 {
+            let result = markdown_to_typst_with_config(md, &config);
+            assert!(result.contains("#align(center, image(bytes(\"<svg"));
+        }
+        assert_eq!(std::fs::read_to_string(&runs).unwrap(), "run\n");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn qrcode_directive() {
+        let result = markdown_to_typst("@qrcode(https://example.com/?a=b, size=2cm)");
+        assert!(result.contains("#image(bytes(\"<?xml"));
+        assert!(
+            result.contains(
+                "format: \"svg\", width: 2cm, alt: \"QR code: https://example.com/?a=b\")"
+            )
+        );
+
+        // Only the trailing `key=value` parts are options, or none after a quoted argument
+        let result = markdown_to_typst("@qrcode(https://example.com/?a=1,2)");
+        assert!(result.contains("alt: \"QR code: https://example.com/?a=1,2\")"));
+        let result = markdown_to_typst("@qrcode(\"https://example.com/?a=1,b=2\", size=1in)");
+        assert!(result.contains("width: 1in, alt: \"QR code: https://example.com/?a=1,b=2\")"));
+
+        // The size is pasted into the markup
+        let result = markdown_to_typst("@qrcode(https://example.com, size=3cm))");
+        assert!(result.contains("\\[QR code size is a length like 3cm, not \"3cm)\"\\]"));
+        assert!(!result.contains("#image("));
+
+        // Inline mentions are left alone
+        assert!(markdown_to_typst("see @qrcode(x) here").contains("see \\@qrcode(x) here"));
+    }
+
+    #[test]
+    fn composes_text_but_not_code() {
+        let result = markdown_to_typst("Cafe\u{301} `e\u{301}`\n\n```\ne\u{301}\n```");
+        assert!(result.contains("Café"));
+        assert!(result.contains("`e\u{301}`"));
+        assert!(result.contains("\ne\u{301}\n"));
+    }
+
+    #[test]
+    fn table_alignment() {
+        let md = "| A | B | C |\n|:--|:-:|--:|\n| 1 | 2 | 3 |";
+        assert!(markdown_to_typst(md).contains("  align: (left, center, right),\n"));
+    }
+
+    #[test]
+    fn table_footer() {
+        let md = "| Item | Cost |\n|---|---|\n| Rent | 10 |\n|===|===|\n| Total | 10 |";
+        assert!(markdown_to_typst(md).contains(
+            "#block[\n#table(\n  columns: 2,\n  [*Item*],\n  [*Cost*],\n  [Rent],\n  [10],\n  table.footer(\n    table.hline(stroke: 1.5pt),\n    [#strong[Total]],\n    [#strong[10]],\n  ),\n)"
+        ));
+
+        // From an HTML table's `<tfoot>`, even when written before the body
+        let html = "<table>\n<tr><th>Item</th></tr>\n<tfoot><tr><td>Sum</td></tr></tfoot>\n<tr><td>Rent</td></tr>\n</table>";
+        assert!(markdown_to_typst(html).contains(
+            "  [Rent],\n  table.footer(\n    table.hline(stroke: 1.5pt),\n    [#strong[Sum]],\n  ),\n"
+        ));
+
+        // Without a separator, `===` cells are content
+        assert!(markdown_to_typst("| A |\n|---|\n| === x |").contains("#block(breakable: false)"));
+    }
+
+    #[test]
+    fn table_style() {
+        let md = "| A | B |\n|---|---|\n| 1 | 2 |\n| 3 | 4 |";
+        let mut config = Config::compiled_default();
+        config.tables.striped = true;
+        assert!(
+            markdown_to_typst_with_config(md, &config).contains(
+                "#table(\n  columns: 2,\n  fill: (_, y) => if calc.odd(y) { luma(242) },\n"
+            )
+        );
+
+        // A comment right before a table overrides the config for it alone
+        let styled =
+            format!("<!-- table: striped=false width=full font-size=8pt -->\n{md}\n\n{md}");
+        let result = markdown_to_typst_with_config(&styled, &config);
+        assert!(result.contains(
+            "#block(breakable: false)[\n#show table: set text(size: 8pt)\n#table(\n  columns: (1fr,) * 2,\n  [*A*],"
+        ));
+        assert_eq!(result.matches("calc.odd(y)").count(), 1);
+
+        // A size that isn't a length would break the whole document
+        let invalid = format!("<!-- table: font-size=big -->\n{md}");
+        assert!(!markdown_to_typst(&invalid).contains("big"));
+
+        // Not when something comes between them
+        let apart = format!("<!-- table: width=full -->\n\nText\n\n{md}");
+        assert!(!markdown_to_typst(&apart).contains("1fr"));
+    }
+
+    #[test]
+    fn csv_include() {
+        let dir = std::env::temp_dir();
+        let path = dir.join("pdf_core_csv_include.csv");
+        std::fs::write(&path, "Name,Score\nAda,\"1,5\"\n").unwrap();
+        let (blocks, lines) = crate::parser::parse_with_resolver(
+            "@csv(pdf_core_csv_include.csv, align=lr)",
+            &crate::FsResolver::new(&dir),
+        );
+        let result = blocks_to_typst_mapped(&blocks, &lines, &Config::compiled_default()).0;
+        assert!(result.contains(
+            "#table(\n  columns: 2,\n  align: (left, right),\n  [*Name*],\n  [*Score*],\n  [Ada],\n  [1,5],\n)"
+        ));
+
+        let missing = markdown_to_typst("@csv(does-not-exist.csv)");
+        assert!(missing.contains("\\[Failed to read CSV does-not-exist.csv"));
+        // Only files below the working directory by default
+        let outside = markdown_to_typst(&format!("@csv({})", path.display()));
+        assert!(outside.contains("\\[Failed to read CSV"));
+    }
+
+    #[test]
+    fn rebase_paths() {
+        let dir = std::path::Path::new("/docs/part 2");
+        let md = "---\ntitle: Two\n---\n![Logo](logo.png) ![](https://example.com/a.png)\n\n![Chart](<chart one.svg> \"Sales\")\n\n@csv(data.csv, align=lr)\n\n`@csv(code.csv)`\n\n![Abs](/abs.png)";
+        let result = crate::rebase_paths(md, dir);
+        assert_eq!(
+            result,
+            "---\ntitle: Two\n---\n![Logo](</docs/part 2/logo.png>) ![](https://example.com/a.png)\n\n![Chart](</docs/part 2/chart one.svg> \"Sales\")\n\n@csv(/docs/part 2/data.csv, align=lr)\n\n`@csv(code.csv)`\n\n![Abs](/abs.png)"
+        );
+        assert!(
+            markdown_to_typst(&result)
+                .contains("image(\"/docs/part 2/chart one.svg\", alt: \"Chart\")")
+        );
+    }
+
+    #[test]
+    fn images() {
+        let md = "![Logo](logo.png)\n\nText ![inline](x.png) here\n\n![Chart](chart.svg \"Sales\")";
+        let result = markdown_to_typst(md);
+        assert!(result.contains("#align(center, image(\"logo.png\", alt: \"Logo\"))\n\n"));
+        assert!(result.contains("Text inline here\n\n"));
+        assert!(
+            result.contains("#figure(image(\"chart.svg\", alt: \"Chart\"), caption: [Sales])\n\n")
+        );
+
+        // The caption stands in for missing alt text
+        assert!(
+            markdown_to_typst("![](chart.svg \"Sales\")")
+                .contains("image(\"chart.svg\", alt: \"Sales\")")
+        );
+
+        let result = markdown_to_typst("![Dot](data:image/png;base64,iVBORw0KGgo=)");
+        assert!(result.contains("image(\"data-uri-"));
+        assert!(result.contains(".png\", alt: \"Dot\")"));
+    }
+
+    #[test]
+    fn csv_from_resolver() {
+        struct Memory;
+        impl ResourceResolver for Memory {
+            fn resolve(&self, path: &str) -> Result<Vec<u8>, String> {
+                match path {
+                    "scores.csv" => Ok(b"Name,Score\nAda,10\n".to_vec()),
+                    _ => Err("not found".to_string()),
+                }
+            }
+        }
+        let (blocks, _) = crate::parser::parse_with_resolver("@csv(scores.csv)", &Memory);
+        let result = crate::blocks_to_typst(&blocks, &Config::compiled_default());
+        assert!(result.contains("  [*Name*],\n  [*Score*],\n  [Ada],\n  [10],\n"));
+    }
+
+    #[test]
+    fn source_map_lines() {
+        let md = "---\ntitle: Doc\n---\n# Title\n\nPara one.\n\n- item\n";
+        let (blocks, lines) = crate::parser::parse_with_lines(md);
+        assert_eq!(lines, vec![4, 6, 8]);
+
+        let (out, map) = blocks_to_typst_mapped(&blocks, &lines, &Config::compiled_default());
+        assert_eq!(map.line_for_offset(out.find("Para one").unwrap()), Some(6));
+        assert_eq!(map.line_for_offset(out.find("- item").unwrap()), Some(8));
+        assert_eq!(map.line_for_offset(0), None);
+    }
+
+    #[test]
+    fn keep_regions() {
+        let md = "<!-- keep -->\nIntro:\n\n| a |\n|---|\n| 1 |\n<!-- /keep -->\n\nAfter.\n";
+        let result = markdown_to_typst(md);
+        assert!(result.contains("#block(breakable: false)[\nIntro:\n\n#block"));
+        assert!(result.ends_with(")\n]\n\n]\n\nAfter.\n\n"));
+
+        // Unclosed regions end before a page break and at the end of the document
+        let md = "<!-- keep -->\nOne.\n\n---pagebreak---\n\n<!-- /keep -->\n<!-- keep -->\nTwo.\n";
+        assert!(markdown_to_typst(md).ends_with(
+            "#block(breakable: false)[\nOne.\n\n]\n\n#pagebreak()\n\n#block(breakable: false)[\nTwo.\n\n]\n\n"
+        ));
+    }
+
+    #[test]
+    fn details() {
+        let md = "<details>\n<summary>More <b>info</b></summary>\n\nBody *text*.\n\n</details>\n\n<details><summary>Raw</summary>\nPlain body\n</details>\n\nAfter.\n";
+        let result = markdown_to_typst(md);
+        assert!(result.contains(
+            "#block(width: 100%, stroke: (left: 0.5pt + gray), inset: (left: 1em, y: 0.3em))[\n*More info*\n\nBody _text_.\n\n]\n\n"
+        ));
+        assert!(result.contains("[\n*Raw*\n\nPlain body\n\n]\n\n"));
+
+        let mut config = Config::compiled_default();
+        config.document.omit_details = true;
+        let result = markdown_to_typst_with_config(md, &config);
+        assert!(!result.contains("stroke"));
+        assert!(!result.contains("body"));
+        assert!(result.contains("After."));
+    }
+
+    #[test]
+    fn wide_regions() {
+        let md = "Text.\n\n<!-- wide -->\n| a | b |\n|---|---|\n<!-- /wide -->\n";
+        let mut config = Config::compiled_default();
+        assert!(!markdown_to_typst_with_config(md, &config).contains("#place"));
+
+        config.page.columns = Some(2);
+        let result = markdown_to_typst_with_config(md, &config);
+        assert!(result.contains("#set page(columns: 2)\n"));
+        assert!(result.contains("Text.\n\n#place(auto, scope: \"parent\", float: true)[\n#block"));
+        assert!(result.ends_with(")\n]\n\n]\n\n"));
+    }
+
+    #[test]
+    fn sidenotes() {
+        let result = markdown_to_typst("Claim.^[margin: From the 2020 survey.] More.");
+        assert!(result.contains("#set page(margin: (right: 4.5cm + 2 * 0.8cm))\n"));
+        assert!(result.contains("Claim.#sidenote[From the 2020 survey.] More.\n\n"));
+        assert!(!markdown_to_typst("x^[not a note]").contains("sidenote"));
+    }
+
+    #[test]
+    fn main_matter() {
+        let md = "Preface.\n\n---mainmatter---\n\n# One\n\n---mainmatter---\n";
+        let result = markdown_to_typst(md);
+        assert!(result.contains("#set page(numbering: \"i\")\n"));
+        assert!(result.contains(
+            "Preface.\n\n#set page(numbering: \"1\")\n#counter(page).update(1)\n\n#block"
+        ));
+        assert_eq!(result.matches("update(1)").count(), 1);
+    }
+
     #[test]
     fn horizontal_rule() {
         assert_eq!(
@@ -0,0 +1,66 @@
+use std::borrow::Cow;
+
+use unicode_normalization::{IsNormalized, UnicodeNormalization, is_nfc_quick};
+
+use crate::config::Config;
+
+/// Compose characters (NFC), so that e.g. "e" followed by a combining accent is
+/// laid out as the precomposed "é" that fonts are more likely to have. Only
+/// for text: code keeps the characters as written.
+pub(crate) fn nfc(text: &str) -> Cow<'_, str> {
+    match is_nfc_quick(text.chars()) {
+        IsNormalized::Yes => Cow::Borrowed(text),
+        IsNormalized::No | IsNormalized::Maybe => Cow::Owned(text.nfc().collect()),
+    }
+}
+
+/// With `typography.clean_characters`, replace characters that copy-pasted
+/// text brings along and that lay out unexpectedly: exotic spaces become
+/// plain ones, non-breaking hyphens plain hyphens, and zero-width characters
+/// (spaces, joiners, byte order marks) are dropped. No-break spaces stay, as
+/// they're usually meant.
+pub(crate) fn clean<'a>(text: &'a str, config: &Config) -> Cow<'a, str> {
+    if !config.typography.clean_characters || !text.chars().any(|ch| replacement(ch).is_some()) {
+        return Cow::Borrowed(text);
+    }
+    Cow::Owned(
+        text.chars()
+            .filter_map(|ch| replacement(ch).unwrap_or(Some(ch)))
+            .collect(),
+    )
+}
+
+/// What a problematic character becomes (`Some(None)` drops it), or `None` to
+/// keep it.
+fn replacement(ch: char) -> Option<Option<char>> {
+    match ch {
+        '\u{1680}' | '\u{2000}'..='\u{200A}' | '\u{205F}' | '\u{3000}' => Some(Some(' ')),
+        '\u{2011}' => Some(Some('-')),
+        '\u{200B}' | '\u{200C}' | '\u{200D}' | '\u{2060}' | '\u{FEFF}' => Some(None),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use super::{clean, nfc};
+    use crate::config::Config;
+
+    #[test]
+    fn composes_characters() {
+        assert_eq!(nfc("Cafe\u{301}"), "Café");
+        assert!(matches!(nfc("Café"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn cleans_pasted_text() {
+        let mut config = Config::compiled_default();
+        let pasted = "\u{FEFF}non\u{2011}breaking\u{2003}em\u{200B}space\u{00A0}kept";
+        assert!(matches!(clean(pasted, &config), Cow::Borrowed(_)));
+
+        config.typography.clean_characters = true;
+        assert_eq!(clean(pasted, &config), "non-breaking emspace\u{00A0}kept");
+    }
+}
@@ -0,0 +1,64 @@
+//! Browser bindings for `pdf_core`, built with `wasm-pack build crates/pdf_wasm --target web`.
+//!
+//! Everything runs in memory: fonts are bundled or passed in as bytes, and
+//! config is given as TOML text instead of being discovered on disk.
+
+use wasm_bindgen::prelude::*;
+
+/// Reusable converter holding config and fonts between renders.
+#[wasm_bindgen]
+pub struct Converter {
+    renderer: pdf_core::Renderer,
+}
+
+#[wasm_bindgen]
+impl Converter {
+    /// Create a converter, optionally merging a `config.toml` (as text) over the defaults.
+    #[wasm_bindgen(constructor)]
+    pub fn new(config: Option<String>) -> Result<Converter, JsError> {
+        let mut resolved = pdf_core::Config::compiled_default();
+        if let Some(config) = config {
+            resolved.merge_toml(&config).map_err(|e| JsError::new(&e))?;
+        }
+        Ok(Converter {
+            renderer: pdf_core::Renderer::new(resolved),
+        })
+    }
+
+    /// Add a font (TTF/OTF/TTC bytes) for documents to use.
+    #[wasm_bindgen(js_name = addFont)]
+    pub fn add_font(&mut self, data: Vec<u8>) -> Result<(), JsError> {
+        self.renderer.add_font(data).map_err(|e| JsError::new(&e))
+    }
+
+    /// Render markdown to PDF bytes.
+    #[wasm_bindgen(js_name = toPdf)]
+    pub fn to_pdf(&self, markdown: &str) -> Result<Vec<u8>, JsError> {
+        self.renderer
+            .render_pdf(markdown)
+            .map_err(|e| JsError::new(&e))
+    }
+
+    /// Render markdown to one SVG string per page.
+    #[wasm_bindgen(js_name = toSvg)]
+    pub fn to_svg(&self, markdown: &str) -> Result<Vec<String>, JsError> {
+        self.renderer
+            .render_svg(markdown)
+            .map(|doc| doc.pages)
+            .map_err(|e| JsError::new(&e))
+    }
+}
+
+/// Convert markdown to PDF bytes using the default config.
+#[wasm_bindgen(js_name = markdownToPdf)]
+pub fn markdown_to_pdf(markdown: &str) -> Result<Vec<u8>, JsError> {
+    pdf_core::markdown_to_pdf(markdown).map_err(|e| JsError::new(&e))
+}
+
+/// Convert markdown to one SVG string per page using the default config.
+#[wasm_bindgen(js_name = markdownToSvg)]
+pub fn markdown_to_svg(markdown: &str) -> Result<Vec<String>, JsError> {
+    pdf_core::markdown_to_svg(markdown)
+        .map(|doc| doc.pages)
+        .map_err(|e| JsError::new(&e))
+}